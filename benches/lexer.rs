@@ -0,0 +1,51 @@
+// Measures lexer throughput in MB/s on the test files and on a large generated source.
+// Run with `cargo bench --bench lexer`.
+
+#![allow(clippy::needless_return)]
+
+use go_compiler::lexer::Lexer;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const MIN_TIME: Duration = Duration::from_secs(2);
+
+// A few megabytes of declarations and statements in the style of the test files.
+fn generated_source() -> Vec<u8> {
+    let mut src = String::from("package main\n\nimport \"fmt\"\n\n");
+    for i in 0..20_000 {
+        src.push_str(&format!(
+            "// f{i} adds its arguments.\nfunc f{i}(a, b int, s string) (int, error) {{\n\tif a > b && s != \"x\\n\" {{\n\t\treturn a + b*0x{i:x}, nil\n\t}}\n\tfmt.Println(s, 3.5e{n}, 'c')\n\treturn a - b, fmt.Errorf(\"bad %d\", a)\n}}\n\n",
+            i = i,
+            n = i % 10
+        ));
+    }
+    return src.into_bytes();
+}
+
+fn bench(name: &str, src: &[u8]) {
+    let mut bytes = 0;
+    let mut tokens = 0;
+    let start = Instant::now();
+    while start.elapsed() < MIN_TIME {
+        tokens += black_box(Lexer::new(src)).count();
+        bytes += src.len();
+    }
+    let seconds = start.elapsed().as_secs_f64();
+    println!(
+        "{:<24} {:>8.1} MB/s {:>12.0} tokens/s",
+        name,
+        bytes as f64 / seconds / 1e6,
+        tokens as f64 / seconds
+    );
+}
+
+fn main() {
+    for path in [
+        "src/test_files/hugeparams.go",
+        "src/test_files/implements.go",
+    ] {
+        let src = std::fs::read(path).unwrap();
+        bench(path.trim_start_matches("src/test_files/"), &src);
+    }
+    bench("generated", &generated_source());
+}
@@ -0,0 +1,606 @@
+// The syntax tree built by the parser. The node types follow go/ast. Every node owns its
+// children and records the span of the source text it was parsed from. Names are
+// interned symbols, to be resolved with the interner the file was parsed with.
+
+use crate::intern::Symbol;
+use crate::source::Span;
+use crate::token::TokenTypes;
+
+#[cfg(test)]
+mod test;
+pub mod visit;
+pub mod visit_mut;
+
+pub use visit::Visitor;
+pub use visit_mut::VisitorMut;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct File {
+    // The span of the `package` keyword.
+    pub package: Span,
+    pub name: Ident,
+    pub decls: Vec<Decl>,
+    // The comments of the file in source order, when the lexer was asked to emit them.
+    pub comments: Vec<Comment>,
+    pub span: Span,
+}
+
+impl File {
+    // The import specs of the file, which all come before any other declaration.
+    pub fn imports(&self) -> impl Iterator<Item = &ImportSpec> {
+        return self.decls.iter().flat_map(|decl| {
+            let specs: &[Spec] = match decl {
+                Decl::Gen(gen) if gen.tok == TokenTypes::Import => &gen.specs,
+                _ => &[],
+            };
+            return specs.iter().filter_map(|spec| match spec {
+                Spec::Import(import) => Some(import),
+                _ => None,
+            });
+        });
+    }
+}
+
+// A `//` or `/* */` comment, with `text` as written in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comment {
+    pub text: String,
+    // Set for the comments that directly precede a declaration.
+    pub doc: bool,
+    pub span: Span,
+}
+
+// ========
+// Declarations
+// ========
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decl {
+    Bad(BadDecl),
+    Gen(GenDecl),
+    Func(Box<FuncDecl>),
+}
+
+impl Decl {
+    pub fn span(&self) -> Span {
+        match self {
+            Decl::Bad(decl) => return decl.span,
+            Decl::Gen(decl) => return decl.span,
+            Decl::Func(decl) => return decl.span,
+        }
+    }
+}
+
+// The source of a declaration that could not be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BadDecl {
+    pub span: Span,
+}
+
+// An import, const, type or var declaration, with its specs in parentheses or not.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenDecl {
+    pub tok: TokenTypes,
+    pub lparen: Option<Span>,
+    pub specs: Vec<Spec>,
+    pub rparen: Option<Span>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Spec {
+    Import(ImportSpec),
+    Value(ValueSpec),
+    Type(TypeSpec),
+}
+
+impl Spec {
+    pub fn span(&self) -> Span {
+        match self {
+            Spec::Import(spec) => return spec.span,
+            Spec::Value(spec) => return spec.span,
+            Spec::Type(spec) => return spec.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportSpec {
+    // The local name, which may also be "." or "_".
+    pub name: Option<Ident>,
+    pub path: BasicLit,
+    pub span: Span,
+}
+
+// A const or var spec.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueSpec {
+    pub names: Vec<Ident>,
+    pub ty: Option<Expr>,
+    pub values: Vec<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeSpec {
+    pub name: Ident,
+    pub type_params: Option<FieldList>,
+    // The `=` of an alias declaration.
+    pub assign: Option<Span>,
+    pub ty: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncDecl {
+    pub recv: Option<FieldList>,
+    pub name: Ident,
+    pub ty: FuncType,
+    // None for a function implemented outside Go.
+    pub body: Option<BlockStmt>,
+    pub span: Span,
+}
+
+// ========
+// Expressions and types
+// ========
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Bad(BadExpr),
+    Ident(Ident),
+    BasicLit(BasicLit),
+    CompositeLit(Box<CompositeLit>),
+    FuncLit(Box<FuncLit>),
+    Paren(Box<ParenExpr>),
+    Selector(Box<SelectorExpr>),
+    Index(Box<IndexExpr>),
+    IndexList(Box<IndexListExpr>),
+    Slice(Box<SliceExpr>),
+    TypeAssert(Box<TypeAssertExpr>),
+    Call(Box<CallExpr>),
+    Star(Box<StarExpr>),
+    Unary(Box<UnaryExpr>),
+    Binary(Box<BinaryExpr>),
+    KeyValue(Box<KeyValueExpr>),
+    Ellipsis(Box<Ellipsis>),
+    ArrayType(Box<ArrayType>),
+    StructType(Box<StructType>),
+    FuncType(Box<FuncType>),
+    InterfaceType(Box<InterfaceType>),
+    MapType(Box<MapType>),
+    ChanType(Box<ChanType>),
+}
+
+impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            Expr::Bad(x) => return x.span,
+            Expr::Ident(x) => return x.span,
+            Expr::BasicLit(x) => return x.span,
+            Expr::CompositeLit(x) => return x.span,
+            Expr::FuncLit(x) => return x.span,
+            Expr::Paren(x) => return x.span,
+            Expr::Selector(x) => return x.span,
+            Expr::Index(x) => return x.span,
+            Expr::IndexList(x) => return x.span,
+            Expr::Slice(x) => return x.span,
+            Expr::TypeAssert(x) => return x.span,
+            Expr::Call(x) => return x.span,
+            Expr::Star(x) => return x.span,
+            Expr::Unary(x) => return x.span,
+            Expr::Binary(x) => return x.span,
+            Expr::KeyValue(x) => return x.span,
+            Expr::Ellipsis(x) => return x.span,
+            Expr::ArrayType(x) => return x.span,
+            Expr::StructType(x) => return x.span,
+            Expr::FuncType(x) => return x.span,
+            Expr::InterfaceType(x) => return x.span,
+            Expr::MapType(x) => return x.span,
+            Expr::ChanType(x) => return x.span,
+        }
+    }
+}
+
+// The source of an expression that could not be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BadExpr {
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ident {
+    pub name: Symbol,
+    pub span: Span,
+}
+
+// An int, float, imaginary, rune or string literal, with `value` as written in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BasicLit {
+    pub kind: TokenTypes,
+    pub value: String,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompositeLit {
+    // None for a literal inside another composite literal whose type is elided.
+    pub ty: Option<Expr>,
+    pub lbrace: Span,
+    pub elts: Vec<Expr>,
+    pub rbrace: Span,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncLit {
+    pub ty: FuncType,
+    pub body: BlockStmt,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParenExpr {
+    pub x: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectorExpr {
+    pub x: Expr,
+    pub sel: Ident,
+    pub span: Span,
+}
+
+// `x[index]`, which is either an index expression or the instantiation of a generic
+// function or type with a single type argument.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexExpr {
+    pub x: Expr,
+    pub index: Expr,
+    pub span: Span,
+}
+
+// `x[A, B]`, the instantiation of a generic function or type with several type arguments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexListExpr {
+    pub x: Expr,
+    pub indices: Vec<Expr>,
+    pub span: Span,
+}
+
+// `x[low:high]` or `x[low:high:max]`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SliceExpr {
+    pub x: Expr,
+    pub low: Option<Expr>,
+    pub high: Option<Expr>,
+    pub max: Option<Expr>,
+    pub slice3: bool,
+    pub span: Span,
+}
+
+// `x.(T)`, or `x.(type)` in a type switch, where `ty` is None.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeAssertExpr {
+    pub x: Expr,
+    pub ty: Option<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallExpr {
+    pub fun: Expr,
+    pub args: Vec<Expr>,
+    // The `...` after the last argument.
+    pub ellipsis: Option<Span>,
+    pub span: Span,
+}
+
+// `*x`, either a pointer type or an indirection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StarExpr {
+    pub x: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnaryExpr {
+    pub op: TokenTypes,
+    pub x: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryExpr {
+    pub op: TokenTypes,
+    pub x: Expr,
+    pub y: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyValueExpr {
+    pub key: Expr,
+    pub value: Expr,
+    pub span: Span,
+}
+
+// `...T` as the type of a final parameter, or `...` as the length of an array literal.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ellipsis {
+    pub elt: Option<Expr>,
+    pub span: Span,
+}
+
+// `[len]T`, or `[]T` for a slice, where `len` is None.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArrayType {
+    pub len: Option<Expr>,
+    pub elt: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructType {
+    pub fields: FieldList,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuncType {
+    // The `func` keyword, which a method signature or a function declaration does not
+    // repeat here.
+    pub func: Option<Span>,
+    // The type parameters of a generic function.
+    pub type_params: Option<FieldList>,
+    pub params: FieldList,
+    pub results: Option<FieldList>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterfaceType {
+    // Methods have a name and a FuncType; embedded interfaces have neither names nor a
+    // FuncType.
+    pub methods: FieldList,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapType {
+    pub key: Expr,
+    pub value: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChanDir {
+    Both,
+    Send,
+    Recv,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChanType {
+    pub dir: ChanDir,
+    pub value: Expr,
+    pub span: Span,
+}
+
+// A list of struct fields, interface elements, parameters or results. `opening` and
+// `closing` are the enclosing braces or parentheses, which a single unnamed result
+// leaves out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldList {
+    pub opening: Option<Span>,
+    pub fields: Vec<Field>,
+    pub closing: Option<Span>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    // Empty for an embedded field or an unnamed parameter.
+    pub names: Vec<Ident>,
+    pub ty: Expr,
+    pub tag: Option<BasicLit>,
+    pub span: Span,
+}
+
+// ========
+// Statements
+// ========
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stmt {
+    Bad(BadStmt),
+    Decl(GenDecl),
+    Empty(EmptyStmt),
+    Labeled(Box<LabeledStmt>),
+    Expr(Expr),
+    Send(Box<SendStmt>),
+    IncDec(Box<IncDecStmt>),
+    Assign(Box<AssignStmt>),
+    Go(Box<GoStmt>),
+    Defer(Box<DeferStmt>),
+    Return(ReturnStmt),
+    Branch(BranchStmt),
+    Block(BlockStmt),
+    If(Box<IfStmt>),
+    Switch(Box<SwitchStmt>),
+    TypeSwitch(Box<TypeSwitchStmt>),
+    Select(SelectStmt),
+    For(Box<ForStmt>),
+    Range(Box<RangeStmt>),
+}
+
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Bad(s) => return s.span,
+            Stmt::Decl(s) => return s.span,
+            Stmt::Empty(s) => return s.span,
+            Stmt::Labeled(s) => return s.span,
+            Stmt::Expr(x) => return x.span(),
+            Stmt::Send(s) => return s.span,
+            Stmt::IncDec(s) => return s.span,
+            Stmt::Assign(s) => return s.span,
+            Stmt::Go(s) => return s.span,
+            Stmt::Defer(s) => return s.span,
+            Stmt::Return(s) => return s.span,
+            Stmt::Branch(s) => return s.span,
+            Stmt::Block(s) => return s.span,
+            Stmt::If(s) => return s.span,
+            Stmt::Switch(s) => return s.span,
+            Stmt::TypeSwitch(s) => return s.span,
+            Stmt::Select(s) => return s.span,
+            Stmt::For(s) => return s.span,
+            Stmt::Range(s) => return s.span,
+        }
+    }
+}
+
+// The source of a statement that could not be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BadStmt {
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmptyStmt {
+    // Set when the statement is only a semicolon inserted at a newline or before `}`.
+    pub implicit: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabeledStmt {
+    pub label: Ident,
+    pub stmt: Stmt,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SendStmt {
+    pub chan: Expr,
+    pub value: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncDecStmt {
+    pub x: Expr,
+    pub tok: TokenTypes,
+    pub span: Span,
+}
+
+// An assignment, a short variable declaration or an assignment operation such as `+=`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AssignStmt {
+    pub lhs: Vec<Expr>,
+    pub tok: TokenTypes,
+    pub rhs: Vec<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GoStmt {
+    pub call: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeferStmt {
+    pub call: Expr,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReturnStmt {
+    pub results: Vec<Expr>,
+    pub span: Span,
+}
+
+// A break, continue, goto or fallthrough statement.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BranchStmt {
+    pub tok: TokenTypes,
+    pub label: Option<Ident>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockStmt {
+    pub stmts: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfStmt {
+    pub init: Option<Stmt>,
+    pub cond: Expr,
+    pub body: BlockStmt,
+    // An IfStmt or a BlockStmt.
+    pub els: Option<Stmt>,
+    pub span: Span,
+}
+
+// A case of an expression or type switch; `list` is empty for the default case.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseClause {
+    pub list: Vec<Expr>,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SwitchStmt {
+    pub init: Option<Stmt>,
+    pub tag: Option<Expr>,
+    pub body: Vec<CaseClause>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeSwitchStmt {
+    pub init: Option<Stmt>,
+    // `x := y.(type)` or `y.(type)`.
+    pub assign: Stmt,
+    pub body: Vec<CaseClause>,
+    pub span: Span,
+}
+
+// A case of a select statement; `comm` is None for the default case.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommClause {
+    pub comm: Option<Stmt>,
+    pub body: Vec<Stmt>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectStmt {
+    pub body: Vec<CommClause>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ForStmt {
+    pub init: Option<Stmt>,
+    pub cond: Option<Expr>,
+    pub post: Option<Stmt>,
+    pub body: BlockStmt,
+    pub span: Span,
+}
+
+// `for key, value := range x`; `tok` is Define or Assign, or None without key and value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeStmt {
+    pub key: Option<Expr>,
+    pub value: Option<Expr>,
+    pub tok: Option<TokenTypes>,
+    pub x: Expr,
+    pub body: BlockStmt,
+    pub span: Span,
+}
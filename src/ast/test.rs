@@ -0,0 +1,174 @@
+use crate::ast::visit::{walk_expr, walk_func_decl};
+use crate::ast::visit_mut::walk_expr_mut;
+use crate::ast::*;
+use crate::intern::Interner;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::token::TokenTypes;
+use std::collections::HashMap;
+use std::fs;
+
+fn parse(src: &[u8]) -> (File, Interner) {
+    let mut lexer = Lexer::new(src);
+    let mut parser = Parser::new(&mut lexer);
+    let file = parser.parse_file();
+    assert!(!parser.has_errors(), "{:?}", parser.diagnostics());
+    return (file, parser.take_interner());
+}
+
+// Counts the calls of each selector such as `fmt.Printf`.
+struct CallCounter<'a> {
+    interner: &'a Interner,
+    calls: HashMap<String, usize>,
+}
+
+impl Visitor for CallCounter<'_> {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if let Expr::Call(call) = expr {
+            if let Expr::Selector(sel) = &call.fun {
+                if let Expr::Ident(pkg) = &sel.x {
+                    let name = format!(
+                        "{}.{}",
+                        self.interner.resolve(pkg.name),
+                        self.interner.resolve(sel.sel.name)
+                    );
+                    *self.calls.entry(name).or_default() += 1;
+                }
+            }
+        }
+        walk_expr(self, expr);
+    }
+}
+
+#[test]
+fn count_calls_in_test_file() {
+    let src = fs::read("src/test_files/implements.go").unwrap();
+    let (file, interner) = parse(&src);
+    let mut counter = CallCounter {
+        interner: &interner,
+        calls: HashMap::new(),
+    };
+    counter.visit_file(&file);
+    assert_eq!(counter.calls["fmt.Printf"], 2);
+    assert_eq!(counter.calls["log.Fatal"], 2);
+    assert_eq!(counter.calls["types.AssignableTo"], 2);
+    assert_eq!(counter.calls["types.NewPointer"], 2);
+}
+
+// Collects identifiers in the order they are visited, optionally without looking into
+// function literals.
+#[derive(Default)]
+struct Idents {
+    names: Vec<String>,
+    skip_literals: bool,
+    interner: Interner,
+}
+
+impl Visitor for Idents {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if self.skip_literals && matches!(expr, Expr::FuncLit(_)) {
+            return;
+        }
+        walk_expr(self, expr);
+    }
+
+    fn visit_ident(&mut self, ident: &Ident) {
+        self.names
+            .push(self.interner.resolve(ident.name).to_string());
+    }
+}
+
+#[test]
+fn walk_in_source_order() {
+    let src =
+        b"package p\nfunc (r *R) f(a int) (b T) {\n\tg := func(c U) {}\n\treturn a + len(g)\n}\n";
+    let (file, interner) = parse(src);
+    let mut idents = Idents {
+        interner,
+        ..Idents::default()
+    };
+    idents.visit_file(&file);
+    assert_eq!(
+        idents.names,
+        vec!["p", "r", "R", "f", "a", "int", "b", "T", "g", "c", "U", "a", "len", "g"]
+    );
+
+    idents.names.clear();
+    idents.skip_literals = true;
+    let Decl::Func(f) = &file.decls[0] else {
+        panic!("expected a function");
+    };
+    walk_func_decl(&mut idents, f);
+    assert_eq!(
+        idents.names,
+        vec!["r", "R", "f", "a", "int", "b", "T", "g", "a", "len", "g"]
+    );
+}
+
+#[test]
+fn walk_type_parameters() {
+    let src = b"package p\ntype L[T any] []T\nfunc F[K comparable](k K) M[K, L[T]] {}\n";
+    let (file, interner) = parse(src);
+    let mut idents = Idents {
+        interner,
+        ..Idents::default()
+    };
+    idents.visit_file(&file);
+    assert_eq!(
+        idents.names,
+        vec![
+            "p",
+            "L",
+            "T",
+            "any",
+            "T",
+            "F",
+            "K",
+            "comparable",
+            "k",
+            "K",
+            "M",
+            "K",
+            "L",
+            "T"
+        ]
+    );
+}
+
+// Replaces sums of two integer literals with their value.
+struct FoldSums;
+
+impl VisitorMut for FoldSums {
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+        let Expr::Binary(binary) = expr else {
+            return;
+        };
+        if let (Expr::BasicLit(x), Expr::BasicLit(y)) = (&binary.x, &binary.y) {
+            if let (Ok(x), Ok(y)) = (x.value.parse::<i64>(), y.value.parse::<i64>()) {
+                *expr = Expr::BasicLit(BasicLit {
+                    kind: TokenTypes::Int,
+                    value: (x + y).to_string(),
+                    span: binary.span,
+                });
+            }
+        }
+    }
+}
+
+#[test]
+fn rewrite_with_mutable_visitor() {
+    let src = b"package p\nvar x = []int{1 + 2 + 3, f(4 + 5), y + 6}\n";
+    let (mut file, _) = parse(src);
+    FoldSums.visit_file(&mut file);
+
+    let mut values = Vec::new();
+    struct Literals<'a>(&'a mut Vec<String>);
+    impl Visitor for Literals<'_> {
+        fn visit_basic_lit(&mut self, lit: &BasicLit) {
+            self.0.push(lit.value.clone());
+        }
+    }
+    Literals(&mut values).visit_file(&file);
+    assert_eq!(values, vec!["6", "9", "6"]);
+}
@@ -0,0 +1,354 @@
+// Read-only traversal of the syntax tree. Each `visit_*` method of a `Visitor` defaults to
+// the matching `walk_*` function, which visits the children of the node in source order.
+// An implementation overrides the methods for the nodes it is interested in and calls the
+// `walk_*` function from them to continue into the children, or leaves it out to skip
+// them.
+
+use crate::ast::*;
+
+pub trait Visitor {
+    fn visit_file(&mut self, file: &File) {
+        walk_file(self, file);
+    }
+
+    fn visit_decl(&mut self, decl: &Decl) {
+        walk_decl(self, decl);
+    }
+
+    fn visit_gen_decl(&mut self, decl: &GenDecl) {
+        walk_gen_decl(self, decl);
+    }
+
+    fn visit_func_decl(&mut self, decl: &FuncDecl) {
+        walk_func_decl(self, decl);
+    }
+
+    fn visit_spec(&mut self, spec: &Spec) {
+        walk_spec(self, spec);
+    }
+
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_block(&mut self, block: &BlockStmt) {
+        walk_block(self, block);
+    }
+
+    fn visit_case_clause(&mut self, clause: &CaseClause) {
+        walk_case_clause(self, clause);
+    }
+
+    fn visit_comm_clause(&mut self, clause: &CommClause) {
+        walk_comm_clause(self, clause);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_func_type(&mut self, ty: &FuncType) {
+        walk_func_type(self, ty);
+    }
+
+    fn visit_field_list(&mut self, list: &FieldList) {
+        walk_field_list(self, list);
+    }
+
+    fn visit_field(&mut self, field: &Field) {
+        walk_field(self, field);
+    }
+
+    fn visit_ident(&mut self, _ident: &Ident) {}
+
+    fn visit_basic_lit(&mut self, _lit: &BasicLit) {}
+}
+
+pub fn walk_file<V: Visitor + ?Sized>(v: &mut V, file: &File) {
+    v.visit_ident(&file.name);
+    for decl in &file.decls {
+        v.visit_decl(decl);
+    }
+}
+
+pub fn walk_decl<V: Visitor + ?Sized>(v: &mut V, decl: &Decl) {
+    match decl {
+        Decl::Bad(_) => {}
+        Decl::Gen(decl) => v.visit_gen_decl(decl),
+        Decl::Func(decl) => v.visit_func_decl(decl),
+    }
+}
+
+pub fn walk_gen_decl<V: Visitor + ?Sized>(v: &mut V, decl: &GenDecl) {
+    for spec in &decl.specs {
+        v.visit_spec(spec);
+    }
+}
+
+pub fn walk_func_decl<V: Visitor + ?Sized>(v: &mut V, decl: &FuncDecl) {
+    if let Some(recv) = &decl.recv {
+        v.visit_field_list(recv);
+    }
+    v.visit_ident(&decl.name);
+    v.visit_func_type(&decl.ty);
+    if let Some(body) = &decl.body {
+        v.visit_block(body);
+    }
+}
+
+pub fn walk_spec<V: Visitor + ?Sized>(v: &mut V, spec: &Spec) {
+    match spec {
+        Spec::Import(spec) => {
+            if let Some(name) = &spec.name {
+                v.visit_ident(name);
+            }
+            v.visit_basic_lit(&spec.path);
+        }
+        Spec::Value(spec) => {
+            for name in &spec.names {
+                v.visit_ident(name);
+            }
+            if let Some(ty) = &spec.ty {
+                v.visit_expr(ty);
+            }
+            for value in &spec.values {
+                v.visit_expr(value);
+            }
+        }
+        Spec::Type(spec) => {
+            v.visit_ident(&spec.name);
+            if let Some(params) = &spec.type_params {
+                v.visit_field_list(params);
+            }
+            v.visit_expr(&spec.ty);
+        }
+    }
+}
+
+pub fn walk_stmt<V: Visitor + ?Sized>(v: &mut V, stmt: &Stmt) {
+    match stmt {
+        Stmt::Bad(_) => {}
+        Stmt::Decl(decl) => v.visit_gen_decl(decl),
+        Stmt::Empty(_) => {}
+        Stmt::Labeled(s) => {
+            v.visit_ident(&s.label);
+            v.visit_stmt(&s.stmt);
+        }
+        Stmt::Expr(x) => v.visit_expr(x),
+        Stmt::Send(s) => {
+            v.visit_expr(&s.chan);
+            v.visit_expr(&s.value);
+        }
+        Stmt::IncDec(s) => v.visit_expr(&s.x),
+        Stmt::Assign(s) => {
+            for x in &s.lhs {
+                v.visit_expr(x);
+            }
+            for x in &s.rhs {
+                v.visit_expr(x);
+            }
+        }
+        Stmt::Go(s) => v.visit_expr(&s.call),
+        Stmt::Defer(s) => v.visit_expr(&s.call),
+        Stmt::Return(s) => {
+            for x in &s.results {
+                v.visit_expr(x);
+            }
+        }
+        Stmt::Branch(s) => {
+            if let Some(label) = &s.label {
+                v.visit_ident(label);
+            }
+        }
+        Stmt::Block(block) => v.visit_block(block),
+        Stmt::If(s) => {
+            if let Some(init) = &s.init {
+                v.visit_stmt(init);
+            }
+            v.visit_expr(&s.cond);
+            v.visit_block(&s.body);
+            if let Some(els) = &s.els {
+                v.visit_stmt(els);
+            }
+        }
+        Stmt::Switch(s) => {
+            if let Some(init) = &s.init {
+                v.visit_stmt(init);
+            }
+            if let Some(tag) = &s.tag {
+                v.visit_expr(tag);
+            }
+            for clause in &s.body {
+                v.visit_case_clause(clause);
+            }
+        }
+        Stmt::TypeSwitch(s) => {
+            if let Some(init) = &s.init {
+                v.visit_stmt(init);
+            }
+            v.visit_stmt(&s.assign);
+            for clause in &s.body {
+                v.visit_case_clause(clause);
+            }
+        }
+        Stmt::Select(s) => {
+            for clause in &s.body {
+                v.visit_comm_clause(clause);
+            }
+        }
+        Stmt::For(s) => {
+            if let Some(init) = &s.init {
+                v.visit_stmt(init);
+            }
+            if let Some(cond) = &s.cond {
+                v.visit_expr(cond);
+            }
+            if let Some(post) = &s.post {
+                v.visit_stmt(post);
+            }
+            v.visit_block(&s.body);
+        }
+        Stmt::Range(s) => {
+            if let Some(key) = &s.key {
+                v.visit_expr(key);
+            }
+            if let Some(value) = &s.value {
+                v.visit_expr(value);
+            }
+            v.visit_expr(&s.x);
+            v.visit_block(&s.body);
+        }
+    }
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(v: &mut V, block: &BlockStmt) {
+    for stmt in &block.stmts {
+        v.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_case_clause<V: Visitor + ?Sized>(v: &mut V, clause: &CaseClause) {
+    for x in &clause.list {
+        v.visit_expr(x);
+    }
+    for stmt in &clause.body {
+        v.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_comm_clause<V: Visitor + ?Sized>(v: &mut V, clause: &CommClause) {
+    if let Some(comm) = &clause.comm {
+        v.visit_stmt(comm);
+    }
+    for stmt in &clause.body {
+        v.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_expr<V: Visitor + ?Sized>(v: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Bad(_) => {}
+        Expr::Ident(ident) => v.visit_ident(ident),
+        Expr::BasicLit(lit) => v.visit_basic_lit(lit),
+        Expr::CompositeLit(x) => {
+            if let Some(ty) = &x.ty {
+                v.visit_expr(ty);
+            }
+            for elt in &x.elts {
+                v.visit_expr(elt);
+            }
+        }
+        Expr::FuncLit(x) => {
+            v.visit_func_type(&x.ty);
+            v.visit_block(&x.body);
+        }
+        Expr::Paren(x) => v.visit_expr(&x.x),
+        Expr::Selector(x) => {
+            v.visit_expr(&x.x);
+            v.visit_ident(&x.sel);
+        }
+        Expr::Index(x) => {
+            v.visit_expr(&x.x);
+            v.visit_expr(&x.index);
+        }
+        Expr::IndexList(x) => {
+            v.visit_expr(&x.x);
+            for index in &x.indices {
+                v.visit_expr(index);
+            }
+        }
+        Expr::Slice(x) => {
+            v.visit_expr(&x.x);
+            for index in [&x.low, &x.high, &x.max].into_iter().flatten() {
+                v.visit_expr(index);
+            }
+        }
+        Expr::TypeAssert(x) => {
+            v.visit_expr(&x.x);
+            if let Some(ty) = &x.ty {
+                v.visit_expr(ty);
+            }
+        }
+        Expr::Call(x) => {
+            v.visit_expr(&x.fun);
+            for arg in &x.args {
+                v.visit_expr(arg);
+            }
+        }
+        Expr::Star(x) => v.visit_expr(&x.x),
+        Expr::Unary(x) => v.visit_expr(&x.x),
+        Expr::Binary(x) => {
+            v.visit_expr(&x.x);
+            v.visit_expr(&x.y);
+        }
+        Expr::KeyValue(x) => {
+            v.visit_expr(&x.key);
+            v.visit_expr(&x.value);
+        }
+        Expr::Ellipsis(x) => {
+            if let Some(elt) = &x.elt {
+                v.visit_expr(elt);
+            }
+        }
+        Expr::ArrayType(x) => {
+            if let Some(len) = &x.len {
+                v.visit_expr(len);
+            }
+            v.visit_expr(&x.elt);
+        }
+        Expr::StructType(x) => v.visit_field_list(&x.fields),
+        Expr::FuncType(x) => v.visit_func_type(x),
+        Expr::InterfaceType(x) => v.visit_field_list(&x.methods),
+        Expr::MapType(x) => {
+            v.visit_expr(&x.key);
+            v.visit_expr(&x.value);
+        }
+        Expr::ChanType(x) => v.visit_expr(&x.value),
+    }
+}
+
+pub fn walk_func_type<V: Visitor + ?Sized>(v: &mut V, ty: &FuncType) {
+    if let Some(params) = &ty.type_params {
+        v.visit_field_list(params);
+    }
+    v.visit_field_list(&ty.params);
+    if let Some(results) = &ty.results {
+        v.visit_field_list(results);
+    }
+}
+
+pub fn walk_field_list<V: Visitor + ?Sized>(v: &mut V, list: &FieldList) {
+    for field in &list.fields {
+        v.visit_field(field);
+    }
+}
+
+pub fn walk_field<V: Visitor + ?Sized>(v: &mut V, field: &Field) {
+    for name in &field.names {
+        v.visit_ident(name);
+    }
+    v.visit_expr(&field.ty);
+    if let Some(tag) = &field.tag {
+        v.visit_basic_lit(tag);
+    }
+}
@@ -0,0 +1,352 @@
+// Traversal of the syntax tree that may change it in place, for rewriting passes. It
+// mirrors `visit`: each `visit_*` method defaults to the matching `walk_*_mut` function,
+// which visits the children of the node in source order.
+
+use crate::ast::*;
+
+pub trait VisitorMut {
+    fn visit_file(&mut self, file: &mut File) {
+        walk_file_mut(self, file);
+    }
+
+    fn visit_decl(&mut self, decl: &mut Decl) {
+        walk_decl_mut(self, decl);
+    }
+
+    fn visit_gen_decl(&mut self, decl: &mut GenDecl) {
+        walk_gen_decl_mut(self, decl);
+    }
+
+    fn visit_func_decl(&mut self, decl: &mut FuncDecl) {
+        walk_func_decl_mut(self, decl);
+    }
+
+    fn visit_spec(&mut self, spec: &mut Spec) {
+        walk_spec_mut(self, spec);
+    }
+
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        walk_stmt_mut(self, stmt);
+    }
+
+    fn visit_block(&mut self, block: &mut BlockStmt) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_case_clause(&mut self, clause: &mut CaseClause) {
+        walk_case_clause_mut(self, clause);
+    }
+
+    fn visit_comm_clause(&mut self, clause: &mut CommClause) {
+        walk_comm_clause_mut(self, clause);
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+
+    fn visit_func_type(&mut self, ty: &mut FuncType) {
+        walk_func_type_mut(self, ty);
+    }
+
+    fn visit_field_list(&mut self, list: &mut FieldList) {
+        walk_field_list_mut(self, list);
+    }
+
+    fn visit_field(&mut self, field: &mut Field) {
+        walk_field_mut(self, field);
+    }
+
+    fn visit_ident(&mut self, _ident: &mut Ident) {}
+
+    fn visit_basic_lit(&mut self, _lit: &mut BasicLit) {}
+}
+
+pub fn walk_file_mut<V: VisitorMut + ?Sized>(v: &mut V, file: &mut File) {
+    v.visit_ident(&mut file.name);
+    for decl in &mut file.decls {
+        v.visit_decl(decl);
+    }
+}
+
+pub fn walk_decl_mut<V: VisitorMut + ?Sized>(v: &mut V, decl: &mut Decl) {
+    match decl {
+        Decl::Bad(_) => {}
+        Decl::Gen(decl) => v.visit_gen_decl(decl),
+        Decl::Func(decl) => v.visit_func_decl(decl),
+    }
+}
+
+pub fn walk_gen_decl_mut<V: VisitorMut + ?Sized>(v: &mut V, decl: &mut GenDecl) {
+    for spec in &mut decl.specs {
+        v.visit_spec(spec);
+    }
+}
+
+pub fn walk_func_decl_mut<V: VisitorMut + ?Sized>(v: &mut V, decl: &mut FuncDecl) {
+    if let Some(recv) = &mut decl.recv {
+        v.visit_field_list(recv);
+    }
+    v.visit_ident(&mut decl.name);
+    v.visit_func_type(&mut decl.ty);
+    if let Some(body) = &mut decl.body {
+        v.visit_block(body);
+    }
+}
+
+pub fn walk_spec_mut<V: VisitorMut + ?Sized>(v: &mut V, spec: &mut Spec) {
+    match spec {
+        Spec::Import(spec) => {
+            if let Some(name) = &mut spec.name {
+                v.visit_ident(name);
+            }
+            v.visit_basic_lit(&mut spec.path);
+        }
+        Spec::Value(spec) => {
+            for name in &mut spec.names {
+                v.visit_ident(name);
+            }
+            if let Some(ty) = &mut spec.ty {
+                v.visit_expr(ty);
+            }
+            for value in &mut spec.values {
+                v.visit_expr(value);
+            }
+        }
+        Spec::Type(spec) => {
+            v.visit_ident(&mut spec.name);
+            if let Some(params) = &mut spec.type_params {
+                v.visit_field_list(params);
+            }
+            v.visit_expr(&mut spec.ty);
+        }
+    }
+}
+
+pub fn walk_stmt_mut<V: VisitorMut + ?Sized>(v: &mut V, stmt: &mut Stmt) {
+    match stmt {
+        Stmt::Bad(_) => {}
+        Stmt::Decl(decl) => v.visit_gen_decl(decl),
+        Stmt::Empty(_) => {}
+        Stmt::Labeled(s) => {
+            v.visit_ident(&mut s.label);
+            v.visit_stmt(&mut s.stmt);
+        }
+        Stmt::Expr(x) => v.visit_expr(x),
+        Stmt::Send(s) => {
+            v.visit_expr(&mut s.chan);
+            v.visit_expr(&mut s.value);
+        }
+        Stmt::IncDec(s) => v.visit_expr(&mut s.x),
+        Stmt::Assign(s) => {
+            for x in &mut s.lhs {
+                v.visit_expr(x);
+            }
+            for x in &mut s.rhs {
+                v.visit_expr(x);
+            }
+        }
+        Stmt::Go(s) => v.visit_expr(&mut s.call),
+        Stmt::Defer(s) => v.visit_expr(&mut s.call),
+        Stmt::Return(s) => {
+            for x in &mut s.results {
+                v.visit_expr(x);
+            }
+        }
+        Stmt::Branch(s) => {
+            if let Some(label) = &mut s.label {
+                v.visit_ident(label);
+            }
+        }
+        Stmt::Block(block) => v.visit_block(block),
+        Stmt::If(s) => {
+            if let Some(init) = &mut s.init {
+                v.visit_stmt(init);
+            }
+            v.visit_expr(&mut s.cond);
+            v.visit_block(&mut s.body);
+            if let Some(els) = &mut s.els {
+                v.visit_stmt(els);
+            }
+        }
+        Stmt::Switch(s) => {
+            if let Some(init) = &mut s.init {
+                v.visit_stmt(init);
+            }
+            if let Some(tag) = &mut s.tag {
+                v.visit_expr(tag);
+            }
+            for clause in &mut s.body {
+                v.visit_case_clause(clause);
+            }
+        }
+        Stmt::TypeSwitch(s) => {
+            if let Some(init) = &mut s.init {
+                v.visit_stmt(init);
+            }
+            v.visit_stmt(&mut s.assign);
+            for clause in &mut s.body {
+                v.visit_case_clause(clause);
+            }
+        }
+        Stmt::Select(s) => {
+            for clause in &mut s.body {
+                v.visit_comm_clause(clause);
+            }
+        }
+        Stmt::For(s) => {
+            if let Some(init) = &mut s.init {
+                v.visit_stmt(init);
+            }
+            if let Some(cond) = &mut s.cond {
+                v.visit_expr(cond);
+            }
+            if let Some(post) = &mut s.post {
+                v.visit_stmt(post);
+            }
+            v.visit_block(&mut s.body);
+        }
+        Stmt::Range(s) => {
+            if let Some(key) = &mut s.key {
+                v.visit_expr(key);
+            }
+            if let Some(value) = &mut s.value {
+                v.visit_expr(value);
+            }
+            v.visit_expr(&mut s.x);
+            v.visit_block(&mut s.body);
+        }
+    }
+}
+
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(v: &mut V, block: &mut BlockStmt) {
+    for stmt in &mut block.stmts {
+        v.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_case_clause_mut<V: VisitorMut + ?Sized>(v: &mut V, clause: &mut CaseClause) {
+    for x in &mut clause.list {
+        v.visit_expr(x);
+    }
+    for stmt in &mut clause.body {
+        v.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_comm_clause_mut<V: VisitorMut + ?Sized>(v: &mut V, clause: &mut CommClause) {
+    if let Some(comm) = &mut clause.comm {
+        v.visit_stmt(comm);
+    }
+    for stmt in &mut clause.body {
+        v.visit_stmt(stmt);
+    }
+}
+
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(v: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Bad(_) => {}
+        Expr::Ident(ident) => v.visit_ident(ident),
+        Expr::BasicLit(lit) => v.visit_basic_lit(lit),
+        Expr::CompositeLit(x) => {
+            if let Some(ty) = &mut x.ty {
+                v.visit_expr(ty);
+            }
+            for elt in &mut x.elts {
+                v.visit_expr(elt);
+            }
+        }
+        Expr::FuncLit(x) => {
+            v.visit_func_type(&mut x.ty);
+            v.visit_block(&mut x.body);
+        }
+        Expr::Paren(x) => v.visit_expr(&mut x.x),
+        Expr::Selector(x) => {
+            v.visit_expr(&mut x.x);
+            v.visit_ident(&mut x.sel);
+        }
+        Expr::Index(x) => {
+            v.visit_expr(&mut x.x);
+            v.visit_expr(&mut x.index);
+        }
+        Expr::IndexList(x) => {
+            v.visit_expr(&mut x.x);
+            for index in &mut x.indices {
+                v.visit_expr(index);
+            }
+        }
+        Expr::Slice(x) => {
+            v.visit_expr(&mut x.x);
+            for index in [&mut x.low, &mut x.high, &mut x.max].into_iter().flatten() {
+                v.visit_expr(index);
+            }
+        }
+        Expr::TypeAssert(x) => {
+            v.visit_expr(&mut x.x);
+            if let Some(ty) = &mut x.ty {
+                v.visit_expr(ty);
+            }
+        }
+        Expr::Call(x) => {
+            v.visit_expr(&mut x.fun);
+            for arg in &mut x.args {
+                v.visit_expr(arg);
+            }
+        }
+        Expr::Star(x) => v.visit_expr(&mut x.x),
+        Expr::Unary(x) => v.visit_expr(&mut x.x),
+        Expr::Binary(x) => {
+            v.visit_expr(&mut x.x);
+            v.visit_expr(&mut x.y);
+        }
+        Expr::KeyValue(x) => {
+            v.visit_expr(&mut x.key);
+            v.visit_expr(&mut x.value);
+        }
+        Expr::Ellipsis(x) => {
+            if let Some(elt) = &mut x.elt {
+                v.visit_expr(elt);
+            }
+        }
+        Expr::ArrayType(x) => {
+            if let Some(len) = &mut x.len {
+                v.visit_expr(len);
+            }
+            v.visit_expr(&mut x.elt);
+        }
+        Expr::StructType(x) => v.visit_field_list(&mut x.fields),
+        Expr::FuncType(x) => v.visit_func_type(x),
+        Expr::InterfaceType(x) => v.visit_field_list(&mut x.methods),
+        Expr::MapType(x) => {
+            v.visit_expr(&mut x.key);
+            v.visit_expr(&mut x.value);
+        }
+        Expr::ChanType(x) => v.visit_expr(&mut x.value),
+    }
+}
+
+pub fn walk_func_type_mut<V: VisitorMut + ?Sized>(v: &mut V, ty: &mut FuncType) {
+    if let Some(params) = &mut ty.type_params {
+        v.visit_field_list(params);
+    }
+    v.visit_field_list(&mut ty.params);
+    if let Some(results) = &mut ty.results {
+        v.visit_field_list(results);
+    }
+}
+
+pub fn walk_field_list_mut<V: VisitorMut + ?Sized>(v: &mut V, list: &mut FieldList) {
+    for field in &mut list.fields {
+        v.visit_field(field);
+    }
+}
+
+pub fn walk_field_mut<V: VisitorMut + ?Sized>(v: &mut V, field: &mut Field) {
+    for name in &mut field.names {
+        v.visit_ident(name);
+    }
+    v.visit_expr(&mut field.ty);
+    if let Some(tag) = &mut field.tag {
+        v.visit_basic_lit(tag);
+    }
+}
@@ -0,0 +1,333 @@
+// Build contexts: which files of a package are compiled for a given target, based on
+// `//go:build` constraints and `_GOOS`/`_GOARCH` file name suffixes.
+
+use crate::diagnostic::{codes, Diagnostic};
+use crate::lexer::directive::DirectiveKind;
+use crate::lexer::Lexer;
+use crate::source::SourceFile;
+use crate::token::TokenTypes;
+use std::path::Path;
+
+#[cfg(test)]
+mod test;
+
+// The newest Go release whose `go1.N` tag is satisfied.
+pub const GO_RELEASE: u32 = 22;
+
+pub const KNOWN_OS: &[&str] = &[
+    "aix",
+    "android",
+    "darwin",
+    "dragonfly",
+    "freebsd",
+    "hurd",
+    "illumos",
+    "ios",
+    "js",
+    "linux",
+    "nacl",
+    "netbsd",
+    "openbsd",
+    "plan9",
+    "solaris",
+    "wasip1",
+    "windows",
+    "zos",
+];
+
+pub const KNOWN_ARCH: &[&str] = &[
+    "386",
+    "amd64",
+    "amd64p32",
+    "arm",
+    "armbe",
+    "arm64",
+    "arm64be",
+    "loong64",
+    "mips",
+    "mipsle",
+    "mips64",
+    "mips64le",
+    "mips64p32",
+    "mips64p32le",
+    "ppc",
+    "ppc64",
+    "ppc64le",
+    "riscv",
+    "riscv64",
+    "s390",
+    "s390x",
+    "sparc",
+    "sparc64",
+    "wasm",
+];
+
+// The operating systems matched by the `unix` tag.
+const UNIX_OS: &[&str] = &[
+    "aix",
+    "android",
+    "darwin",
+    "dragonfly",
+    "freebsd",
+    "hurd",
+    "illumos",
+    "ios",
+    "linux",
+    "netbsd",
+    "openbsd",
+    "solaris",
+];
+
+// A parsed `//go:build` expression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Constraint {
+    Tag(String),
+    Not(Box<Constraint>),
+    And(Box<Constraint>, Box<Constraint>),
+    Or(Box<Constraint>, Box<Constraint>),
+}
+
+impl Constraint {
+    // Parses an expression built from tags, `!`, `&&`, `||` and parentheses. As in Go,
+    // `&&` binds more tightly than `||`.
+    pub fn parse(text: &str) -> Result<Constraint, String> {
+        let mut parser = ConstraintParser { text, pos: 0 };
+        let constraint = parser.or()?;
+        parser.skip_space();
+        if parser.pos < text.len() {
+            return Err(format!("unexpected {:?}", &text[parser.pos..]));
+        }
+        return Ok(constraint);
+    }
+
+    pub fn eval(&self, has_tag: &dyn Fn(&str) -> bool) -> bool {
+        match self {
+            Constraint::Tag(tag) => return has_tag(tag),
+            Constraint::Not(x) => return !x.eval(has_tag),
+            Constraint::And(x, y) => return x.eval(has_tag) && y.eval(has_tag),
+            Constraint::Or(x, y) => return x.eval(has_tag) || y.eval(has_tag),
+        }
+    }
+}
+
+struct ConstraintParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl ConstraintParser<'_> {
+    fn or(&mut self) -> Result<Constraint, String> {
+        let mut x = self.and()?;
+        while self.eat("||") {
+            x = Constraint::Or(Box::new(x), Box::new(self.and()?));
+        }
+        return Ok(x);
+    }
+
+    fn and(&mut self) -> Result<Constraint, String> {
+        let mut x = self.not()?;
+        while self.eat("&&") {
+            x = Constraint::And(Box::new(x), Box::new(self.not()?));
+        }
+        return Ok(x);
+    }
+
+    fn not(&mut self) -> Result<Constraint, String> {
+        if self.eat("!") {
+            return Ok(Constraint::Not(Box::new(self.not()?)));
+        }
+        if self.eat("(") {
+            let x = self.or()?;
+            if !self.eat(")") {
+                return Err("missing )".to_string());
+            }
+            return Ok(x);
+        }
+
+        self.skip_space();
+        let rest = &self.text[self.pos..];
+        let len = rest
+            .find(|c: char| !(c.is_alphanumeric() || c == '_' || c == '.'))
+            .unwrap_or(rest.len());
+        if len == 0 {
+            return match rest.chars().next() {
+                Some(c) => Err(format!("unexpected {:?}", c)),
+                None => Err("unexpected end of expression".to_string()),
+            };
+        }
+        self.pos += len;
+        return Ok(Constraint::Tag(rest[..len].to_string()));
+    }
+
+    fn eat(&mut self, op: &str) -> bool {
+        self.skip_space();
+        if self.text[self.pos..].starts_with(op) {
+            self.pos += op.len();
+            return true;
+        }
+        return false;
+    }
+
+    fn skip_space(&mut self) {
+        let rest = &self.text[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+}
+
+// The target a package is built for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Context {
+    pub goos: String,
+    pub goarch: String,
+    // Extra tags, as given with `-tags` to the go command.
+    pub tags: Vec<String>,
+    pub release: u32,
+}
+
+impl Context {
+    pub fn new(goos: &str, goarch: &str) -> Context {
+        Context {
+            goos: goos.to_string(),
+            goarch: goarch.to_string(),
+            tags: Vec::new(),
+            release: GO_RELEASE,
+        }
+    }
+
+    // The context of the machine the compiler runs on, using Go's names for it.
+    pub fn host() -> Context {
+        let goos = match std::env::consts::OS {
+            "macos" => "darwin",
+            os => os,
+        };
+        let goarch = match std::env::consts::ARCH {
+            "x86" => "386",
+            "x86_64" => "amd64",
+            "aarch64" => "arm64",
+            "loongarch64" => "loong64",
+            "powerpc" => "ppc",
+            "powerpc64" => "ppc64",
+            "wasm32" => "wasm",
+            arch => arch,
+        };
+        return Context::new(goos, goarch);
+    }
+
+    pub fn with_tags(mut self, tags: &[&str]) -> Self {
+        self.tags = tags.iter().map(|t| t.to_string()).collect();
+        return self;
+    }
+
+    pub fn matches_tag(&self, tag: &str) -> bool {
+        if tag == self.goos || tag == self.goarch || self.tags.iter().any(|t| t == tag) {
+            return true;
+        }
+        match tag {
+            "unix" => return UNIX_OS.contains(&self.goos.as_str()),
+            "linux" => return self.goos == "android",
+            "darwin" => return self.goos == "ios",
+            "solaris" => return self.goos == "illumos",
+            _ => {}
+        }
+        return match tag.strip_prefix("go1.").map(str::parse::<u32>) {
+            Some(Ok(minor)) => minor >= 1 && minor <= self.release,
+            _ => false,
+        };
+    }
+
+    // Applies the `name_GOOS`, `name_GOARCH` and `name_GOOS_GOARCH` suffix rules to a
+    // file name. A `_test` suffix is ignored, and so is the part before the first `_`, so
+    // `linux.go` is built everywhere.
+    pub fn matches_file_name(&self, name: &str) -> bool {
+        let name = name.split('.').next().unwrap_or("");
+        let name = match name.find('_') {
+            Some(i) => &name[i..],
+            None => return true,
+        };
+        let mut parts: Vec<&str> = name.split('_').collect();
+        if parts.last() == Some(&"test") {
+            parts.pop();
+        }
+        let n = parts.len();
+        if n >= 2 && KNOWN_OS.contains(&parts[n - 2]) && KNOWN_ARCH.contains(&parts[n - 1]) {
+            return self.matches_tag(parts[n - 2]) && self.matches_tag(parts[n - 1]);
+        }
+        if n >= 1 && (KNOWN_OS.contains(&parts[n - 1]) || KNOWN_ARCH.contains(&parts[n - 1])) {
+            return self.matches_tag(parts[n - 1]);
+        }
+        return true;
+    }
+
+    // Reports whether a file is part of the package in this context. Files whose names
+    // start with `_` or `.` are always ignored, as the go command does.
+    pub fn includes(&self, file: &SourceFile) -> Result<bool, Diagnostic> {
+        let name = Path::new(&file.name)
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(&file.name);
+        if name.starts_with('_') || name.starts_with('.') || !self.matches_file_name(name) {
+            return Ok(false);
+        }
+        match build_constraint(file)? {
+            Some(constraint) => return Ok(constraint.eval(&|tag| self.matches_tag(tag))),
+            None => return Ok(true),
+        }
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        return Context::host();
+    }
+}
+
+// Finds and parses the `//go:build` line in the header of the file. As in Go, the header
+// ends at the last blank line before the package clause, so a directive in the package doc
+// comment does not count.
+pub fn build_constraint(file: &SourceFile) -> Result<Option<Constraint>, Diagnostic> {
+    let mut lexer = Lexer::new(&file.src).with_file(file.id).with_comments(true);
+    let mut header_end = 0;
+    let mut prev_end = 0;
+    for token in lexer.by_ref() {
+        // A blank line is a gap with two line breaks, or one at the start of the file.
+        let newlines = file.src[prev_end..token.span.start]
+            .iter()
+            .filter(|&&b| b == b'\n')
+            .count();
+        if newlines >= 2 || (prev_end == 0 && newlines == 1) {
+            header_end = prev_end;
+        }
+        if !matches!(
+            token.token_type,
+            TokenTypes::Comment | TokenTypes::DocComment
+        ) {
+            break;
+        }
+        prev_end = token.span.end;
+    }
+
+    let mut found = None;
+    for directive in lexer.directives() {
+        let expr = match &directive.kind {
+            DirectiveKind::Build(expr) if directive.span.end <= header_end => expr,
+            _ => continue,
+        };
+        if found.is_some() {
+            return Err(Diagnostic::error("Multiple //go:build lines.")
+                .with_code(codes::INVALID_BUILD_CONSTRAINT)
+                .with_primary(directive.span, "")
+                .with_note("a file can have at most one //go:build line"));
+        }
+        match Constraint::parse(expr) {
+            Ok(constraint) => found = Some(constraint),
+            Err(msg) => {
+                return Err(
+                    Diagnostic::error(&format!("Invalid //go:build constraint: {}.", msg))
+                        .with_code(codes::INVALID_BUILD_CONSTRAINT)
+                        .with_primary(directive.span, ""),
+                )
+            }
+        }
+    }
+    return Ok(found);
+}
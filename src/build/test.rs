@@ -0,0 +1,159 @@
+use crate::build::{build_constraint, Constraint, Context};
+use crate::diagnostic::codes;
+use crate::source::SourceMap;
+
+fn tag(name: &str) -> Box<Constraint> {
+    return Box::new(Constraint::Tag(name.to_string()));
+}
+
+fn eval(expr: &str, tags: &[&str]) -> bool {
+    return Constraint::parse(expr)
+        .unwrap()
+        .eval(&|t| tags.contains(&t));
+}
+
+#[test]
+fn parse_constraints() {
+    assert_eq!(
+        Constraint::parse("linux && amd64 || !cgo"),
+        Ok(Constraint::Or(
+            Box::new(Constraint::And(tag("linux"), tag("amd64"))),
+            Box::new(Constraint::Not(tag("cgo"))),
+        ))
+    );
+    assert_eq!(
+        Constraint::parse("!(a||b) && go1.21"),
+        Ok(Constraint::And(
+            Box::new(Constraint::Not(Box::new(Constraint::Or(
+                tag("a"),
+                tag("b")
+            )))),
+            tag("go1.21"),
+        ))
+    );
+}
+
+#[test]
+fn reject_malformed_constraints() {
+    assert!(Constraint::parse("").is_err());
+    assert!(Constraint::parse("linux &&").is_err());
+    assert!(Constraint::parse("(linux").is_err());
+    assert!(Constraint::parse("linux amd64").is_err());
+    assert!(Constraint::parse("linux & amd64").is_err());
+    assert!(Constraint::parse("linux,amd64").is_err());
+}
+
+#[test]
+fn evaluate_constraints() {
+    assert!(eval("linux && amd64", &["linux", "amd64"]));
+    assert!(!eval("linux && amd64", &["linux", "arm64"]));
+    assert!(eval("linux && (amd64 || arm64)", &["linux", "arm64"]));
+    assert!(eval("!windows", &["linux"]));
+    assert!(!eval("!windows || plan9", &["windows"]));
+    assert!(eval("a || b && c", &["a"]));
+    assert!(!eval("(a || b) && c", &["a"]));
+}
+
+#[test]
+fn match_tags() {
+    let linux = Context::new("linux", "amd64").with_tags(&["netgo"]);
+    assert!(linux.matches_tag("linux"));
+    assert!(linux.matches_tag("amd64"));
+    assert!(linux.matches_tag("unix"));
+    assert!(linux.matches_tag("netgo"));
+    assert!(linux.matches_tag("go1.1"));
+    assert!(linux.matches_tag("go1.22"));
+    assert!(!linux.matches_tag("go1.99"));
+    assert!(!linux.matches_tag("windows"));
+
+    let android = Context::new("android", "arm64");
+    assert!(android.matches_tag("linux"));
+    assert!(android.matches_tag("unix"));
+    assert!(!Context::new("windows", "amd64").matches_tag("unix"));
+    assert!(Context::new("ios", "arm64").matches_tag("darwin"));
+}
+
+#[test]
+fn file_name_suffixes() {
+    let linux = Context::new("linux", "amd64");
+    assert!(linux.matches_file_name("main.go"));
+    assert!(linux.matches_file_name("linux.go"));
+    assert!(linux.matches_file_name("file_linux.go"));
+    assert!(linux.matches_file_name("file_amd64.go"));
+    assert!(linux.matches_file_name("file_linux_amd64.go"));
+    assert!(linux.matches_file_name("file_linux_test.go"));
+    assert!(linux.matches_file_name("file_unknown.go"));
+    assert!(linux.matches_file_name("file_windows_unknown.go"));
+    assert!(!linux.matches_file_name("file_windows.go"));
+    assert!(!linux.matches_file_name("file_arm64.go"));
+    assert!(!linux.matches_file_name("file_linux_arm64.go"));
+    assert!(!linux.matches_file_name("file_windows_amd64_test.go"));
+
+    // Only an exact GOOS matches a suffix, even where tags would also match.
+    let android = Context::new("android", "arm64");
+    assert!(android.matches_file_name("file_android.go"));
+    assert!(android.matches_file_name("file_linux.go"));
+    assert!(!android.matches_file_name("file_darwin.go"));
+}
+
+fn includes(context: &Context, name: &str, src: &str) -> bool {
+    let mut sources = SourceMap::new();
+    let id = sources.add(name, src.as_bytes().to_vec());
+    return context.includes(sources.get(id).unwrap()).unwrap();
+}
+
+#[test]
+fn include_files() {
+    let linux = Context::new("linux", "amd64");
+    let windows = Context::new("windows", "amd64");
+    let src = "// Copyright notice.\n\n//go:build linux || darwin\n\npackage p\n";
+    assert!(includes(&linux, "pkg/a.go", src));
+    assert!(!includes(&windows, "pkg/a.go", src));
+    assert!(!includes(&linux, "pkg/a_windows.go", "package p\n"));
+    assert!(!includes(&linux, "pkg/_a.go", "package p\n"));
+    assert!(!includes(&linux, "pkg/.a.go", "package p\n"));
+
+    // A //go:build line after the package clause is an ordinary comment.
+    assert!(includes(
+        &windows,
+        "b.go",
+        "package p\n\n//go:build linux\n"
+    ));
+
+    // So is one in the package doc comment, which no blank line separates from the
+    // package clause.
+    assert!(includes(
+        &windows,
+        "c.go",
+        "// Package p is a package.\n//go:build linux\npackage p\n"
+    ));
+    assert!(includes(
+        &windows,
+        "d.go",
+        "//go:build linux\n// Package p is a package.\npackage p\n"
+    ));
+    assert!(!includes(
+        &windows,
+        "e.go",
+        "//go:build linux\n\n// Package p is a package.\n\n/* More. */\npackage p\n"
+    ));
+}
+
+#[test]
+fn report_invalid_constraints() {
+    let mut sources = SourceMap::new();
+    let bad = sources.add("a.go", b"//go:build linux &&\n\npackage p\n".to_vec());
+    let twice = sources.add(
+        "b.go",
+        b"//go:build a\n//go:build b\n\npackage p\n".to_vec(),
+    );
+
+    let error = build_constraint(sources.get(bad).unwrap()).unwrap_err();
+    assert_eq!(error.code, Some(codes::INVALID_BUILD_CONSTRAINT));
+    assert_eq!(
+        error.message,
+        "Invalid //go:build constraint: unexpected end of expression."
+    );
+    let error = build_constraint(sources.get(twice).unwrap()).unwrap_err();
+    assert_eq!(error.message, "Multiple //go:build lines.");
+}
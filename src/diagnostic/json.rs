@@ -0,0 +1,108 @@
+// Machine-readable diagnostics, written as one JSON object per line.
+//
+// Every object has the following fields. New fields may be added in later versions but
+// existing fields keep their names and meaning.
+//
+//   "severity"     "error" | "warning" | "note" | "help"
+//   "code"         stable error code such as "E0005", or null
+//   "message"      the main message
+//   "spans"        list of span objects, the primary span(s) first
+//   "notes"        list of strings
+//   "fixes"        list of {"message", "replacement", "span"} objects; applying a fix
+//                  replaces the bytes covered by its span with the replacement text
+//
+// A span object has the fields "file" (the file name, or null when unknown),
+// "byte_start" and "byte_end" (a half-open byte range), "line_start", "column_start",
+// "line_end" and "column_end" (1-based, columns counted in bytes), "is_primary" and
+// "label" (the label text, or null). Lines, columns and file names are reported after
+// applying `//line` directives; byte offsets always refer to the file as read.
+
+use crate::diagnostic::{Diagnostic, Label};
+use crate::source::{SourceMap, Span};
+
+pub fn render(diagnostic: &Diagnostic, sources: &SourceMap) -> String {
+    let mut labels: Vec<&Label> = diagnostic.labels.iter().collect();
+    labels.sort_by_key(|l| !l.primary);
+
+    let spans: Vec<String> = labels
+        .iter()
+        .map(|l| {
+            let label = if l.message.is_empty() {
+                "null".to_string()
+            } else {
+                string(&l.message)
+            };
+            return span(
+                l.span,
+                sources,
+                &format!(",\"is_primary\":{},\"label\":{}", l.primary, label),
+            );
+        })
+        .collect();
+    let notes: Vec<String> = diagnostic.notes.iter().map(|n| string(n)).collect();
+    let fixes: Vec<String> = diagnostic
+        .suggestions
+        .iter()
+        .map(|s| {
+            format!(
+                "{{\"message\":{},\"replacement\":{},\"span\":{}}}",
+                string(&s.message),
+                string(&s.replacement),
+                span(s.span, sources, "")
+            )
+        })
+        .collect();
+    let code = match diagnostic.code {
+        Some(code) => string(code),
+        None => "null".to_string(),
+    };
+
+    return format!(
+        "{{\"severity\":{},\"code\":{},\"message\":{},\"spans\":[{}],\"notes\":[{}],\"fixes\":[{}]}}",
+        string(&diagnostic.severity.to_string()),
+        code,
+        string(&diagnostic.message),
+        spans.join(","),
+        notes.join(","),
+        fixes.join(",")
+    );
+}
+
+fn span(span: Span, sources: &SourceMap, extra: &str) -> String {
+    let (name, line_start, column_start, line_end, column_end) = match sources.get(span.file) {
+        Some(file) => {
+            let (name, line_start, column_start) = file.presumed(span.start);
+            let (_, line_end, column_end) = file.presumed(span.end);
+            (string(name), line_start, column_start, line_end, column_end)
+        }
+        None => (
+            "null".to_string(),
+            span.line,
+            span.column,
+            span.line,
+            span.column + span.len(),
+        ),
+    };
+    return format!(
+        "{{\"file\":{},\"byte_start\":{},\"byte_end\":{},\"line_start\":{},\"column_start\":{},\"line_end\":{},\"column_end\":{}{}}}",
+        name, span.start, span.end, line_start, column_start, line_end, column_end, extra
+    );
+}
+
+pub fn string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    return out;
+}
@@ -0,0 +1,144 @@
+use crate::source::Span;
+use std::fmt;
+
+pub mod json;
+pub mod render;
+#[cfg(test)]
+mod test;
+
+// Stable error codes. Codes are never reused once a diagnostic is removed.
+pub mod codes {
+    pub const UNEXPECTED_CHARACTER: &str = "E0001";
+    pub const INVALID_UTF8: &str = "E0002";
+    pub const INVALID_BOM: &str = "E0003";
+    pub const UNTERMINATED_COMMENT: &str = "E0004";
+    pub const UNTERMINATED_STRING: &str = "E0005";
+    pub const UNTERMINATED_RUNE: &str = "E0006";
+    pub const INVALID_RUNE: &str = "E0007";
+    pub const INVALID_ESCAPE: &str = "E0008";
+    pub const INVALID_NUMBER: &str = "E0009";
+    pub const CANNOT_READ_FILE: &str = "E0010";
+    pub const NOT_IMPLEMENTED: &str = "E0011";
+    pub const INVALID_DIRECTIVE: &str = "E0012";
+    pub const INVALID_BUILD_CONSTRAINT: &str = "E0013";
+    pub const NO_BUILDABLE_FILES: &str = "E0014";
+    pub const UNEXPECTED_TOKEN: &str = "E0015";
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+            Severity::Help => "help",
+        };
+        return write!(f, "{}", name);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+// A suggested fix that replaces the text covered by `span` with `replacement`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Suggestion {
+    pub span: Span,
+    pub replacement: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: &str) -> Diagnostic {
+        Diagnostic {
+            severity,
+            code: None,
+            message: message.to_string(),
+            labels: Vec::new(),
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    pub fn error(message: &str) -> Diagnostic {
+        return Diagnostic::new(Severity::Error, message);
+    }
+
+    pub fn warning(message: &str) -> Diagnostic {
+        return Diagnostic::new(Severity::Warning, message);
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Diagnostic {
+        self.code = Some(code);
+        return self;
+    }
+
+    pub fn with_primary(mut self, span: Span, message: &str) -> Diagnostic {
+        self.labels.push(Label {
+            span,
+            message: message.to_string(),
+            primary: true,
+        });
+        return self;
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: &str) -> Diagnostic {
+        self.labels.push(Label {
+            span,
+            message: message.to_string(),
+            primary: false,
+        });
+        return self;
+    }
+
+    pub fn with_note(mut self, note: &str) -> Diagnostic {
+        self.notes.push(note.to_string());
+        return self;
+    }
+
+    pub fn with_suggestion(mut self, span: Span, replacement: &str, message: &str) -> Diagnostic {
+        self.suggestions.push(Suggestion {
+            span,
+            replacement: replacement.to_string(),
+            message: message.to_string(),
+        });
+        return self;
+    }
+
+    pub fn is_error(&self) -> bool {
+        return self.severity == Severity::Error;
+    }
+
+    pub fn primary_span(&self) -> Option<Span> {
+        return self.labels.iter().find(|l| l.primary).map(|l| l.span);
+    }
+}
@@ -0,0 +1,165 @@
+use crate::diagnostic::{Diagnostic, Label, Severity};
+use crate::source::{SourceFile, SourceMap};
+
+const TAB_WIDTH: usize = 4;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const GREEN: &str = "\x1b[1;32m";
+const CYAN: &str = "\x1b[1;36m";
+const BLUE: &str = "\x1b[1;34m";
+
+// Renders a diagnostic in the style of rustc: a header, the location of the primary
+// span, the quoted source lines with their labels underlined, then notes and fixes.
+pub fn render(diagnostic: &Diagnostic, sources: &SourceMap, color: bool) -> String {
+    let style = Style { color };
+    let mut out = String::new();
+
+    let severity_color = severity_color(diagnostic.severity);
+    let header = match diagnostic.code {
+        Some(code) => format!("{}[{}]", diagnostic.severity, code),
+        None => diagnostic.severity.to_string(),
+    };
+    out.push_str(&style.paint(&header, severity_color));
+    out.push_str(&style.paint(&format!(": {}", diagnostic.message), BOLD));
+    out.push('\n');
+
+    let mut labels: Vec<&Label> = diagnostic.labels.iter().collect();
+    labels.sort_by_key(|l| (l.span.start, !l.primary));
+    let file = diagnostic
+        .primary_span()
+        .or(labels.first().map(|l| l.span))
+        .and_then(|span| sources.get(span.file).map(|file| (file, span)));
+
+    let gutter_width = match file {
+        Some(_) => labels
+            .iter()
+            .map(|l| l.span.line.to_string().len())
+            .max()
+            .unwrap_or(1),
+        None => 1,
+    };
+    let gutter = " ".repeat(gutter_width);
+    let bar = style.paint("|", BLUE);
+
+    if let Some((file, span)) = file {
+        let (name, line, column) = file.presumed(span.start);
+        out.push_str(&format!(
+            "{}{} {}:{}:{}\n",
+            gutter,
+            style.paint("-->", BLUE),
+            name,
+            line,
+            column
+        ));
+        out.push_str(&format!("{} {}\n", gutter, bar));
+
+        let labels: Vec<&Label> = labels
+            .into_iter()
+            .filter(|l| l.span.file == file.id)
+            .collect();
+        let mut previous_line: Option<usize> = None;
+        for (i, label) in labels.iter().enumerate() {
+            let line = label.span.line;
+            if previous_line != Some(line) {
+                if previous_line.is_some_and(|p| line > p + 1) {
+                    out.push_str(&style.paint("...\n", BLUE));
+                }
+                let text = expand_tabs(file.line_text(line).unwrap_or(b""));
+                let number = format!("{:>width$} |", line, width = gutter_width);
+                out.push_str(format!("{} {}", style.paint(&number, BLUE), text).trim_end());
+                out.push('\n');
+                previous_line = Some(line);
+            }
+
+            let (offset, width) = underline(file, label);
+            let marker_color = if label.primary { severity_color } else { BLUE };
+            let marker = if label.primary { "^" } else { "-" }.repeat(width);
+            let mut marker_line = format!(
+                "{} {} {}{}",
+                gutter,
+                bar,
+                " ".repeat(offset),
+                style.paint(&marker, marker_color)
+            );
+            if !label.message.is_empty() {
+                marker_line.push(' ');
+                marker_line.push_str(&style.paint(&label.message, marker_color));
+            }
+            out.push_str(&marker_line);
+            out.push('\n');
+
+            if i + 1 == labels.len()
+                && (!diagnostic.notes.is_empty() || !diagnostic.suggestions.is_empty())
+            {
+                out.push_str(&format!("{} {}\n", gutter, bar));
+            }
+        }
+    }
+
+    for note in &diagnostic.notes {
+        out.push_str(&format!(
+            "{} {} {}\n",
+            gutter,
+            style.paint("= note:", BOLD),
+            note
+        ));
+    }
+    for suggestion in &diagnostic.suggestions {
+        let help = style.paint(&format!("= {}:", Severity::Help), BOLD);
+        if suggestion.replacement.is_empty() {
+            out.push_str(&format!("{} {} {}\n", gutter, help, suggestion.message));
+        } else {
+            out.push_str(&format!(
+                "{} {} {}: `{}`\n",
+                gutter, help, suggestion.message, suggestion.replacement
+            ));
+        }
+    }
+    return out;
+}
+
+// Returns the display column and width of a label's underline within its first line.
+fn underline(file: &SourceFile, label: &Label) -> (usize, usize) {
+    let text = file.line_text(label.span.line).unwrap_or(b"");
+    let start = usize::min(label.span.column - 1, text.len());
+    let end = usize::min(start + label.span.len(), text.len());
+    let offset = display_width(&text[..start]);
+    let width = display_width(&text[start..end]);
+    return (offset, usize::max(width, 1));
+}
+
+fn display_width(text: &[u8]) -> usize {
+    return String::from_utf8_lossy(text)
+        .chars()
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum();
+}
+
+fn expand_tabs(text: &[u8]) -> String {
+    return String::from_utf8_lossy(text).replace('\t', &" ".repeat(TAB_WIDTH));
+}
+
+fn severity_color(severity: Severity) -> &'static str {
+    return match severity {
+        Severity::Error => RED,
+        Severity::Warning => YELLOW,
+        Severity::Note => GREEN,
+        Severity::Help => CYAN,
+    };
+}
+
+struct Style {
+    color: bool,
+}
+
+impl Style {
+    fn paint(&self, text: &str, code: &str) -> String {
+        if !self.color {
+            return text.to_string();
+        }
+        return format!("{}{}{}", code, text, RESET);
+    }
+}
@@ -0,0 +1,232 @@
+use crate::diagnostic::render::render;
+use crate::diagnostic::{codes, json, Diagnostic, OutputFormat, Severity};
+use crate::error::ErrorHandler;
+use crate::lexer::Lexer;
+use crate::source::{FileId, SourceMap, Span};
+use std::{env, fs};
+
+fn span(start: usize, end: usize) -> Span {
+    return Span::new(FileId(0), start, end, 1, start + 1);
+}
+
+#[test]
+fn build_diagnostic() {
+    let diagnostic = Diagnostic::error("Unterminated string.")
+        .with_code(codes::UNTERMINATED_STRING)
+        .with_secondary(span(0, 2), "context")
+        .with_primary(span(4, 5), "here")
+        .with_note("strings cannot span lines")
+        .with_suggestion(span(9, 9), "\"", "close the literal");
+
+    assert_eq!(diagnostic.severity, Severity::Error);
+    assert_eq!(diagnostic.code, Some("E0005"));
+    assert_eq!(diagnostic.primary_span(), Some(span(4, 5)));
+    assert_eq!(diagnostic.labels.len(), 2);
+    assert_eq!(diagnostic.notes, vec!["strings cannot span lines"]);
+    assert_eq!(diagnostic.suggestions[0].replacement, "\"");
+}
+
+#[test]
+fn handler_counts_errors() {
+    let mut handler = ErrorHandler::new();
+    assert!(!handler.has_errors());
+
+    handler.emit(Diagnostic::warning("Unused import."));
+    assert!(!handler.has_errors());
+
+    handler.error(
+        span(0, 1),
+        codes::UNEXPECTED_CHARACTER,
+        "Unexpected character $.",
+    );
+    assert!(handler.has_errors());
+    assert_eq!(handler.error_count(), 1);
+    assert_eq!(handler.diagnostics().len(), 2);
+
+    let taken = handler.take_diagnostics();
+    assert_eq!(taken.len(), 2);
+    assert!(!handler.has_errors());
+}
+
+fn render_one(src: &str, diagnostic: Diagnostic) -> String {
+    let mut sources = SourceMap::new();
+    sources.add("main.go", src.as_bytes().to_vec());
+    return render(&diagnostic, &sources, false);
+}
+
+#[test]
+fn render_snippet_with_caret() {
+    let src = "package main\n\nvar s = \"abc\nvar t = 1\n";
+    let diagnostic = Diagnostic::error("Unterminated string.")
+        .with_code(codes::UNTERMINATED_STRING)
+        .with_primary(Span::new(FileId(0), 22, 23, 3, 9), "literal starts here")
+        .with_suggestion(
+            Span::new(FileId(0), 26, 26, 3, 13),
+            "\"",
+            "close the literal",
+        );
+
+    assert_eq!(
+        render_one(src, diagnostic),
+        r#"error[E0005]: Unterminated string.
+ --> main.go:3:9
+  |
+3 | var s = "abc
+  |         ^ literal starts here
+  |
+  = help: close the literal: `"`
+"#
+    );
+}
+
+#[test]
+fn render_expands_tabs_and_multiple_labels() {
+    let src = "func f() {\n\tx := 0x1e3p\n}\n";
+    let diagnostic = Diagnostic::error("'e' exponent requires decimal mantissa.")
+        .with_code(codes::INVALID_NUMBER)
+        .with_primary(Span::new(FileId(0), 20, 21, 2, 10), "")
+        .with_secondary(Span::new(FileId(0), 17, 19, 2, 7), "base prefix here")
+        .with_note("hexadecimal floats use a 'p' exponent");
+
+    assert_eq!(
+        render_one(src, diagnostic),
+        r#"error[E0009]: 'e' exponent requires decimal mantissa.
+ --> main.go:2:10
+  |
+2 |     x := 0x1e3p
+  |          -- base prefix here
+  |             ^
+  |
+  = note: hexadecimal floats use a 'p' exponent
+"#
+    );
+}
+
+#[test]
+fn render_labels_on_distant_lines() {
+    let src = "a\nb\nc\nd\n";
+    let diagnostic = Diagnostic::warning("Spread out.")
+        .with_primary(Span::new(FileId(0), 0, 1, 1, 1), "first")
+        .with_secondary(Span::new(FileId(0), 6, 7, 4, 1), "last");
+
+    assert_eq!(
+        render_one(src, diagnostic),
+        "warning: Spread out.\n --> main.go:1:1\n  |\n1 | a\n  | ^ first\n...\n4 | d\n  | - last\n"
+    );
+}
+
+#[test]
+fn render_without_source() {
+    let diagnostic = Diagnostic::error("No files given.").with_note("pass at least one file");
+    assert_eq!(
+        render(&diagnostic, &SourceMap::new(), false),
+        "error: No files given.\n  = note: pass at least one file\n"
+    );
+}
+
+#[test]
+fn render_with_color() {
+    let diagnostic = Diagnostic::error("Boom.");
+    assert_eq!(
+        render(&diagnostic, &SourceMap::new(), true),
+        "\x1b[1;31merror\x1b[0m\x1b[1m: Boom.\x1b[0m\n"
+    );
+}
+
+#[test]
+fn report_renders_every_diagnostic() {
+    let mut sources = SourceMap::new();
+    let file = sources.add("x.go", b"x := $\n".to_vec());
+    let mut handler = ErrorHandler::new();
+    handler.error(
+        Span::new(file, 5, 6, 1, 6),
+        codes::UNEXPECTED_CHARACTER,
+        "Unexpected character $.",
+    );
+    handler.emit(Diagnostic::warning("Something odd."));
+
+    let mut out: Vec<u8> = Vec::new();
+    handler.report(&sources, &mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "error[E0001]: Unexpected character $.\n --> x.go:1:6\n  |\n1 | x := $\n  |      ^\n\nwarning: Something odd.\n\n"
+    );
+}
+
+#[test]
+fn report_positions_after_line_directives() {
+    let src = b"package p\n//line parser.y:42\nx := $\n".to_vec();
+    let mut sources = SourceMap::new();
+    let file = sources.add("x.go", src.clone());
+    let mut lexer = Lexer::new(&src).with_file(file);
+    for _ in lexer.by_ref() {}
+    let mut handler = ErrorHandler::new();
+    handler.extend(lexer.take_diagnostics());
+    sources.set_line_directives(file, lexer.line_directives());
+
+    let mut out: Vec<u8> = Vec::new();
+    handler.report(&sources, &mut out).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "error[E0001]: Unexpected character $.\n --> parser.y:42:6\n  |\n3 | x := $\n  |      ^\n\n"
+    );
+
+    let json = json::render(&handler.diagnostics()[0], &sources);
+    assert!(json.contains("\"file\":\"parser.y\",\"byte_start\":34,\"byte_end\":35,\"line_start\":42,\"column_start\":6,\"line_end\":42,\"column_end\":7"));
+}
+
+#[test]
+fn json_escapes_strings() {
+    assert_eq!(
+        json::string("a\"b\\c\nd\u{1}é"),
+        "\"a\\\"b\\\\c\\nd\\u0001é\""
+    );
+}
+
+#[test]
+fn json_diagnostic_without_source() {
+    let diagnostic = Diagnostic::warning("Odd.")
+        .with_primary(Span::new(FileId(4), 2, 5, 1, 3), "")
+        .with_note("just so");
+
+    assert_eq!(
+        json::render(&diagnostic, &SourceMap::new()),
+        r#"{"severity":"warning","code":null,"message":"Odd.","spans":[{"file":null,"byte_start":2,"byte_end":5,"line_start":1,"column_start":3,"line_end":1,"column_end":6,"is_primary":true,"label":null}],"notes":["just so"],"fixes":[]}"#
+    );
+}
+
+// Reports the lexer diagnostics for `name` in the given format and compares them with
+// the golden file next to it. Set UPDATE_GOLDEN=1 to rewrite the golden files.
+fn assert_golden(name: &str, format: OutputFormat, extension: &str) {
+    let dir = "src/test_files/diagnostics";
+    let mut sources = SourceMap::new();
+    let src = fs::read(format!("{}/{}.go", dir, name)).unwrap();
+    let file = sources.add(&format!("{}.go", name), src);
+
+    let mut lexer = Lexer::new(&sources.get(file).unwrap().src).with_file(file);
+    for _ in lexer.by_ref() {}
+    let mut handler = ErrorHandler::new();
+    handler.set_format(format);
+    handler.extend(lexer.take_diagnostics());
+
+    let mut out: Vec<u8> = Vec::new();
+    handler.report(&sources, &mut out).unwrap();
+    let golden = format!("{}/{}.{}", dir, name, extension);
+    if env::var("UPDATE_GOLDEN").is_ok() {
+        fs::write(&golden, &out).unwrap();
+    }
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        fs::read_to_string(&golden).unwrap()
+    );
+}
+
+#[test]
+fn golden_lex_errors_json() {
+    assert_golden("lex_errors", OutputFormat::Json, "json");
+}
+
+#[test]
+fn golden_lex_errors_human() {
+    assert_golden("lex_errors", OutputFormat::Human, "txt");
+}
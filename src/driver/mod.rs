@@ -0,0 +1,386 @@
+use crate::ast;
+use crate::build::Context;
+use crate::diagnostic::{codes, Diagnostic, OutputFormat};
+use crate::error::ErrorHandler;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::printer;
+use crate::source::{FileId, SourceMap};
+use crate::token::Token;
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+#[cfg(test)]
+mod test;
+pub mod tokens;
+
+pub use tokens::TokenFormat;
+
+pub const USAGE: &str = "Usage: go-compiler <command> [options] <files or directories>
+
+Commands:
+    tokens    print the tokens of each file
+    parse     parse each file
+    fmt       print each file in the canonical gofmt style
+    check     report errors without producing output
+    build     compile the package
+    run       compile and run the package
+
+Options:
+    -o <path>                  write output to <path> instead of stdout
+    --format=text|json         output format of the tokens command (default text)
+    --comments                 include comments in the output of the tokens command
+    --goos=<os>                target operating system (default: the host's)
+    --goarch=<arch>            target architecture (default: the host's)
+    --tags=<tag,...>           extra build tags to satisfy
+    --error-format=human|json  how diagnostics are printed (default human)
+    --color=auto|always|never  colorize human-readable diagnostics (default auto)
+    -h, --help                 print this message
+";
+
+// Exit statuses returned by `run`.
+pub const EXIT_SUCCESS: i32 = 0;
+pub const EXIT_ERRORS: i32 = 1;
+pub const EXIT_USAGE: i32 = 2;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Tokens,
+    Parse,
+    Fmt,
+    Check,
+    Build,
+    Run,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Options {
+    pub command: Command,
+    pub inputs: Vec<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub token_format: TokenFormat,
+    pub comments: bool,
+    pub target: Context,
+    pub error_format: OutputFormat,
+    pub color: Color,
+}
+
+// The result of parsing the command line: either options to act on or a request for help.
+#[derive(Debug, PartialEq)]
+pub enum Invocation {
+    Compile(Options),
+    Help,
+}
+
+pub fn parse_args(args: &[String]) -> Result<Invocation, String> {
+    let mut command: Option<Command> = None;
+    let mut inputs: Vec<PathBuf> = Vec::new();
+    let mut output: Option<PathBuf> = None;
+    let mut token_format = TokenFormat::Text;
+    let mut comments = false;
+    let mut target = Context::host();
+    let mut error_format = OutputFormat::Human;
+    let mut color = Color::Auto;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Invocation::Help),
+            "-o" => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => return Err("-o requires a path".to_string()),
+            },
+            "--format=text" => token_format = TokenFormat::Text,
+            "--format=json" => token_format = TokenFormat::Json,
+            "--comments" => comments = true,
+            _ if arg.starts_with("--goos=") => target.goos = arg["--goos=".len()..].to_string(),
+            _ if arg.starts_with("--goarch=") => {
+                target.goarch = arg["--goarch=".len()..].to_string()
+            }
+            _ if arg.starts_with("--tags=") => {
+                target.tags = arg["--tags=".len()..]
+                    .split(',')
+                    .filter(|t| !t.is_empty())
+                    .map(|t| t.to_string())
+                    .collect()
+            }
+            "--error-format=human" => error_format = OutputFormat::Human,
+            "--error-format=json" => error_format = OutputFormat::Json,
+            "--color=auto" => color = Color::Auto,
+            "--color=always" => color = Color::Always,
+            "--color=never" => color = Color::Never,
+            _ if arg.starts_with('-') && arg.len() > 1 => {
+                return Err(format!("unknown option '{}'", arg));
+            }
+            _ if command.is_none() => {
+                command = Some(match arg.as_str() {
+                    "tokens" => Command::Tokens,
+                    "parse" => Command::Parse,
+                    "fmt" => Command::Fmt,
+                    "check" => Command::Check,
+                    "build" => Command::Build,
+                    "run" => Command::Run,
+                    _ => return Err(format!("unknown command '{}'", arg)),
+                });
+            }
+            _ => inputs.push(PathBuf::from(arg)),
+        }
+    }
+
+    let command = match command {
+        Some(command) => command,
+        None => return Err("no command given".to_string()),
+    };
+    if inputs.is_empty() {
+        return Err("no input files".to_string());
+    }
+    return Ok(Invocation::Compile(Options {
+        command,
+        inputs,
+        output,
+        token_format,
+        comments,
+        target,
+        error_format,
+        color,
+    }));
+}
+
+// Expands directories into the Go files they contain, in name order. Test files are
+// skipped for the commands that compile a package.
+pub fn collect_files(options: &Options) -> io::Result<Vec<PathBuf>> {
+    let skip_tests = matches!(
+        options.command,
+        Command::Check | Command::Build | Command::Run
+    );
+    let mut files = Vec::new();
+    for input in &options.inputs {
+        if !input.is_dir() {
+            files.push(input.clone());
+            continue;
+        }
+        let mut found: Vec<PathBuf> = Vec::new();
+        for entry in fs::read_dir(input)? {
+            let path = entry?.path();
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            if path.is_file()
+                && name.ends_with(".go")
+                && !(skip_tests && name.ends_with("_test.go"))
+            {
+                found.push(path);
+            }
+        }
+        found.sort();
+        files.extend(found);
+    }
+    return Ok(files);
+}
+
+// Runs the compiler with the arguments that follow the program name and returns the
+// process exit status. Output goes to `stdout` unless `-o` is given; diagnostics go to
+// `stderr`.
+pub fn run(args: &[String], stdout: &mut dyn Write, stderr: &mut dyn Write) -> i32 {
+    let options = match parse_args(args) {
+        Ok(Invocation::Compile(options)) => options,
+        Ok(Invocation::Help) => {
+            let _ = write!(stdout, "{}", USAGE);
+            return EXIT_SUCCESS;
+        }
+        Err(msg) => {
+            let _ = write!(stderr, "error: {}\n\n{}", msg, USAGE);
+            return EXIT_USAGE;
+        }
+    };
+
+    let mut session = Session::new(&options);
+    let status = match session.compile(&options, stdout) {
+        Ok(()) => {
+            if session.error_handler.has_errors() {
+                EXIT_ERRORS
+            } else {
+                EXIT_SUCCESS
+            }
+        }
+        Err(err) => {
+            session.error_handler.emit(Diagnostic::error(&format!(
+                "Unable to write output: {}.",
+                err
+            )));
+            EXIT_ERRORS
+        }
+    };
+    session
+        .error_handler
+        .report(&session.sources, stderr)
+        .expect("Unable to write diagnostics");
+    return status;
+}
+
+// The state shared by every phase of one compiler invocation.
+pub struct Session {
+    pub sources: SourceMap,
+    pub error_handler: ErrorHandler,
+}
+
+impl Session {
+    pub fn new(options: &Options) -> Session {
+        let mut error_handler = ErrorHandler::new();
+        error_handler.set_format(options.error_format);
+        error_handler.set_color(match options.color {
+            Color::Auto => io::stderr().is_terminal(),
+            Color::Always => true,
+            Color::Never => false,
+        });
+        Session {
+            sources: SourceMap::new(),
+            error_handler,
+        }
+    }
+
+    pub fn compile(&mut self, options: &Options, stdout: &mut dyn Write) -> io::Result<()> {
+        let files = match collect_files(options) {
+            Ok(files) => files,
+            Err(err) => {
+                self.error_handler.emit(
+                    Diagnostic::error(&format!("Unable to read input directory: {}.", err))
+                        .with_code(codes::CANNOT_READ_FILE),
+                );
+                return Ok(());
+            }
+        };
+        let mut ids: Vec<FileId> = files.iter().filter_map(|f| self.load(f)).collect();
+        if matches!(
+            options.command,
+            Command::Check | Command::Build | Command::Run
+        ) {
+            ids = self.select(ids, &options.target);
+        }
+
+        let mut file: Option<fs::File> = match &options.output {
+            // The output of build is a binary, which cannot be produced yet.
+            Some(path) if options.command == Command::Build => {
+                self.error_handler.emit(
+                    Diagnostic::error(&format!(
+                        "Writing the binary to {} is not implemented yet.",
+                        path.display()
+                    ))
+                    .with_code(codes::NOT_IMPLEMENTED),
+                );
+                None
+            }
+            Some(path) => Some(fs::File::create(path)?),
+            None => None,
+        };
+        let out: &mut dyn Write = match &mut file {
+            Some(file) => file,
+            None => stdout,
+        };
+
+        for id in ids {
+            match options.command {
+                Command::Tokens => self.tokens(id, options, out)?,
+                Command::Fmt => self.fmt(id, out)?,
+                Command::Parse | Command::Check | Command::Build | Command::Run => {
+                    self.parse(id);
+                }
+            }
+        }
+
+        if matches!(options.command, Command::Build | Command::Run)
+            && !self.error_handler.has_errors()
+        {
+            self.error_handler.emit(
+                Diagnostic::error("Code generation is not implemented yet.")
+                    .with_code(codes::NOT_IMPLEMENTED),
+            );
+        }
+        return out.flush();
+    }
+
+    fn load(&mut self, path: &Path) -> Option<FileId> {
+        match fs::read(path) {
+            Ok(src) => return Some(self.sources.add(&path.to_string_lossy(), src)),
+            Err(err) => {
+                self.error_handler.emit(
+                    Diagnostic::error(&format!("Unable to read {}: {}.", path.display(), err))
+                        .with_code(codes::CANNOT_READ_FILE),
+                );
+                return None;
+            }
+        }
+    }
+
+    // Keeps the files that the build constraints include for `target`.
+    fn select(&mut self, ids: Vec<FileId>, target: &Context) -> Vec<FileId> {
+        let loaded = ids.len();
+        let mut selected = Vec::new();
+        for id in ids {
+            match target.includes(self.sources.get(id).unwrap()) {
+                Ok(true) => selected.push(id),
+                Ok(false) => {}
+                Err(diagnostic) => self.error_handler.emit(diagnostic),
+            }
+        }
+        if loaded > 0 && selected.is_empty() && !self.error_handler.has_errors() {
+            self.error_handler.emit(
+                Diagnostic::error(&format!(
+                    "Build constraints exclude all Go files for {}/{}.",
+                    target.goos, target.goarch
+                ))
+                .with_code(codes::NO_BUILDABLE_FILES),
+            );
+        }
+        return selected;
+    }
+
+    fn parse(&mut self, id: FileId) -> ast::File {
+        let mut lexer = Lexer::new(&self.sources.get(id).unwrap().src).with_file(id);
+        let mut parser = Parser::new(&mut lexer);
+        let file = parser.parse_file();
+        self.error_handler.extend(parser.take_diagnostics());
+        let directives = lexer.line_directives();
+        self.sources.set_line_directives(id, directives);
+        return file;
+    }
+
+    fn tokens(&mut self, id: FileId, options: &Options, out: &mut dyn Write) -> io::Result<()> {
+        let file = self.sources.get(id).unwrap();
+        let mut lexer = Lexer::new(&file.src)
+            .with_file(id)
+            .with_comments(options.comments);
+        let tokens: Vec<Token> = lexer.by_ref().collect();
+        self.error_handler.extend(lexer.take_diagnostics());
+        let directives = lexer.line_directives();
+        match options.token_format {
+            TokenFormat::Text => tokens::write_text(file, &tokens, out)?,
+            TokenFormat::Json => tokens::write_json(file, &tokens, out)?,
+        }
+        self.sources.set_line_directives(id, directives);
+        return Ok(());
+    }
+
+    // Writes the file in gofmt style, or reports its syntax errors.
+    fn fmt(&mut self, id: FileId, out: &mut dyn Write) -> io::Result<()> {
+        let src = &self.sources.get(id).unwrap().src;
+        match printer::format_source(src, id) {
+            Ok(formatted) => out.write_all(&formatted)?,
+            Err(diagnostics) => {
+                // The errors are reported at the positions the //line directives give.
+                let mut lexer = Lexer::new(src).with_file(id);
+                lexer.by_ref().for_each(drop);
+                let directives = lexer.line_directives();
+                self.sources.set_line_directives(id, directives);
+                self.error_handler.extend(diagnostics);
+            }
+        }
+        return Ok(());
+    }
+}
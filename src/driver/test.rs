@@ -0,0 +1,347 @@
+use crate::build::Context;
+use crate::diagnostic::OutputFormat;
+use crate::driver::{
+    collect_files, parse_args, run, Color, Command, Invocation, Options, TokenFormat, EXIT_ERRORS,
+    EXIT_SUCCESS, EXIT_USAGE,
+};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+fn args(list: &[&str]) -> Vec<String> {
+    return list.iter().map(|s| s.to_string()).collect();
+}
+
+// Runs the driver, discarding its output, and returns the exit status.
+fn status(list: &[&str]) -> i32 {
+    return run(&args(list), &mut Vec::new(), &mut Vec::new());
+}
+
+// A path in the temporary directory, removed when dropped so that a test that fails
+// does not leave it behind.
+struct TempPath(PathBuf);
+
+impl TempPath {
+    fn new(name: &str) -> TempPath {
+        // Tests run in parallel in one process, so the process id alone is not unique.
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let name = format!("go-compiler-{}-{}-{}", std::process::id(), n, name);
+        return TempPath(env::temp_dir().join(name));
+    }
+
+    fn as_str(&self) -> &str {
+        return self.0.to_str().unwrap();
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if self.0.is_dir() {
+            let _ = fs::remove_dir_all(&self.0);
+        } else {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+}
+
+// Runs `cmd`, a command and its flags, on a file holding `src`. Returns the exit status and
+// what was written to stdout and stderr, with the name of the file replaced by `x.go`.
+fn run_on_source(cmd: &[&str], src: &[u8]) -> (i32, String, String) {
+    let path = TempPath::new("x.go");
+    fs::write(&path.0, src).unwrap();
+    let (mut stdout, mut stderr): (Vec<u8>, Vec<u8>) = (Vec::new(), Vec::new());
+    let mut list = cmd.to_vec();
+    list.push(path.as_str());
+    let status = run(&args(&list), &mut stdout, &mut stderr);
+    let output = |bytes: Vec<u8>| {
+        String::from_utf8(bytes)
+            .unwrap()
+            .replace(path.as_str(), "x.go")
+    };
+    return (status, output(stdout), output(stderr));
+}
+
+fn options(list: &[&str]) -> Options {
+    match parse_args(&args(list)) {
+        Ok(Invocation::Compile(options)) => return options,
+        other => panic!("unexpected {:?}", other),
+    }
+}
+
+#[test]
+fn parse_command_and_inputs() {
+    let options = options(&["check", "a.go", "pkg"]);
+    assert_eq!(
+        options,
+        Options {
+            command: Command::Check,
+            inputs: vec![PathBuf::from("a.go"), PathBuf::from("pkg")],
+            output: None,
+            token_format: TokenFormat::Text,
+            comments: false,
+            target: Context::host(),
+            error_format: OutputFormat::Human,
+            color: Color::Auto,
+        }
+    );
+}
+
+#[test]
+fn parse_flags_in_any_position() {
+    let options = options(&[
+        "--error-format=json",
+        "tokens",
+        "a.go",
+        "-o",
+        "out.txt",
+        "--color=never",
+    ]);
+    assert_eq!(options.command, Command::Tokens);
+    assert_eq!(options.output, Some(PathBuf::from("out.txt")));
+    assert_eq!(options.error_format, OutputFormat::Json);
+    assert_eq!(options.color, Color::Never);
+}
+
+#[test]
+fn parse_errors() {
+    assert_eq!(parse_args(&args(&[])), Err("no command given".to_string()));
+    assert_eq!(
+        parse_args(&args(&["check"])),
+        Err("no input files".to_string())
+    );
+    assert_eq!(
+        parse_args(&args(&["compile", "a.go"])),
+        Err("unknown command 'compile'".to_string())
+    );
+    assert_eq!(
+        parse_args(&args(&["check", "--fast", "a.go"])),
+        Err("unknown option '--fast'".to_string())
+    );
+    assert_eq!(
+        parse_args(&args(&["build", "a.go", "-o"])),
+        Err("-o requires a path".to_string())
+    );
+    assert_eq!(parse_args(&args(&["check", "-h"])), Ok(Invocation::Help));
+}
+
+#[test]
+fn collect_go_files_from_directories() {
+    let files = collect_files(&options(&["check", "src/test_files", "extra.go"])).unwrap();
+    assert_eq!(
+        files,
+        vec![
+            PathBuf::from("src/test_files/generics.go"),
+            PathBuf::from("src/test_files/hugeparams.go"),
+            PathBuf::from("src/test_files/implements.go"),
+            PathBuf::from("extra.go"),
+        ]
+    );
+}
+
+#[test]
+fn exit_status_reflects_diagnostics() {
+    assert_eq!(status(&["check", "src/test_files"]), EXIT_SUCCESS);
+    assert_eq!(
+        status(&["check", "src/test_files/diagnostics"]),
+        EXIT_ERRORS
+    );
+    assert_eq!(status(&["check", "does/not/exist.go"]), EXIT_ERRORS);
+    assert_eq!(status(&["build", "src/test_files"]), EXIT_ERRORS);
+    assert_eq!(status(&["frobnicate", "a.go"]), EXIT_USAGE);
+    assert_eq!(status(&["--help"]), EXIT_SUCCESS);
+}
+
+#[test]
+fn diagnostics_go_to_stderr() {
+    let mut stdout: Vec<u8> = Vec::new();
+    let mut stderr: Vec<u8> = Vec::new();
+    let status = run(
+        &args(&["check", "--error-format=json", "src/test_files/diagnostics"]),
+        &mut stdout,
+        &mut stderr,
+    );
+
+    assert_eq!(status, EXIT_ERRORS);
+    assert!(stdout.is_empty());
+    let stderr = String::from_utf8(stderr).unwrap();
+    assert_eq!(stderr.lines().count(), 7);
+    assert!(stderr.starts_with("{\"severity\":\"error\",\"code\":\"E0009\""));
+}
+
+#[test]
+fn write_output_to_file() {
+    let path = TempPath::new("tokens.txt");
+    let mut stdout: Vec<u8> = Vec::new();
+    let status = run(
+        &args(&[
+            "tokens",
+            "src/test_files/implements.go",
+            "-o",
+            path.as_str(),
+        ]),
+        &mut stdout,
+        &mut Vec::new(),
+    );
+    assert_eq!(status, EXIT_SUCCESS);
+    assert!(stdout.is_empty());
+
+    let output = fs::read_to_string(&path.0).unwrap();
+    assert!(output.starts_with("src/test_files/implements.go:1:1    Package     \"package\"\n"));
+    assert!(output.lines().count() > 100);
+}
+
+// Runs the tokens command on `src` and returns what it printed.
+fn dump_tokens(src: &str, flag: &str) -> String {
+    let (status, stdout, stderr) = run_on_source(&["tokens", flag], src.as_bytes());
+    assert_eq!(status, EXIT_SUCCESS, "{}", stderr);
+    return stdout;
+}
+
+#[test]
+fn dump_tokens_as_text() {
+    assert_eq!(
+        dump_tokens("x := \"é\"\n", "--format=text"),
+        "x.go:1:1   Identifier  \"x\"\nx.go:1:3   Define      \":=\"\nx.go:1:6   String      \"\\\"é\\\"\"\nx.go:1:10  Semicolon   \"\\n\"\nx.go:2:1   Eof         \"\"\n"
+    );
+}
+
+#[test]
+fn dump_tokens_as_json() {
+    assert_eq!(
+        dump_tokens("f(1)", "--format=json"),
+        r#"{"file":"x.go","kind":"Identifier","go_token":"IDENT","start":0,"end":1,"line":1,"column":1,"lexeme":"f"}
+{"file":"x.go","kind":"LParen","go_token":"(","start":1,"end":2,"line":1,"column":2,"lexeme":"("}
+{"file":"x.go","kind":"Int","go_token":"INT","start":2,"end":3,"line":1,"column":3,"lexeme":"1"}
+{"file":"x.go","kind":"RParen","go_token":")","start":3,"end":4,"line":1,"column":4,"lexeme":")"}
+{"file":"x.go","kind":"Semicolon","go_token":";","start":4,"end":4,"line":1,"column":5,"lexeme":"\n"}
+{"file":"x.go","kind":"Eof","go_token":"EOF","start":4,"end":4,"line":1,"column":5,"lexeme":""}
+"#
+    );
+}
+
+#[test]
+fn dump_tokens_with_invalid_utf8() {
+    let (status, stdout, _) = run_on_source(&["tokens"], b"s := \"caf\xe9\"");
+    assert_eq!(status, EXIT_ERRORS);
+    assert!(stdout.contains("\"\\\"caf\u{fffd}\\\"\""), "{}", stdout);
+}
+
+#[test]
+fn dump_tokens_with_comments() {
+    assert_eq!(
+        dump_tokens("// F.\nfunc F()", "--comments"),
+        "x.go:1:1  DocComment  \"// F.\"\nx.go:2:1  Func        \"func\"\nx.go:2:6  Identifier  \"F\"\nx.go:2:7  LParen      \"(\"\nx.go:2:8  RParen      \")\"\nx.go:2:9  Semicolon   \"\\n\"\nx.go:2:9  Eof         \"\"\n"
+    );
+}
+
+#[test]
+fn parse_target_flags() {
+    let options = options(&[
+        "check",
+        "--goos=windows",
+        "--goarch=arm64",
+        "--tags=netgo,,osusergo",
+        "a.go",
+    ]);
+    assert_eq!(
+        options.target,
+        Context::new("windows", "arm64").with_tags(&["netgo", "osusergo"])
+    );
+}
+
+#[test]
+fn select_files_by_build_constraints() {
+    let temp = TempPath::new("constraints");
+    let dir = &temp.0;
+    fs::create_dir_all(dir).unwrap();
+    fs::write(dir.join("a.go"), "package p\n").unwrap();
+    fs::write(dir.join("b_windows.go"), "package p\n\nx := $\n").unwrap();
+    fs::write(
+        dir.join("c.go"),
+        "//go:build !linux\n\npackage p\n\ny := $\n",
+    )
+    .unwrap();
+    let linux = status(&["check", "--goos=linux", "--goarch=amd64", temp.as_str()]);
+    let windows = status(&["check", "--goos=windows", "--goarch=amd64", temp.as_str()]);
+    assert_eq!(linux, EXIT_SUCCESS);
+    assert_eq!(windows, EXIT_ERRORS);
+}
+
+#[test]
+fn parse_reports_syntax_errors() {
+    let (status, _, stderr) = run_on_source(
+        &["parse"],
+        b"package p\n\nfunc f() {\n\tx := )\n\ty = ]\n}\n",
+    );
+    assert_eq!(status, EXIT_ERRORS);
+    assert!(
+        stderr.starts_with("error[E0015]: Expected expression, found ')'.\n"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("x.go:4:7\n"), "{}", stderr);
+    assert!(
+        stderr.contains("error[E0015]: Expected expression, found ']'.\n"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("x.go:5:6\n"), "{}", stderr);
+}
+
+#[test]
+fn fmt_prints_files_in_gofmt_style() {
+    let (status, stdout, stderr) = run_on_source(
+        &["fmt"],
+        b"package p\nimport (\n\"os\"\n\"fmt\"\n)\nfunc f( ) {\n  x:=1\n}\n",
+    );
+    assert_eq!(status, EXIT_SUCCESS, "{}", stderr);
+    assert_eq!(
+        stdout,
+        "package p\n\nimport (\n\t\"fmt\"\n\t\"os\"\n)\n\nfunc f() {\n\tx := 1\n}\n"
+    );
+}
+
+#[test]
+fn fmt_reports_syntax_errors() {
+    let (status, stdout, stderr) =
+        run_on_source(&["fmt"], b"package p\n\nfunc f() {\n\tx := )\n}\n");
+    assert_eq!(status, EXIT_ERRORS);
+    assert!(stdout.is_empty());
+    assert!(
+        stderr.contains("Expected expression, found ')'."),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn fmt_reports_errors_at_line_directives() {
+    let src = b"package p\n\n//line gen.y:100\nvar x = \"a\\q\"\n";
+    let (status, _, stderr) = run_on_source(&["fmt"], src);
+    assert_eq!(status, EXIT_ERRORS);
+    assert!(stderr.contains("gen.y:100:11"), "{}", stderr);
+}
+
+#[test]
+fn build_reports_unsupported_output_path() {
+    let path = TempPath::new("build");
+    let mut stderr: Vec<u8> = Vec::new();
+    let status = run(
+        &args(&["build", "src/test_files/implements.go", "-o", path.as_str()]),
+        &mut Vec::new(),
+        &mut stderr,
+    );
+    assert_eq!(status, EXIT_ERRORS);
+    assert!(!path.0.exists());
+    let stderr = String::from_utf8(stderr).unwrap();
+    assert!(
+        stderr.contains(&format!(
+            "error[E0011]: Writing the binary to {} is not implemented yet.\n",
+            path.as_str()
+        )),
+        "{}",
+        stderr
+    );
+}
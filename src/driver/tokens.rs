@@ -0,0 +1,60 @@
+use crate::diagnostic::json;
+use crate::source::SourceFile;
+use crate::token::Token;
+use std::io::{self, Write};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TokenFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+// Writes one token per line as `file:line:column  Kind  "lexeme"`, with the first two
+// columns padded so the kinds and lexemes line up.
+pub fn write_text(file: &SourceFile, tokens: &[Token], out: &mut dyn Write) -> io::Result<()> {
+    let positions: Vec<String> = tokens
+        .iter()
+        .map(|t| format!("{}:{}:{}", file.name, t.span.line, t.span.column))
+        .collect();
+    let kinds: Vec<String> = tokens
+        .iter()
+        .map(|t| format!("{:?}", t.token_type))
+        .collect();
+    let position_width = positions.iter().map(|p| p.len()).max().unwrap_or(0);
+    let kind_width = kinds.iter().map(|k| k.len()).max().unwrap_or(0);
+
+    for ((token, position), kind) in tokens.iter().zip(&positions).zip(&kinds) {
+        writeln!(
+            out,
+            "{:pw$}  {:kw$}  {:?}",
+            position,
+            kind,
+            String::from_utf8_lossy(token.lexeme),
+            pw = position_width,
+            kw = kind_width
+        )?;
+    }
+    return Ok(());
+}
+
+// Writes one JSON object per token with the fields "file", "kind", "go_token" (the
+// go/token name), "start", "end", "line", "column" and "lexeme".
+pub fn write_json(file: &SourceFile, tokens: &[Token], out: &mut dyn Write) -> io::Result<()> {
+    let name = json::string(&file.name);
+    for token in tokens {
+        writeln!(
+            out,
+            "{{\"file\":{},\"kind\":\"{:?}\",\"go_token\":{},\"start\":{},\"end\":{},\"line\":{},\"column\":{},\"lexeme\":{}}}",
+            name,
+            token.token_type,
+            json::string(token.token_type.go_name()),
+            token.span.start,
+            token.span.end,
+            token.span.line,
+            token.span.column,
+            json::string(&String::from_utf8_lossy(token.lexeme))
+        )?;
+    }
+    return Ok(());
+}
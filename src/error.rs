@@ -1,15 +1,73 @@
-pub struct ErrorHandler();
+use crate::diagnostic::{json, render, Diagnostic, OutputFormat};
+use crate::source::{SourceMap, Span};
+use std::io;
+
+// Collects the diagnostics reported by every phase of the compiler.
+#[derive(Default)]
+pub struct ErrorHandler {
+    diagnostics: Vec<Diagnostic>,
+    color: bool,
+    format: OutputFormat,
+}
 
 impl ErrorHandler {
-    pub fn report(&self, line: usize, loc: &str, msg: &str) {
-        eprintln!("[line {}] Error{}: {}", line, loc, msg);
+    pub fn new() -> ErrorHandler {
+        ErrorHandler {
+            diagnostics: Vec::new(),
+            color: false,
+            format: OutputFormat::Human,
+        }
     }
 
-    pub fn error(&self, line: usize, msg: &str) {
-        self.report(line, "", msg);
+    pub fn set_color(&mut self, color: bool) {
+        self.color = color;
     }
 
-    pub fn new() -> ErrorHandler {
-        ErrorHandler()
+    pub fn set_format(&mut self, format: OutputFormat) {
+        self.format = format;
+    }
+
+    pub fn emit(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    pub fn error(&mut self, span: Span, code: &'static str, msg: &str) {
+        self.emit(
+            Diagnostic::error(msg)
+                .with_code(code)
+                .with_primary(span, ""),
+        );
+    }
+
+    pub fn has_errors(&self) -> bool {
+        return self.error_count() > 0;
+    }
+
+    pub fn error_count(&self) -> usize {
+        return self.diagnostics.iter().filter(|d| d.is_error()).count();
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        return &self.diagnostics;
+    }
+
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        return std::mem::take(&mut self.diagnostics);
+    }
+
+    pub fn extend(&mut self, diagnostics: Vec<Diagnostic>) {
+        self.diagnostics.extend(diagnostics);
+    }
+
+    pub fn report(&self, sources: &SourceMap, out: &mut dyn io::Write) -> io::Result<()> {
+        for diagnostic in &self.diagnostics {
+            match self.format {
+                OutputFormat::Human => {
+                    writeln!(out, "{}", render::render(diagnostic, sources, self.color))?
+                }
+                OutputFormat::Json => writeln!(out, "{}", json::render(diagnostic, sources))?,
+            }
+        }
+        return Ok(());
     }
 }
@@ -0,0 +1,217 @@
+// Interning of identifier and keyword text. Equal names get equal `Symbol`s, so later
+// phases compare and hash names as integers instead of byte strings.
+
+use crate::token::TokenTypes;
+
+#[cfg(test)]
+mod test;
+
+// The keywords. The symbol of a keyword is its index in this table, so keywords are
+// recognised with a perfect hash instead of a map lookup.
+const KEYWORDS: [(&str, TokenTypes); 28] = [
+    ("break", TokenTypes::Break),
+    ("case", TokenTypes::Case),
+    ("chan", TokenTypes::Chan),
+    ("const", TokenTypes::Const),
+    ("continue", TokenTypes::Continue),
+    ("default", TokenTypes::Default),
+    ("defer", TokenTypes::Defer),
+    ("else", TokenTypes::Else),
+    ("fallthrough", TokenTypes::Fallthrough),
+    ("for", TokenTypes::For),
+    ("func", TokenTypes::Func),
+    ("go", TokenTypes::Go),
+    ("goto", TokenTypes::Goto),
+    ("if", TokenTypes::If),
+    ("import", TokenTypes::Import),
+    ("interface", TokenTypes::Interface),
+    ("map", TokenTypes::Map),
+    ("package", TokenTypes::Package),
+    ("range", TokenTypes::Range),
+    ("return", TokenTypes::Return),
+    ("select", TokenTypes::Select),
+    ("struct", TokenTypes::Struct),
+    ("switch", TokenTypes::Switch),
+    ("type", TokenTypes::Type),
+    ("var", TokenTypes::Var),
+    ("true", TokenTypes::True),
+    ("false", TokenTypes::False),
+    ("nil", TokenTypes::Nil),
+];
+
+// Maps `keyword_hash` of each keyword to its index plus one. Building it at compile time
+// fails if two keywords collide.
+const KEYWORD_TABLE: [u8; 128] = keyword_table();
+
+const fn keyword_hash(s: &[u8]) -> usize {
+    return ((((s[0] as usize) << 2) ^ ((s[1] as usize) << 4)) + s.len()) & 127;
+}
+
+const fn keyword_table() -> [u8; 128] {
+    let mut table = [0; 128];
+    let mut i = 0;
+    while i < KEYWORDS.len() {
+        let h = keyword_hash(KEYWORDS[i].0.as_bytes());
+        assert!(table[h] == 0, "keyword hash collision");
+        table[h] = i as u8 + 1;
+        i += 1;
+    }
+    return table;
+}
+
+fn keyword_index(name: &str) -> Option<usize> {
+    let bytes = name.as_bytes();
+    if bytes.len() < 2 {
+        return None;
+    }
+    let i = (KEYWORD_TABLE[keyword_hash(bytes)] as usize).checked_sub(1)?;
+    if KEYWORDS[i].0 != name {
+        return None;
+    }
+    return Some(i);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    pub fn index(self) -> usize {
+        return self.0 as usize;
+    }
+}
+
+// Keywords are interned from the start. Other names get the symbols that follow them and
+// are stored back to back in one string, indexed by an open-addressing hash table, so
+// interning a new name rarely allocates.
+pub struct Interner {
+    text: String,
+    // The end of each name in `text`; a name starts where the previous one ends.
+    ends: Vec<u32>,
+    hashes: Vec<u32>,
+    // Name numbers plus one, with zero marking an empty slot. The length is zero or a
+    // power of two, and at most half of the slots are used.
+    table: Vec<u32>,
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Interner {
+            text: String::new(),
+            ends: Vec::new(),
+            hashes: Vec::new(),
+            table: Vec::new(),
+        }
+    }
+
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Some(i) = keyword_index(name) {
+            return Symbol(i as u32);
+        }
+        let hash = fnv(name);
+        let slot = match self.find(name, hash) {
+            Ok(n) => return Symbol((KEYWORDS.len() + n) as u32),
+            Err(slot) => slot,
+        };
+
+        let n = self.ends.len();
+        self.text.push_str(name);
+        self.ends.push(self.text.len() as u32);
+        self.hashes.push(hash);
+        self.table[slot] = n as u32 + 1;
+        return Symbol((KEYWORDS.len() + n) as u32);
+    }
+
+    // Returns the symbol of `name` if it has been interned, without interning it.
+    pub fn lookup(&self, name: &str) -> Option<Symbol> {
+        if let Some(i) = keyword_index(name) {
+            return Some(Symbol(i as u32));
+        }
+        if self.table.is_empty() {
+            return None;
+        }
+        let mut slot = fnv(name) as usize & (self.table.len() - 1);
+        loop {
+            let n = (self.table[slot] as usize).checked_sub(1)?;
+            if self.name(n) == name {
+                return Some(Symbol((KEYWORDS.len() + n) as u32));
+            }
+            slot = (slot + 1) & (self.table.len() - 1);
+        }
+    }
+
+    pub fn resolve(&self, symbol: Symbol) -> &str {
+        match KEYWORDS.get(symbol.index()) {
+            Some((keyword, _)) => return keyword,
+            None => return self.name(symbol.index() - KEYWORDS.len()),
+        }
+    }
+
+    // The token type of a keyword symbol, or None for any other name.
+    pub fn keyword(&self, symbol: Symbol) -> Option<TokenTypes> {
+        return KEYWORDS
+            .get(symbol.index())
+            .map(|&(_, token_type)| token_type);
+    }
+
+    // The number of interned names, counting the keywords.
+    pub fn len(&self) -> usize {
+        return KEYWORDS.len() + self.ends.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.len() == 0;
+    }
+
+    fn name(&self, n: usize) -> &str {
+        let start = if n == 0 { 0 } else { self.ends[n - 1] as usize };
+        return &self.text[start..self.ends[n] as usize];
+    }
+
+    // Returns the number of `name`, or the empty slot where it belongs, growing the
+    // table first if it is half full.
+    fn find(&mut self, name: &str, hash: u32) -> Result<usize, usize> {
+        if (self.ends.len() + 1) * 2 > self.table.len() {
+            self.grow();
+        }
+        let mask = self.table.len() - 1;
+        let mut slot = hash as usize & mask;
+        loop {
+            let n = match (self.table[slot] as usize).checked_sub(1) {
+                Some(n) => n,
+                None => return Err(slot),
+            };
+            if self.hashes[n] == hash && self.name(n) == name {
+                return Ok(n);
+            }
+            slot = (slot + 1) & mask;
+        }
+    }
+
+    fn grow(&mut self) {
+        let len = usize::max(64, self.table.len() * 2);
+        self.table = vec![0; len];
+        for (n, &hash) in self.hashes.iter().enumerate() {
+            let mut slot = hash as usize & (len - 1);
+            while self.table[slot] != 0 {
+                slot = (slot + 1) & (len - 1);
+            }
+            self.table[slot] = n as u32 + 1;
+        }
+    }
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        return Interner::new();
+    }
+}
+
+// The 32-bit FNV-1a hash, which is fast for short names like identifiers.
+fn fnv(name: &str) -> u32 {
+    let mut hash: u32 = 0x811c9dc5;
+    for &b in name.as_bytes() {
+        hash ^= b as u32;
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    return hash;
+}
@@ -0,0 +1,65 @@
+use crate::intern::Interner;
+use crate::token::TokenTypes;
+
+#[test]
+fn intern_names() {
+    let mut interner = Interner::new();
+    let a = interner.intern("main");
+    let b = interner.intern("Println");
+    let c = interner.intern("main");
+
+    assert_eq!(a, c);
+    assert_ne!(a, b);
+    assert_eq!(interner.resolve(a), "main");
+    assert_eq!(interner.resolve(b), "Println");
+    assert_eq!(interner.lookup("Println"), Some(b));
+    assert_eq!(interner.lookup("fmt"), None);
+}
+
+#[test]
+fn keywords_are_pre_interned() {
+    let mut interner = Interner::new();
+    let keywords = interner.len();
+    assert_eq!(keywords, 28);
+
+    let func = interner.lookup("func").unwrap();
+    assert_eq!(interner.keyword(func), Some(TokenTypes::Func));
+    let nil = interner.intern("nil");
+    assert_eq!(interner.keyword(nil), Some(TokenTypes::Nil));
+    assert_eq!(interner.resolve(nil), "nil");
+    assert_eq!(interner.len(), keywords);
+
+    // Names that share a keyword's hash are not keywords.
+    for name in ["function", "fo", "gox", "i", "nilly", "_"] {
+        let symbol = interner.intern(name);
+        assert_eq!(interner.keyword(symbol), None);
+        assert_eq!(interner.resolve(symbol), name);
+    }
+    assert_eq!(interner.len(), keywords + 6);
+}
+
+#[test]
+fn intern_unicode_names() {
+    let mut interner = Interner::new();
+    let pi = interner.intern("π");
+    assert_eq!(interner.intern("π"), pi);
+    assert_ne!(interner.intern("日本語"), pi);
+    assert_eq!(interner.resolve(pi), "π");
+}
+
+#[test]
+fn intern_many_names() {
+    let mut interner = Interner::new();
+    let symbols: Vec<_> = (0..10_000)
+        .map(|i| interner.intern(&format!("name{}", i)))
+        .collect();
+
+    assert_eq!(interner.len(), 28 + 10_000);
+    for (i, &symbol) in symbols.iter().enumerate() {
+        let name = format!("name{}", i);
+        assert_eq!(interner.resolve(symbol), name);
+        assert_eq!(interner.lookup(&name), Some(symbol));
+        assert_eq!(interner.intern(&name), symbol);
+    }
+    assert_eq!(interner.lookup("name10000"), None);
+}
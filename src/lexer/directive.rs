@@ -0,0 +1,124 @@
+use crate::source::{LineDirective, Span};
+
+// A compiler directive found in a comment, such as `//go:build linux` or
+// `//line gen.go:10`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Directive {
+    pub kind: DirectiveKind,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirectiveKind {
+    // The constraint expression of a `//go:build` line, evaluated by the build module.
+    Build(String),
+    NoInline,
+    NoSplit,
+    // The patterns of a `//go:embed` line, with any quotes removed.
+    Embed(Vec<String>),
+    Line(LineDirective),
+    // Any other `//go:` directive, such as `//go:linkname`.
+    Other { name: String, args: String },
+}
+
+// Parses the directive in `comment`, the full text of a comment including its
+// delimiters. `offset` is where the positions of a line directive take effect. Returns
+// None for ordinary comments and an error message for malformed directives.
+pub fn parse(comment: &[u8], offset: usize) -> Option<Result<DirectiveKind, String>> {
+    let text = std::str::from_utf8(comment).ok()?;
+    if let Some(rest) = text.strip_prefix("//go:") {
+        let (name, args) = match rest.find([' ', '\t']) {
+            Some(i) => (&rest[..i], rest[i..].trim()),
+            None => (rest, ""),
+        };
+        return Some(go_directive(name, args));
+    }
+    let position = match text.strip_prefix("//line ") {
+        Some(rest) => rest,
+        None => text.strip_prefix("/*line ")?.strip_suffix("*/")?,
+    };
+    // Like gc, a comment without a colon is not a line directive at all.
+    if !position.contains(':') {
+        return None;
+    }
+    return Some(line_directive(position.trim_end(), offset).map(DirectiveKind::Line));
+}
+
+fn go_directive(name: &str, args: &str) -> Result<DirectiveKind, String> {
+    match name {
+        "build" if args.is_empty() => return Err("Empty //go:build constraint.".to_string()),
+        "build" => return Ok(DirectiveKind::Build(args.to_string())),
+        "noinline" => return Ok(DirectiveKind::NoInline),
+        "nosplit" => return Ok(DirectiveKind::NoSplit),
+        "embed" => {
+            let patterns = embed_patterns(args)?;
+            if patterns.is_empty() {
+                return Err("A //go:embed directive needs at least one pattern.".to_string());
+            }
+            return Ok(DirectiveKind::Embed(patterns));
+        }
+        _ => {
+            return Ok(DirectiveKind::Other {
+                name: name.to_string(),
+                args: args.to_string(),
+            })
+        }
+    }
+}
+
+// Splits the arguments of `//go:embed` at spaces. Patterns containing spaces can be
+// written as Go string literals.
+fn embed_patterns(args: &str) -> Result<Vec<String>, String> {
+    let mut patterns = Vec::new();
+    let mut rest = args.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let (pattern, tail) = match c {
+            '"' | '`' => {
+                let end = match rest[1..].find(c) {
+                    Some(end) => end + 1,
+                    None => return Err(format!("Unterminated pattern {} in //go:embed.", rest)),
+                };
+                (&rest[1..end], &rest[end + 1..])
+            }
+            _ => match rest.find([' ', '\t']) {
+                Some(end) => (&rest[..end], &rest[end..]),
+                None => (rest, ""),
+            },
+        };
+        patterns.push(pattern.to_string());
+        rest = tail.trim_start();
+    }
+    return Ok(patterns);
+}
+
+// Parses `filename:line` or `filename:line:column`. The filename may be empty, in which
+// case the previous one stays in effect.
+fn line_directive(position: &str, offset: usize) -> Result<LineDirective, String> {
+    let invalid = || format!("Invalid line number in //line directive \"{}\".", position);
+    let (rest, last) = position.rsplit_once(':').ok_or_else(invalid)?;
+    let last = number(last).ok_or_else(invalid)?;
+    let (file, line, column) = match rest
+        .rsplit_once(':')
+        .and_then(|(f, l)| Some((f, number(l)?)))
+    {
+        Some((file, line)) => (file, line, Some(last)),
+        None => (rest, last, None),
+    };
+    return Ok(LineDirective {
+        offset,
+        file: if file.is_empty() {
+            None
+        } else {
+            Some(file.to_string())
+        },
+        line,
+        column,
+    });
+}
+
+fn number(text: &str) -> Option<usize> {
+    if text.is_empty() || !text.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    return text.parse().ok().filter(|&n| n > 0);
+}
@@ -0,0 +1,209 @@
+// Re-lexing after an edit. Scanning restarts at the last token the edit cannot have
+// changed and stops at the first token that the old stream also has at the same place;
+// the old tokens after it are reused with their positions shifted.
+//
+// The old tokens must have been scanned without comments, and the diagnostics of reused
+// Illegal tokens keep the spans they had before the edit.
+
+use crate::diagnostic::Diagnostic;
+use crate::intern::Interner;
+use crate::lexer::{ends_statement, Lexer};
+use crate::source::Span;
+use crate::token::{Token, TokenTypes};
+use std::mem;
+use std::ops::Range;
+
+// How far past the end of a token the lexer may look when scanning it, as it does for
+// "..." after a '.'.
+const LOOKAHEAD: usize = 2;
+
+// Replaces the bytes in `range` of the old source with `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub range: Range<usize>,
+    pub text: Vec<u8>,
+}
+
+impl Edit {
+    pub fn new(range: Range<usize>, text: &[u8]) -> Edit {
+        Edit {
+            range,
+            text: text.to_vec(),
+        }
+    }
+
+    // Returns the new source.
+    pub fn apply(&self, src: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(src.len() + self.text.len());
+        out.extend_from_slice(&src[..self.range.start]);
+        out.extend_from_slice(&self.text);
+        out.extend_from_slice(&src[self.range.end..]);
+        return out;
+    }
+
+    // The change in length of the source.
+    fn delta(&self) -> isize {
+        return self.text.len() as isize - self.range.len() as isize;
+    }
+}
+
+pub struct Relexed<'src> {
+    // The complete token stream of the new source.
+    pub tokens: Vec<Token<'src>>,
+    // The old tokens in `old` were replaced by the new tokens in `new`. The tokens before
+    // them are unchanged and the tokens after them only moved.
+    pub old: Range<usize>,
+    pub new: Range<usize>,
+    // The diagnostics of the tokens that were scanned again.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+// Re-lexes `src`, the result of applying `edit` to the source of `tokens`. Names are
+// interned in `interner`, which should be the one the old tokens were scanned with.
+pub fn relex<'src>(
+    tokens: &[Token<'_>],
+    edit: &Edit,
+    src: &'src [u8],
+    interner: &mut Interner,
+) -> Relexed<'src> {
+    let file = tokens.first().map(|t| t.span.file).unwrap_or_default();
+    // The lexer state after a token follows from its type, except after Illegal tokens,
+    // so scanning never restarts after one of those. Nor after a zero-width semicolon,
+    // which depends on the comments after it however far they reach.
+    let mut keep = tokens
+        .iter()
+        .take_while(|t| t.span.end + LOOKAHEAD <= edit.range.start)
+        .count();
+    while keep > 0 && !restartable(&tokens[keep - 1]) {
+        keep -= 1;
+    }
+
+    let mut lexer = Lexer::new(src)
+        .with_file(file)
+        .with_interner(mem::take(interner));
+    if keep > 0 {
+        let last = &tokens[keep - 1];
+        let (line, line_start) = end_of(last);
+        lexer.current = last.span.end;
+        lexer.line = line;
+        lexer.line_start = line_start;
+        lexer.insert_semi = ends_statement(last.token_type);
+    }
+
+    let mut out: Vec<Token<'src>> = tokens[..keep]
+        .iter()
+        .map(|t| rebase(t, t.span, src))
+        .collect();
+    let delta = edit.delta();
+    let edited_end = edit.range.start + edit.text.len();
+    let mut old = keep;
+    let mut resync: Option<(usize, Token<'src>)> = None;
+    for token in lexer.by_ref() {
+        if token.span.start >= edited_end && token.token_type != TokenTypes::Illegal {
+            let old_start = (token.span.start as isize - delta) as usize;
+            while old < tokens.len() && tokens[old].span.start < old_start {
+                old += 1;
+            }
+            if old < tokens.len()
+                && tokens[old].span.start == old_start
+                && same_token(&tokens[old], &token)
+            {
+                resync = Some((old, token));
+                break;
+            }
+        }
+        out.push(token);
+    }
+    let diagnostics = lexer.take_diagnostics();
+    *interner = lexer.take_interner();
+
+    // Scanning restarts a little before the edit, so the first tokens scanned again may
+    // not have changed at all.
+    let mut first = keep;
+    let old_end = resync.as_ref().map_or(tokens.len(), |(old, _)| *old);
+    while first < out.len()
+        && first < old_end
+        && tokens[first].span == out[first].span
+        && tokens[first].token_type == out[first].token_type
+    {
+        first += 1;
+    }
+    let new = first..out.len();
+    let Some((old, token)) = resync else {
+        return Relexed {
+            tokens: out,
+            old: first..tokens.len(),
+            new,
+            diagnostics,
+        };
+    };
+
+    // Tokens on the line of the resynchronizing token also move sideways.
+    let anchor = tokens[old].span;
+    let line_delta = token.span.line as isize - anchor.line as isize;
+    let column_delta = token.span.column as isize - anchor.column as isize;
+    out.push(token);
+    for t in &tokens[old + 1..] {
+        let column = if t.span.line == anchor.line {
+            (t.span.column as isize + column_delta) as usize
+        } else {
+            t.span.column
+        };
+        let span = Span::new(
+            t.span.file,
+            (t.span.start as isize + delta) as usize,
+            (t.span.end as isize + delta) as usize,
+            (t.span.line as isize + line_delta) as usize,
+            column,
+        );
+        out.push(rebase(t, span, src));
+    }
+    return Relexed {
+        tokens: out,
+        old: first..old,
+        new,
+        diagnostics,
+    };
+}
+
+fn restartable(token: &Token) -> bool {
+    return match token.token_type {
+        TokenTypes::Illegal | TokenTypes::Comment | TokenTypes::DocComment => false,
+        TokenTypes::Semicolon => !token.span.is_empty(),
+        _ => true,
+    };
+}
+
+// The line and line start offset at the end of `token`.
+fn end_of(token: &Token) -> (usize, usize) {
+    let span = token.span;
+    // A zero-width semicolon has the lexeme "\n" without covering a newline.
+    if span.len() == token.lexeme.len() {
+        if let Some(i) = token.lexeme.iter().rposition(|&b| b == b'\n') {
+            let lines = token.lexeme.iter().filter(|&&b| b == b'\n').count();
+            return (span.line + lines, span.start + i + 1);
+        }
+    }
+    return (span.line, span.start + 1 - span.column);
+}
+
+fn same_token(old: &Token, new: &Token) -> bool {
+    return old.token_type == new.token_type && old.span.len() == new.span.len();
+}
+
+// Copies `token` to `span` in the new source.
+fn rebase<'src>(token: &Token<'_>, span: Span, src: &'src [u8]) -> Token<'src> {
+    let lexeme: &'src [u8] = if token.token_type == TokenTypes::Semicolon && token.lexeme == b"\n" {
+        b"\n"
+    } else {
+        &src[span.start..span.end]
+    };
+    return Token {
+        token_type: token.token_type,
+        lexeme,
+        value: token.value.clone(),
+        symbol: token.symbol,
+        error: token.error.clone(),
+        span,
+    };
+}
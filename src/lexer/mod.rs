@@ -1,162 +1,439 @@
+use crate::diagnostic::{codes, Diagnostic};
 use crate::error;
+use crate::intern::Interner;
+use crate::source::{FileId, LineDirective, Span};
 use crate::token::{Token, TokenTypes};
+use std::collections::VecDeque;
+use std::iter::FusedIterator;
 use std::str;
 
+pub mod directive;
+pub mod incremental;
 #[cfg(test)]
 mod test;
+mod unicode;
+
+use directive::{Directive, DirectiveKind};
+
+const BOM: char = '\u{FEFF}';
+
+// Whether a newline after a token of this type ends the statement.
+fn ends_statement(token_type: TokenTypes) -> bool {
+    return matches!(
+        token_type,
+        TokenTypes::Identifier
+            | TokenTypes::Int
+            | TokenTypes::Octal
+            | TokenTypes::Hex
+            | TokenTypes::Binary
+            | TokenTypes::Float
+            | TokenTypes::Imag
+            | TokenTypes::Char
+            | TokenTypes::String
+            | TokenTypes::RawString
+            | TokenTypes::True
+            | TokenTypes::False
+            | TokenTypes::Nil
+            | TokenTypes::Break
+            | TokenTypes::Continue
+            | TokenTypes::Fallthrough
+            | TokenTypes::Return
+            | TokenTypes::Increment
+            | TokenTypes::Decrement
+            | TokenTypes::RParen
+            | TokenTypes::RBrack
+            | TokenTypes::RBrace
+    );
+}
 
 pub struct Lexer<'src> {
     current: usize,
-    src: &'src Vec<u8>,
+    src: &'src [u8],
     src_len: usize,
     start: usize,
+    start_line: usize,
+    start_column: usize,
     line: usize,
+    line_start: usize,
+    file: FileId,
+    insert_semi: bool,
+    // Set once the Eof token has been scanned.
+    done: bool,
+    comments: bool,
+    // Tokens scanned ahead while deciding whether a run of comments is a doc comment.
+    pending: VecDeque<Token<'src>>,
+    // The line of the last token that was not a comment.
+    last_line: usize,
+    directives: Vec<Directive>,
+    interner: Interner,
     error_handler: error::ErrorHandler,
 }
 
-impl Lexer<'_> {
-    pub fn new(src: &Vec<u8>) -> Lexer {
+impl<'src> Lexer<'src> {
+    pub fn new(src: &'src [u8]) -> Lexer<'src> {
+        // A byte order mark is only permitted as the very first character.
+        let current = if src.starts_with(b"\xEF\xBB\xBF") {
+            3
+        } else {
+            0
+        };
         Lexer {
-            current: 0,
+            current,
             src,
             src_len: src.len(),
-            start: 0,
+            start: current,
+            start_line: 1,
+            start_column: current + 1,
             line: 1,
+            line_start: 0,
+            file: FileId::default(),
+            insert_semi: false,
+            done: false,
+            comments: false,
+            pending: VecDeque::new(),
+            last_line: 0,
+            directives: Vec::new(),
+            interner: Interner::new(),
             error_handler: error::ErrorHandler::new(),
         }
     }
 
-    fn scan_token(&mut self) -> Token {
-        if self.is_at_end() {
-            return self.get_token(TokenTypes::Eof);
-        }
-        self.start = self.current;
-        let c: u8 = self.advance();
-        match c {
-            b'(' => return self.get_token(TokenTypes::LParen),
-            b')' => return self.get_token(TokenTypes::RParen),
-            b'{' => return self.get_token(TokenTypes::LBrace),
-            b'}' => return self.get_token(TokenTypes::RBrace),
-            b'[' => return self.get_token(TokenTypes::LBrack),
-            b']' => return self.get_token(TokenTypes::RBrack),
-            b',' => return self.get_token(TokenTypes::Comma),
-            b'-' => {
-                if self.matches(b'-') {
-                    return self.get_token(TokenTypes::Decrement);
-                } else if self.matches(b'=') {
-                    return self.get_token(TokenTypes::SubAssign);
+    pub fn with_file(mut self, file: FileId) -> Self {
+        self.file = file;
+        return self;
+    }
+
+    pub fn file(&self) -> FileId {
+        return self.file;
+    }
+
+    // Emits comments as Comment tokens instead of skipping them. Comments that directly
+    // precede a declaration are emitted as DocComment tokens.
+    pub fn with_comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
+        return self;
+    }
+
+    // Interns names into `interner`, so that the symbols of several files agree.
+    pub fn with_interner(mut self, interner: Interner) -> Self {
+        self.interner = interner;
+        return self;
+    }
+
+    pub fn interner(&self) -> &Interner {
+        return &self.interner;
+    }
+
+    pub fn take_interner(&mut self) -> Interner {
+        return std::mem::take(&mut self.interner);
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        return self.error_handler.diagnostics();
+    }
+
+    pub fn has_errors(&self) -> bool {
+        return self.error_handler.has_errors();
+    }
+
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        return self.error_handler.take_diagnostics();
+    }
+
+    // The directives found so far, in source order.
+    pub fn directives(&self) -> &[Directive] {
+        return &self.directives;
+    }
+
+    pub fn take_directives(&mut self) -> Vec<Directive> {
+        return std::mem::take(&mut self.directives);
+    }
+
+    // The line directives found so far, for `SourceMap::set_line_directives`.
+    pub fn line_directives(&self) -> Vec<LineDirective> {
+        return self
+            .directives
+            .iter()
+            .filter_map(|d| match &d.kind {
+                DirectiveKind::Line(line) => Some(line.clone()),
+                _ => None,
+            })
+            .collect();
+    }
+
+    // Scans the next token. Whitespace, skipped comments and invalid characters go
+    // round the loop rather than recursing, so long runs of them cannot exhaust the stack.
+    fn scan_token(&mut self) -> Token<'src> {
+        loop {
+            self.start = self.current;
+            self.start_line = self.line;
+            self.start_column = self.column(self.start);
+            if self.is_at_end() {
+                if self.insert_semi {
+                    return self.auto_semicolon();
                 }
-                return self.get_token(TokenTypes::Sub);
+                return self.get_token(TokenTypes::Eof);
             }
-            b'+' => {
-                if self.matches(b'+') {
-                    return self.get_token(TokenTypes::Increment);
-                } else if self.matches(b'=') {
-                    return self.get_token(TokenTypes::AddAssign);
+            let c: u8 = self.advance();
+            match c {
+                b'(' => return self.get_token(TokenTypes::LParen),
+                b')' => return self.get_token(TokenTypes::RParen),
+                b'{' => return self.get_token(TokenTypes::LBrace),
+                b'}' => return self.get_token(TokenTypes::RBrace),
+                b'[' => return self.get_token(TokenTypes::LBrack),
+                b']' => return self.get_token(TokenTypes::RBrack),
+                b',' => return self.get_token(TokenTypes::Comma),
+                b'-' => {
+                    if self.matches(b'-') {
+                        return self.get_token(TokenTypes::Decrement);
+                    } else if self.matches(b'=') {
+                        return self.get_token(TokenTypes::SubAssign);
+                    }
+                    return self.get_token(TokenTypes::Sub);
                 }
-                return self.get_token(TokenTypes::Add);
-            }
-            b';' => return self.get_token(TokenTypes::Semicolon),
-            b'*' => return self.eq_after(TokenTypes::Mul, TokenTypes::MulAssign),
-            b'!' => return self.eq_after(TokenTypes::Not, TokenTypes::Neq),
-            b'=' => return self.eq_after(TokenTypes::Assign, TokenTypes::Eql),
-            b':' => return self.eq_after(TokenTypes::Colon, TokenTypes::Define),
-            b'<' => {
-                if self.matches(b'-') {
-                    return self.get_token(TokenTypes::Arrow);
-                } else if self.matches(b'<') {
-                    return self.eq_after(TokenTypes::Lshift, TokenTypes::LshiftAssign);
+                b'+' => {
+                    if self.matches(b'+') {
+                        return self.get_token(TokenTypes::Increment);
+                    } else if self.matches(b'=') {
+                        return self.get_token(TokenTypes::AddAssign);
+                    }
+                    return self.get_token(TokenTypes::Add);
                 }
-                return self.eq_after(TokenTypes::Lss, TokenTypes::Leq);
-            }
-            b'>' => {
-                if self.matches(b'>') {
-                    return self.eq_after(TokenTypes::Rshift, TokenTypes::RshiftAssign);
+                b';' => return self.get_token(TokenTypes::Semicolon),
+                b'*' => return self.eq_after(TokenTypes::Mul, TokenTypes::MulAssign),
+                b'!' => return self.eq_after(TokenTypes::Not, TokenTypes::Neq),
+                b'=' => return self.eq_after(TokenTypes::Assign, TokenTypes::Eql),
+                b':' => return self.eq_after(TokenTypes::Colon, TokenTypes::Define),
+                b'<' => {
+                    if self.matches(b'-') {
+                        return self.get_token(TokenTypes::Arrow);
+                    } else if self.matches(b'<') {
+                        return self.eq_after(TokenTypes::Lshift, TokenTypes::LshiftAssign);
+                    }
+                    return self.eq_after(TokenTypes::Lss, TokenTypes::Leq);
                 }
-                return self.eq_after(TokenTypes::Gtr, TokenTypes::Geq);
-            }
-            b'^' => return self.eq_after(TokenTypes::Caret, TokenTypes::CaretAssign),
-            b'%' => return self.eq_after(TokenTypes::Percent, TokenTypes::PercentAssign),
-            b'&' => {
-                if self.matches(b'&') {
-                    return self.get_token(TokenTypes::And);
-                } else if self.matches(b'=') {
-                    return self.get_token(TokenTypes::AndAssign);
-                } else if self.matches(b'^') {
-                    if self.peek() == b'=' {
-                        self.advance();
-                        return self.get_token(TokenTypes::BitClearAssign);
+                b'>' => {
+                    if self.matches(b'>') {
+                        return self.eq_after(TokenTypes::Rshift, TokenTypes::RshiftAssign);
+                    }
+                    return self.eq_after(TokenTypes::Gtr, TokenTypes::Geq);
+                }
+                b'^' => return self.eq_after(TokenTypes::Caret, TokenTypes::CaretAssign),
+                b'%' => return self.eq_after(TokenTypes::Percent, TokenTypes::PercentAssign),
+                b'~' => return self.get_token(TokenTypes::Tilde),
+                b'&' => {
+                    if self.matches(b'&') {
+                        return self.get_token(TokenTypes::And);
+                    } else if self.matches(b'=') {
+                        return self.get_token(TokenTypes::AndAssign);
+                    } else if self.matches(b'^') {
+                        if self.peek() == b'=' {
+                            self.advance();
+                            return self.get_token(TokenTypes::BitClearAssign);
+                        } else {
+                            return self.get_token(TokenTypes::BitClear);
+                        }
                     } else {
-                        return self.get_token(TokenTypes::BitClear);
+                        return self.get_token(TokenTypes::Amp);
                     }
-                } else {
-                    return self.get_token(TokenTypes::Amp);
                 }
-            }
-            b'|' => {
-                if self.matches(b'=') {
-                    return self.get_token(TokenTypes::OrAssign);
-                } else if self.matches(b'|') {
-                    return self.get_token(TokenTypes::OrOr);
-                } else {
-                    return self.get_token(TokenTypes::Or);
+                b'|' => {
+                    if self.matches(b'=') {
+                        return self.get_token(TokenTypes::OrAssign);
+                    } else if self.matches(b'|') {
+                        return self.get_token(TokenTypes::OrOr);
+                    } else {
+                        return self.get_token(TokenTypes::Or);
+                    }
                 }
-            }
-            b'/' => {
-                if self.matches(b'/') {
-                    while self.peek() != b'\n' && !self.is_at_end() {
-                        self.advance();
+                b'/' => {
+                    if self.peek() == b'/' || self.peek() == b'*' {
+                        // As in gc, a comment that runs to the end of the line ends the
+                        // statement, so the semicolon is placed before the comment.
+                        if self.insert_semi && self.reaches_line_end(self.start) {
+                            self.current = self.start;
+                            return self.auto_semicolon();
+                        }
+                        let token = self.comment();
+                        if self.comments {
+                            return token;
+                        }
+                        continue;
                     }
-                    return self.scan_token();
-                } else if self.matches(b'*') {
-                    while !self.is_at_end() {
-                        if self.peek() == b'*' && self.peek_next() == b'/' {
-                            break;
-                        } else if self.peek() == b'\n' {
-                            self.line += 1;
+                    return self.eq_after(TokenTypes::Quo, TokenTypes::QuoAssign);
+                }
+                b' ' | b'\r' | b'\t' | b'\0' => continue,
+                b'\n' => {
+                    if self.insert_semi {
+                        let token = self.auto_semicolon();
+                        self.newline();
+                        return token;
+                    }
+                    self.newline();
+                    continue;
+                }
+                b'"' => return self.string(),
+                b'\'' => return self.rune(),
+                b'`' => return self.raw_string(),
+                _ => {
+                    if c == b'.' {
+                        if self.peek().is_ascii_digit() {
+                            return self.number(c);
+                        } else if self.peek() == b'.' && self.peek_next() == b'.' {
+                            self.advance();
+                            self.advance();
+                            return self.get_token(TokenTypes::Ellipsis);
+                        } else {
+                            return self.get_token(TokenTypes::Dot);
                         }
-                        self.advance();
                     }
-                    if self.is_at_end() {
-                        self.error_handler.error(self.line, "Unterminated comment.");
-                        return self.scan_token();
+                    if c.is_ascii_digit() {
+                        return self.number(c);
+                    } else if c.is_ascii_alphabetic() || c == b'_' {
+                        return self.identifier();
+                    }
+
+                    let offset = self.start;
+                    match self.finish_char(c) {
+                        Some(ch) if unicode::is_letter(ch) => return self.identifier(),
+                        // Invalid encodings and misplaced byte order marks have already
+                        // been reported.
+                        Some(BOM) | None => return self.illegal(),
+                        Some(ch) => {
+                            let diagnostic =
+                                Diagnostic::error(&format!("Unexpected character {}.", ch))
+                                    .with_code(codes::UNEXPECTED_CHARACTER)
+                                    .with_primary(self.span_from(offset), "");
+                            self.emit(diagnostic);
+                            return self.illegal();
+                        }
                     }
-                    self.advance();
-                    self.advance();
-                    return self.scan_token();
-                } else {
-                    return self.eq_after(TokenTypes::Quo, TokenTypes::QuoAssign);
                 }
             }
-            b' ' | b'\r' | b'\t' | b'\0' => return self.scan_token(),
-            b'\n' => {
-                self.line += 1;
-                return self.scan_token();
+        }
+    }
+
+    // Scans a comment whose leading '/' has been consumed.
+    fn comment(&mut self) -> Token<'src> {
+        if self.matches(b'/') {
+            while self.peek() != b'\n' && !self.is_at_end() {
+                let c = self.advance();
+                self.finish_char(c);
             }
-            b'"' => return self.string(),
-            b'`' => return self.raw_string(),
-            _ => {
-                if c == b'.' {
-                    if self.peek() == b'.' && self.peek_next() == b'.' {
-                        self.advance();
-                        self.advance();
-                        return self.get_token(TokenTypes::Ellipsis);
-                    } else {
-                        return self.get_token(TokenTypes::Dot);
-                    }
+            // `//line` must start its line; `//go:` directives may be indented.
+            let at_line_start = self.src[self.line_start..self.start]
+                .iter()
+                .all(|&c| c == b' ' || c == b'\t');
+            if self.start_column == 1
+                || (at_line_start && self.src.get(self.start + 2) == Some(&b'g'))
+            {
+                // The positions of a line directive apply from the next line on.
+                self.directive(usize::min(self.current + 1, self.src_len));
+            }
+            return self.get_token(TokenTypes::Comment);
+        }
+
+        self.advance();
+        while !self.is_at_end() {
+            if self.peek() == b'*' && self.peek_next() == b'/' {
+                self.advance();
+                self.advance();
+                self.directive(self.current);
+                return self.get_token(TokenTypes::Comment);
+            }
+            let c = self.advance();
+            if c == b'\n' {
+                self.newline();
+            } else {
+                self.finish_char(c);
+            }
+        }
+        let diagnostic = Diagnostic::error("Unterminated comment.")
+            .with_code(codes::UNTERMINATED_COMMENT)
+            .with_primary(self.start_span(2), "comment starts here")
+            .with_suggestion(self.span_from(self.current), "*/", "close the comment");
+        self.emit(diagnostic);
+        return self.get_token(TokenTypes::Comment);
+    }
+
+    // Records the directive in the comment just scanned, if there is one.
+    fn directive(&mut self, offset: usize) {
+        let span = self.span();
+        match directive::parse(&self.src[self.start..self.current], offset) {
+            Some(Ok(kind)) => self.directives.push(Directive { kind, span }),
+            Some(Err(msg)) => self
+                .error_handler
+                .error(span, codes::INVALID_DIRECTIVE, &msg),
+            None => {}
+        }
+    }
+
+    // Reports whether the comments starting at `offset` are followed by the end of the
+    // line, either because one of them is a line comment or contains a newline, or
+    // because only whitespace follows them.
+    fn reaches_line_end(&self, mut offset: usize) -> bool {
+        let src = &self.src[..self.src_len];
+        loop {
+            if src[offset + 1] == b'/' {
+                return true;
+            }
+            let mut i = offset + 2;
+            loop {
+                if i >= src.len() || src[i] == b'\n' {
+                    return true;
                 }
-                if (c >= b'0' && c <= b'9') || c == b'.' {
-                    return self.number(c);
-                } else if (c >= b'a' && c <= b'z') || (c >= b'A' && c <= b'Z') || c == b'_' {
-                    return self.identifier();
-                } else {
-                    self.error_handler.error(
-                        self.line,
-                        &format!("Unexpected character {}.", str::from_utf8(&[c]).unwrap()),
-                    );
-                    return self.scan_token();
+                if src[i] == b'*' && src.get(i + 1) == Some(&b'/') {
+                    break;
                 }
+                i += 1;
+            }
+            i += 2;
+            while i < src.len() && matches!(src[i], b' ' | b'\t' | b'\r') {
+                i += 1;
+            }
+            if i >= src.len() || src[i] == b'\n' {
+                return true;
+            }
+            if src[i] != b'/' || !matches!(src.get(i + 1), Some(b'/') | Some(b'*')) {
+                return false;
+            }
+            offset = i;
+        }
+    }
+
+    // Marks the comments in `pending` that form a doc comment: a group of comments on
+    // consecutive lines that ends on the line before a declaration keyword. A comment on
+    // the same line as the preceding token trails that token and is never part of one.
+    fn mark_doc_comments(&mut self) {
+        let (declaration, line) = match self.pending.back() {
+            Some(token) => (
+                matches!(
+                    token.token_type,
+                    TokenTypes::Package
+                        | TokenTypes::Import
+                        | TokenTypes::Const
+                        | TokenTypes::Type
+                        | TokenTypes::Var
+                        | TokenTypes::Func
+                ),
+                token.span.line,
+            ),
+            None => return,
+        };
+        if !declaration {
+            return;
+        }
+
+        let mut next_line = line;
+        for i in (0..self.pending.len() - 1).rev() {
+            let comment = &mut self.pending[i];
+            let end_line =
+                comment.span.line + comment.lexeme.iter().filter(|&&c| c == b'\n').count();
+            if end_line + 1 != next_line || (i == 0 && comment.span.line == self.last_line) {
+                break;
             }
+            comment.token_type = TokenTypes::DocComment;
+            next_line = comment.span.line;
         }
     }
 
@@ -170,6 +447,32 @@ impl Lexer<'_> {
         return c;
     }
 
+    // Completes a character whose first byte `c` was just consumed, decoding any further
+    // UTF-8 bytes. Invalid encodings and misplaced byte order marks are reported.
+    fn finish_char(&mut self, c: u8) -> Option<char> {
+        if c.is_ascii() {
+            return Some(c as char);
+        }
+        let offset = self.current - 1;
+        match decode_char(&self.src[offset..]) {
+            Some(ch) => {
+                self.current = offset + ch.len_utf8();
+                if ch == BOM {
+                    self.error_at(
+                        offset,
+                        codes::INVALID_BOM,
+                        "Invalid BOM in the middle of the file.",
+                    );
+                }
+                return Some(ch);
+            }
+            None => {
+                self.error_at(offset, codes::INVALID_UTF8, "Invalid UTF-8 encoding.");
+                return None;
+            }
+        }
+    }
+
     fn matches(&mut self, expected: u8) -> bool {
         if self.is_at_end() || self.src[self.current] != expected {
             return false;
@@ -178,7 +481,7 @@ impl Lexer<'_> {
         return true;
     }
 
-    fn eq_after(&mut self, t1: TokenTypes, t2: TokenTypes) -> Token {
+    fn eq_after(&mut self, t1: TokenTypes, t2: TokenTypes) -> Token<'src> {
         if self.matches(b'=') {
             return self.get_token(t2);
         }
@@ -192,106 +495,448 @@ impl Lexer<'_> {
         return self.src[self.current];
     }
 
-    fn string(&mut self) -> Token {
-        while self.peek() != b'"' && !self.is_at_end() {
-            if self.peek() == b'\n' {
-                self.line += 1;
+    fn newline(&mut self) {
+        self.line += 1;
+        self.line_start = self.current;
+    }
+
+    fn column(&self, offset: usize) -> usize {
+        return offset - self.line_start + 1;
+    }
+
+    fn span(&self) -> Span {
+        return Span::new(
+            self.file,
+            self.start,
+            self.current,
+            self.start_line,
+            self.start_column,
+        );
+    }
+
+    // The first `len` bytes of the current token.
+    fn start_span(&self, len: usize) -> Span {
+        let end = usize::min(self.start + len, self.src_len);
+        return Span::new(
+            self.file,
+            self.start,
+            end,
+            self.start_line,
+            self.start_column,
+        );
+    }
+
+    // A single byte on the current line.
+    fn span_at(&self, offset: usize) -> Span {
+        let end = usize::min(offset + 1, self.src_len);
+        return Span::new(self.file, offset, end, self.line, self.column(offset));
+    }
+
+    // The bytes from `offset` on the current line up to the current position.
+    fn span_from(&self, offset: usize) -> Span {
+        return Span::new(
+            self.file,
+            offset,
+            self.current,
+            self.line,
+            self.column(offset),
+        );
+    }
+
+    fn emit(&mut self, diagnostic: Diagnostic) {
+        self.error_handler.emit(diagnostic);
+    }
+
+    fn error_at(&mut self, offset: usize, code: &'static str, msg: &str) {
+        let span = self.span_at(offset);
+        self.error_handler.error(span, code, msg);
+    }
+
+    fn unterminated(&mut self, code: &'static str, msg: &str, quote: &str) {
+        let diagnostic = Diagnostic::error(msg)
+            .with_code(code)
+            .with_primary(self.start_span(1), "literal starts here")
+            .with_suggestion(self.span_from(self.current), quote, "close the literal");
+        self.emit(diagnostic);
+    }
+
+    fn string(&mut self) -> Token<'src> {
+        let mut value: Vec<u8> = Vec::new();
+        loop {
+            if self.is_at_end() || self.peek() == b'\n' {
+                self.unterminated(codes::UNTERMINATED_STRING, "Unterminated string.", "\"");
+                return self.unterminated_literal();
+            }
+            let c = self.advance();
+            if c == b'"' {
+                break;
             }
+            if c == b'\\' {
+                // Octal and \x escapes denote single bytes rather than code points.
+                let is_byte = matches!(self.peek(), b'0'..=b'7' | b'x');
+                match self.escape(b'"') {
+                    Some(v) if is_byte => value.push(v as u8),
+                    Some(v) => push_utf8(&mut value, v),
+                    None => {}
+                }
+            } else if let Some(ch) = self.finish_char(c) {
+                push_utf8(&mut value, ch as u32);
+            } else {
+                value.push(c);
+            }
+        }
+
+        let mut token = self.get_token(TokenTypes::String);
+        token.value = Some(value);
+        return token;
+    }
+
+    fn rune(&mut self) -> Token<'src> {
+        let mut value: Vec<u8> = Vec::new();
+        let mut count = 0;
+        loop {
+            if self.is_at_end() || self.peek() == b'\n' {
+                self.unterminated(codes::UNTERMINATED_RUNE, "Unterminated rune literal.", "'");
+                return self.unterminated_literal();
+            }
+            let c = self.advance();
+            if c == b'\'' {
+                break;
+            }
+            if c == b'\\' {
+                if let Some(v) = self.escape(b'\'') {
+                    push_utf8(&mut value, v);
+                }
+            } else if let Some(ch) = self.finish_char(c) {
+                push_utf8(&mut value, ch as u32);
+            }
+            count += 1;
+        }
+
+        if count == 0 {
+            let diagnostic = Diagnostic::error("Empty rune literal.")
+                .with_code(codes::INVALID_RUNE)
+                .with_primary(self.span(), "")
+                .with_note("a rune literal must contain exactly one character");
+            self.emit(diagnostic);
+        } else if count > 1 {
+            let contents = &self.src[self.start + 1..self.current - 1];
+            let mut diagnostic = Diagnostic::error("Rune literal has more than one character.")
+                .with_code(codes::INVALID_RUNE)
+                .with_primary(self.span(), "");
+            if !contents.contains(&b'"') {
+                let replacement = format!("\"{}\"", String::from_utf8_lossy(contents));
+                diagnostic =
+                    diagnostic.with_suggestion(self.span(), &replacement, "use a string literal");
+            }
+            self.emit(diagnostic);
+        }
+        let mut token = self.get_token(TokenTypes::Char);
+        token.value = Some(value);
+        return token;
+    }
+
+    // Scans the escape sequence following a backslash and returns its value.
+    fn escape(&mut self, quote: u8) -> Option<u32> {
+        let offset = self.current - 1;
+        let (len, base, max): (usize, u32, u32) = match self.peek() {
+            b'a' => return self.simple_escape(0x07),
+            b'b' => return self.simple_escape(0x08),
+            b'f' => return self.simple_escape(0x0C),
+            b'n' => return self.simple_escape(b'\n' as u32),
+            b'r' => return self.simple_escape(b'\r' as u32),
+            b't' => return self.simple_escape(b'\t' as u32),
+            b'v' => return self.simple_escape(0x0B),
+            b'\\' => return self.simple_escape(b'\\' as u32),
+            c if c == quote => return self.simple_escape(quote as u32),
+            b'0'..=b'7' => (3, 8, 255),
+            b'x' => {
+                self.advance();
+                (2, 16, 255)
+            }
+            b'u' => {
+                self.advance();
+                (4, 16, 0x10FFFF)
+            }
+            b'U' => {
+                self.advance();
+                (8, 16, 0x10FFFF)
+            }
+            _ => {
+                if self.is_at_end() || self.peek() == b'\n' {
+                    self.error_at(
+                        offset,
+                        codes::INVALID_ESCAPE,
+                        "Escape sequence not terminated.",
+                    );
+                } else {
+                    self.advance();
+                    let diagnostic = Diagnostic::error("Unknown escape sequence.")
+                        .with_code(codes::INVALID_ESCAPE)
+                        .with_primary(self.span_from(offset), "")
+                        .with_note(&format!(
+                            "valid escapes are \\a \\b \\f \\n \\r \\t \\v \\\\ \\{}, octal, \\x, \\u and \\U",
+                            quote as char
+                        ));
+                    self.emit(diagnostic);
+                }
+                return None;
+            }
+        };
+
+        let mut value: u32 = 0;
+        for _ in 0..len {
+            let digit = match (self.peek() as char).to_digit(base) {
+                Some(digit) if !self.is_at_end() => digit,
+                _ => {
+                    self.error_at(
+                        offset,
+                        codes::INVALID_ESCAPE,
+                        "Illegal character in escape sequence.",
+                    );
+                    return None;
+                }
+            };
+            value = value * base + digit;
             self.advance();
         }
 
-        if self.is_at_end() {
-            self.error_handler.error(self.line, "Unterminated string.");
-            return self.get_token(TokenTypes::Eof);
+        if value > max || (0xD800..0xE000).contains(&value) {
+            self.error_at(
+                offset,
+                codes::INVALID_ESCAPE,
+                "Escape sequence is invalid Unicode code point.",
+            );
+            return None;
         }
+        return Some(value);
+    }
 
+    fn simple_escape(&mut self, value: u32) -> Option<u32> {
         self.advance();
-        return self.get_token(TokenTypes::String);
+        return Some(value);
     }
 
-    fn raw_string(&mut self) -> Token {
+    fn raw_string(&mut self) -> Token<'src> {
+        let mut value: Vec<u8> = Vec::new();
         while self.peek() != b'`' && !self.is_at_end() {
-            if self.peek() == b'\n' {
-                self.line += 1;
+            let c = self.advance();
+            if c == b'\n' {
+                self.newline();
+            }
+            // Carriage returns are discarded from raw string values.
+            if c == b'\r' {
+                continue;
+            }
+            match self.finish_char(c) {
+                Some(ch) => push_utf8(&mut value, ch as u32),
+                None => value.push(c),
             }
-            self.advance();
         }
 
         if self.is_at_end() {
-            self.error_handler.error(self.line, "Unterminated string.");
-            return self.get_token(TokenTypes::Eof);
+            self.unterminated(codes::UNTERMINATED_STRING, "Unterminated string.", "`");
+            return self.unterminated_literal();
         }
 
         self.advance();
-        return self.get_token(TokenTypes::RawString);
+        let mut token = self.get_token(TokenTypes::RawString);
+        token.value = Some(value);
+        return token;
     }
 
-    fn get_token(&self, token_type: TokenTypes) -> Token {
-        return Token::new(
-            token_type,
-            self.src[self.start..self.current].to_vec(),
-            self.line,
+    // An Illegal token for the current lexeme, carrying the diagnostic that was just
+    // reported. Like gc, it leaves semicolon insertion as it was.
+    fn illegal(&mut self) -> Token<'src> {
+        let mut token = Token::new(
+            TokenTypes::Illegal,
+            &self.src[self.start..self.current],
+            self.span(),
         );
+        token.error = self
+            .error_handler
+            .diagnostics()
+            .last()
+            .cloned()
+            .map(Box::new);
+        return token;
     }
 
-    fn number(&mut self, c: u8) -> Token {
-        if c == b'0' && (self.peek() == b'x' || self.peek() == b'X') {
-            self.advance();
+    // An Illegal token for a literal that runs to the end of the line or file. Lexing
+    // resumes after it, and a newline ends the statement as it would after a literal.
+    fn unterminated_literal(&mut self) -> Token<'src> {
+        let token = self.illegal();
+        self.insert_semi = true;
+        return token;
+    }
+
+    fn get_token(&mut self, token_type: TokenTypes) -> Token<'src> {
+        self.insert_semi = ends_statement(token_type);
+        return Token::new(token_type, &self.src[self.start..self.current], self.span());
+    }
 
-            while self.peek().is_ascii_hexdigit() {
+    // Semicolons inserted at a newline or EOF use "\n" as their lexeme, as go/scanner does.
+    fn auto_semicolon(&mut self) -> Token<'src> {
+        self.insert_semi = false;
+        return Token::new(TokenTypes::Semicolon, b"\n", self.span());
+    }
+
+    fn number(&mut self, c: u8) -> Token<'src> {
+        let mut base: u32 = 10;
+        let mut prefix: u8 = 0;
+        // Bit 0 is set once a digit has been seen, bit 1 once a '_' separator has been seen.
+        let mut seen: u8 = 0;
+        let mut invalid: Option<usize> = None;
+        let mut token_type = TokenTypes::Int;
+
+        if c != b'.' {
+            if c == b'0' {
+                match self.peek().to_ascii_lowercase() {
+                    b'x' => {
+                        self.advance();
+                        base = 16;
+                        prefix = b'x';
+                    }
+                    b'o' => {
+                        self.advance();
+                        base = 8;
+                        prefix = b'o';
+                    }
+                    b'b' => {
+                        self.advance();
+                        base = 2;
+                        prefix = b'b';
+                    }
+                    _ => {
+                        base = 8;
+                        prefix = b'0';
+                        seen = 1;
+                    }
+                }
+            } else {
+                seen = 1;
+            }
+            seen |= self.digits(base, &mut invalid);
+        }
+
+        if c == b'.' || self.peek() == b'.' {
+            token_type = TokenTypes::Float;
+            if prefix == b'o' || prefix == b'b' {
+                let offset = if c == b'.' { self.start } else { self.current };
+                self.error_at(
+                    offset,
+                    codes::INVALID_NUMBER,
+                    &format!("Invalid radix point in {}.", literal_name(prefix)),
+                );
+            }
+            if c != b'.' {
                 self.advance();
             }
+            seen |= self.digits(base, &mut invalid);
+        }
 
-            return self.get_token(TokenTypes::Hex);
+        if seen & 1 == 0 {
+            self.error_at(
+                self.current,
+                codes::INVALID_NUMBER,
+                &format!("Missing digits in {}.", literal_name(prefix)),
+            );
         }
 
-        if c == b'0' && (self.peek() == b'b' || self.peek() == b'B') {
+        let exponent = self.peek().to_ascii_lowercase();
+        if exponent == b'e' || exponent == b'p' {
+            if exponent == b'e' && prefix != 0 && prefix != b'0' {
+                let diagnostic = Diagnostic::error("'e' exponent requires decimal mantissa.")
+                    .with_code(codes::INVALID_NUMBER)
+                    .with_primary(self.span_at(self.current), "")
+                    .with_secondary(self.start_span(2), "base prefix here");
+                self.emit(diagnostic);
+            } else if exponent == b'p' && prefix != b'x' {
+                self.error_at(
+                    self.current,
+                    codes::INVALID_NUMBER,
+                    "'p' exponent requires hexadecimal mantissa.",
+                );
+            }
             self.advance();
-
-            while self.peek() == b'0' || self.peek() == b'1' {
+            token_type = TokenTypes::Float;
+            if self.peek() == b'+' || self.peek() == b'-' {
                 self.advance();
             }
+            let exponent_seen = self.digits(10, &mut None);
+            seen |= exponent_seen;
+            if exponent_seen & 1 == 0 {
+                self.error_at(
+                    self.current,
+                    codes::INVALID_NUMBER,
+                    "Exponent has no digits.",
+                );
+            }
+        } else if prefix == b'x' && token_type == TokenTypes::Float {
+            self.error_at(
+                self.current,
+                codes::INVALID_NUMBER,
+                "Hexadecimal mantissa requires a 'p' exponent.",
+            );
+        }
+
+        if self.peek() == b'i' {
+            self.advance();
+            token_type = TokenTypes::Imag;
+        } else if token_type == TokenTypes::Int {
+            if let Some(offset) = invalid {
+                self.error_at(
+                    offset,
+                    codes::INVALID_NUMBER,
+                    &format!(
+                        "Invalid digit '{}' in {}.",
+                        self.src[offset] as char,
+                        literal_name(prefix)
+                    ),
+                );
+            }
+            token_type = match prefix {
+                b'x' => TokenTypes::Hex,
+                b'o' => TokenTypes::Octal,
+                b'0' if self.current - self.start > 1 => TokenTypes::Octal,
+                b'b' => TokenTypes::Binary,
+                _ => TokenTypes::Int,
+            };
+        }
 
-            return self.get_token(TokenTypes::Binary);
+        if seen & 2 != 0 {
+            if let Some(i) = invalid_separator(&self.src[self.start..self.current]) {
+                let span = self.span_at(self.start + i);
+                let diagnostic = Diagnostic::error("'_' must separate successive digits.")
+                    .with_code(codes::INVALID_NUMBER)
+                    .with_primary(span, "")
+                    .with_suggestion(span, "", "remove the separator");
+                self.emit(diagnostic);
+            }
         }
 
-        let is_lead_zero = c == b'0';
-        let mut has_e = false;
-        let mut has_dot = false;
-        let mut is_octal = true;
+        return self.get_token(token_type);
+    }
 
+    // Consumes digits of the given base along with '_' separators. Digits that are too large
+    // for a base below 10 are accepted here and the first one is recorded in `invalid`.
+    fn digits(&mut self, base: u32, invalid: &mut Option<usize>) -> u8 {
+        let mut seen: u8 = 0;
         loop {
-            if self.peek() >= b'0' && self.peek() <= b'7' {
-                self.advance();
-            } else if self.peek().is_ascii_digit() {
-                self.advance();
-                is_octal = false;
-            } else if !has_e && (self.peek() == b'e' || self.peek() == b'E') {
-                self.advance();
-                has_e = true;
-                if self.peek() == b'+' || self.peek() == b'-' {
-                    self.advance();
+            let c = self.peek();
+            if c == b'_' {
+                seen |= 2;
+            } else if (base <= 10 && c.is_ascii_digit()) || (base == 16 && c.is_ascii_hexdigit()) {
+                seen |= 1;
+                if base < 10 && ((c - b'0') as u32) >= base && invalid.is_none() {
+                    *invalid = Some(self.current);
                 }
-            } else if !has_e && !has_dot && self.peek() == b'.' {
-                self.advance();
-                has_dot = true;
-            } else if self.peek() == b'i' {
-                self.advance();
-                return self.get_token(TokenTypes::Imag);
             } else {
                 break;
             }
+            self.advance();
         }
-
-        let token_type = if has_e || has_dot {
-            TokenTypes::Float
-        } else if is_lead_zero && is_octal {
-            TokenTypes::Octal
-        } else {
-            TokenTypes::Int
-        };
-
-        return self.get_token(token_type);
+        return seen;
     }
 
     fn peek_next(&self) -> u8 {
@@ -301,61 +946,142 @@ impl Lexer<'_> {
         return self.src[self.current + 1];
     }
 
-    fn identifier(&mut self) -> Token {
-        while (self.peek() >= b'a' && self.peek() <= b'z')
-            || (self.peek() >= b'A' && self.peek() <= b'Z')
-            || (self.peek() >= b'0' && self.peek() <= b'9')
-            || self.peek() == b'_'
-        {
-            self.advance();
+    fn identifier(&mut self) -> Token<'src> {
+        loop {
+            let c = self.peek();
+            if c.is_ascii_alphanumeric() || c == b'_' {
+                self.advance();
+                continue;
+            }
+            if c.is_ascii() {
+                break;
+            }
+            match decode_char(&self.src[self.current..]) {
+                Some(ch) if unicode::is_letter(ch) || unicode::is_digit(ch) => {
+                    self.current += ch.len_utf8();
+                }
+                _ => break,
+            }
         }
 
-        let text: Vec<u8> = self.src[self.start..self.current].to_vec();
-        let token_type: TokenTypes = match text.as_slice() {
-            b"break" => TokenTypes::Break,
-            b"case" => TokenTypes::Case,
-            b"chan" => TokenTypes::Chan,
-            b"const" => TokenTypes::Const,
-            b"continue" => TokenTypes::Continue,
-            b"default" => TokenTypes::Default,
-            b"defer" => TokenTypes::Defer,
-            b"else" => TokenTypes::Else,
-            b"fallthrough" => TokenTypes::Fallthrough,
-            b"for" => TokenTypes::For,
-            b"func" => TokenTypes::Func,
-            b"go" => TokenTypes::Go,
-            b"goto" => TokenTypes::Goto,
-            b"if" => TokenTypes::If,
-            b"import" => TokenTypes::Import,
-            b"interface" => TokenTypes::Interface,
-            b"map" => TokenTypes::Map,
-            b"package" => TokenTypes::Package,
-            b"range" => TokenTypes::Range,
-            b"return" => TokenTypes::Return,
-            b"select" => TokenTypes::Select,
-            b"struct" => TokenTypes::Struct,
-            b"switch" => TokenTypes::Switch,
-            b"type" => TokenTypes::Type,
-            b"var" => TokenTypes::Var,
-            b"true" => TokenTypes::True,
-            b"false" => TokenTypes::False,
-            b"nil" => TokenTypes::Nil,
-            _ => TokenTypes::Identifier,
-        };
+        // Identifiers only contain characters that were decoded above.
+        let text = str::from_utf8(&self.src[self.start..self.current]).unwrap();
+        let symbol = self.interner.intern(text);
+        let token_type = self
+            .interner
+            .keyword(symbol)
+            .unwrap_or(TokenTypes::Identifier);
 
-        return self.get_token(token_type);
+        let mut token = self.get_token(token_type);
+        token.symbol = Some(symbol);
+        return token;
+    }
+}
+
+fn literal_name(prefix: u8) -> &'static str {
+    return match prefix {
+        b'x' => "hexadecimal literal",
+        b'o' | b'0' => "octal literal",
+        b'b' => "binary literal",
+        _ => "decimal literal",
+    };
+}
+
+// Returns the index of the first '_' in a number literal that does not sit between two
+// digits, treating a base prefix as a digit.
+fn invalid_separator(lit: &[u8]) -> Option<usize> {
+    let mut is_hex = false;
+    // The previous character, normalised to '0' for a digit, '_' or '.' for anything else.
+    let mut d = b'.';
+    let mut i = 0;
+
+    if lit.len() >= 2 && lit[0] == b'0' {
+        let x = lit[1].to_ascii_lowercase();
+        if x == b'x' || x == b'o' || x == b'b' {
+            is_hex = x == b'x';
+            d = b'0';
+            i = 2;
+        }
+    }
+
+    while i < lit.len() {
+        let p = d;
+        d = lit[i];
+        if d == b'_' {
+            if p != b'0' {
+                return Some(i);
+            }
+        } else if d.is_ascii_digit() || (is_hex && d.is_ascii_hexdigit()) {
+            d = b'0';
+        } else {
+            if p == b'_' {
+                return Some(i - 1);
+            }
+            d = b'.';
+        }
+        i += 1;
+    }
+
+    if d == b'_' {
+        return Some(lit.len() - 1);
     }
+    return None;
+}
+
+fn decode_char(bytes: &[u8]) -> Option<char> {
+    let bytes = &bytes[..usize::min(bytes.len(), 4)];
+    let valid = match str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(err) => str::from_utf8(&bytes[..err.valid_up_to()]).unwrap(),
+    };
+    return valid.chars().next();
+}
+
+fn push_utf8(value: &mut Vec<u8>, code_point: u32) {
+    let c = char::from_u32(code_point).unwrap_or(char::REPLACEMENT_CHARACTER);
+    let mut buf = [0; 4];
+    value.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
 }
 
-impl Iterator for Lexer<'_> {
-    type Item = Token;
+impl<'src> Iterator for Lexer<'src> {
+    type Item = Token<'src>;
 
+    // Yields every token of the source followed by exactly one Eof token, whose span is
+    // the empty span at the end of the source, and then only None.
     fn next(&mut self) -> Option<Self::Item> {
-        if self.is_at_end() {
+        if let Some(token) = self.pending.pop_front() {
+            return Some(token);
+        }
+        if self.done {
             return None;
         }
 
-        self.start = self.current;
-        return Some(self.scan_token());
+        let token = self.scan_token();
+        self.done = token.token_type == TokenTypes::Eof;
+        if token.token_type != TokenTypes::Comment {
+            self.last_line = token.span.line;
+            return Some(token);
+        }
+
+        // Read ahead to the next token to find out whether the comments are a doc comment.
+        self.pending.push_back(token);
+        loop {
+            let token = self.scan_token();
+            self.done = token.token_type == TokenTypes::Eof;
+            let is_comment = token.token_type == TokenTypes::Comment;
+            self.pending.push_back(token);
+            if !is_comment {
+                break;
+            }
+        }
+        self.mark_doc_comments();
+        if let Some(token) = self.pending.back() {
+            if token.token_type != TokenTypes::Comment {
+                self.last_line = token.span.line;
+            }
+        }
+        return self.pending.pop_front();
     }
 }
+
+impl FusedIterator for Lexer<'_> {}
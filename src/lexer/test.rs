@@ -1,16 +1,53 @@
+use crate::diagnostic::{codes, Diagnostic};
+use crate::intern::{Interner, Symbol};
 use crate::lexer;
-use crate::token::TokenTypes;
+use crate::lexer::directive::DirectiveKind;
+use crate::lexer::incremental::{relex, Edit};
+use crate::source::{FileId, LineDirective, LineIndex, Span};
+use crate::token::{Token, TokenTypes};
+use std::ops::Range;
 
-fn assert_tokens(src: &str, expected: Vec<TokenTypes>) {
+// Checks the token types of `src`, which must end with the Eof token whether or not
+// `expected` lists it.
+fn assert_tokens(src: &str, mut expected: Vec<TokenTypes>) {
     let data: Vec<u8> = src.as_bytes().to_vec();
-    let lexer = lexer::Lexer::new(&data);
-    for (pos, token) in lexer.enumerate() {
-        assert_eq!(token.token_type, expected[pos]);
+    let mut tokens: Vec<TokenTypes> = lexer::Lexer::new(&data).map(|t| t.token_type).collect();
+    assert_eq!(tokens.pop(), Some(TokenTypes::Eof), "{:?}", src);
+    if expected.last() == Some(&TokenTypes::Eof) {
+        expected.pop();
     }
+    assert_eq!(tokens, expected, "{:?}", src);
+}
+
+fn lex_diagnostics(src: &[u8]) -> Vec<Diagnostic> {
+    let data: Vec<u8> = src.to_vec();
+    let mut lexer = lexer::Lexer::new(&data);
+    for _ in lexer.by_ref() {}
+    return lexer.take_diagnostics();
+}
+
+fn assert_error(src: &str, code: &str) {
+    let diagnostics = lex_diagnostics(src.as_bytes());
+    assert!(
+        diagnostics.iter().any(|d| d.code == Some(code)),
+        "expected {} for {:?}, got {:?}",
+        code,
+        src,
+        diagnostics
+    );
 }
 
 fn assert_token(src: &str, expected: TokenTypes) {
-    assert_tokens(src, vec![expected]);
+    let data: Vec<u8> = src.as_bytes().to_vec();
+    let mut lexer = lexer::Lexer::new(&data);
+    assert_eq!(lexer.next().unwrap().token_type, expected);
+    for token in lexer.by_ref() {
+        if token.token_type != TokenTypes::Eof {
+            assert_eq!(token.token_type, TokenTypes::Semicolon);
+            assert_eq!(token.lexeme, b"\n");
+        }
+    }
+    assert!(!lexer.has_errors(), "{:?}", lexer.diagnostics());
 }
 
 #[test]
@@ -22,6 +59,24 @@ fn test_numerical_tokems() {
     assert_token("0b101", TokenTypes::Binary);
     assert_token("0B101", TokenTypes::Binary);
 
+    let int_tests: Vec<(&str, TokenTypes)> = vec![
+        ("0", TokenTypes::Int),
+        ("42", TokenTypes::Int),
+        ("1_000_000", TokenTypes::Int),
+        ("0600", TokenTypes::Octal),
+        ("0_600", TokenTypes::Octal),
+        ("0o600", TokenTypes::Octal),
+        ("0O600", TokenTypes::Octal),
+        ("0o_600", TokenTypes::Octal),
+        ("0xBadFace", TokenTypes::Hex),
+        ("0x_67_7a_2f_cc_40_c6", TokenTypes::Hex),
+        ("0b_1010_1010", TokenTypes::Binary),
+    ];
+
+    for (test, kind) in int_tests {
+        assert_token(test, kind);
+    }
+
     let float_tests: Vec<&str> = vec![
         "1.0", "1.0e10", "1.0e+10", "1.0e-10", "1.0E10", "1.0E+10", "1.0E-10",
     ];
@@ -30,6 +85,32 @@ fn test_numerical_tokems() {
         assert_token(test, TokenTypes::Float);
     }
 
+    let more_float_tests: Vec<&str> = vec![
+        "0.",
+        "72.40",
+        "072.40",
+        "2.71828",
+        "1.e+0",
+        "6.67428e-11",
+        "1E6",
+        ".25",
+        ".12345E+5",
+        "1_5.",
+        "0.15e+0_2",
+        "0x1p-2",
+        "0x2.p10",
+        "0x1.Fp+0",
+        "0X.8p-0",
+        "0X_1FFFP-16",
+        "0x1.8p-3",
+        "09.5",
+        "09e1",
+    ];
+
+    for test in more_float_tests {
+        assert_token(test, TokenTypes::Float);
+    }
+
     let complex_tests: Vec<&str> = vec![
         "1.0i", "1.0e10i", "1.0e+10i", "1.0e-10i", "1.0E10i", "1.0E+10i", "1.0E-10i",
     ];
@@ -37,6 +118,60 @@ fn test_numerical_tokems() {
     for test in complex_tests {
         assert_token(test, TokenTypes::Imag);
     }
+
+    let more_complex_tests: Vec<&str> = vec![
+        "0i", "0123i", "0o123i", "0xabci", "0b101i", "1_000i", "0x1p-2i", ".25i", "1e3i",
+    ];
+
+    for test in more_complex_tests {
+        assert_token(test, TokenTypes::Imag);
+    }
+}
+
+#[test]
+fn test_malformed_numbers() {
+    let malformed_tests: Vec<(&str, TokenTypes)> = vec![
+        ("0x", TokenTypes::Hex),
+        ("0b", TokenTypes::Binary),
+        ("0o", TokenTypes::Octal),
+        ("09", TokenTypes::Octal),
+        ("0o8", TokenTypes::Octal),
+        ("0b102", TokenTypes::Binary),
+        ("0x1.8", TokenTypes::Float),
+        ("0b1.0", TokenTypes::Float),
+        ("0x1e3p", TokenTypes::Float),
+        ("0o7e3", TokenTypes::Float),
+        ("1p3", TokenTypes::Float),
+        ("1e", TokenTypes::Float),
+        ("1__0", TokenTypes::Int),
+        ("1_", TokenTypes::Int),
+        ("0_x1", TokenTypes::Octal),
+        ("0x_", TokenTypes::Hex),
+        ("1._5", TokenTypes::Float),
+    ];
+
+    for (test, kind) in malformed_tests {
+        let data: Vec<u8> = test.as_bytes().to_vec();
+        let token = lexer::Lexer::new(&data).next().unwrap();
+        assert_eq!(token.token_type, kind, "{}", test);
+        assert_error(test, codes::INVALID_NUMBER);
+    }
+}
+
+#[test]
+fn test_number_error_positions() {
+    let diagnostics = lex_diagnostics(b"x := 0b102");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "Invalid digit '2' in binary literal."
+    );
+    let span = diagnostics[0].primary_span().unwrap();
+    assert_eq!((span.start, span.end, span.column), (9, 10, 10));
+
+    let diagnostics = lex_diagnostics(b"1__0");
+    assert_eq!(diagnostics[0].suggestions[0].replacement, "");
+    assert_eq!(diagnostics[0].suggestions[0].span.start, 2);
 }
 
 #[test]
@@ -44,10 +179,174 @@ fn test_text_literals() {
     assert_token("\"\"", TokenTypes::String);
     assert_token("\"hello\"", TokenTypes::String);
     assert_token("\"hello world\"", TokenTypes::String);
-    // assert_token("\"hello \\\"world\\\"\"", TokenTypes::String);
+    assert_token("\"hello \\\"world\\\"\"", TokenTypes::String);
     assert_token("`hello \\\\world`", TokenTypes::RawString);
 }
 
+fn assert_value(src: &str, expected: &[u8]) {
+    let data: Vec<u8> = src.as_bytes().to_vec();
+    let token = lexer::Lexer::new(&data).next().unwrap();
+    assert_eq!(token.lexeme, data);
+    assert_eq!(token.value.as_deref(), Some(expected));
+}
+
+#[test]
+fn test_string_values() {
+    let pairs: Vec<(&str, &[u8])> = vec![
+        ("\"\"", b""),
+        ("\"hello\"", b"hello"),
+        ("\"hello \\\"world\\\"\"", b"hello \"world\""),
+        ("\"a\\\\b\"", b"a\\b"),
+        ("\"\\a\\b\\f\\n\\r\\t\\v\"", b"\x07\x08\x0c\n\r\t\x0b"),
+        ("\"\\377\\xFF\"", b"\xff\xff"),
+        ("\"\\u00e9\\U0001F600\"", "\u{e9}\u{1F600}".as_bytes()),
+        ("\"日本\"", "日本".as_bytes()),
+        ("`a\\n\r\nb`", b"a\\n\nb"),
+        ("'\\x41'", b"A"),
+        ("'\\u00e9'", "\u{e9}".as_bytes()),
+    ];
+
+    for (src, value) in pairs {
+        assert_value(src, value);
+    }
+}
+
+#[test]
+fn test_invalid_string_escapes() {
+    let invalid_tests: Vec<&str> = vec![
+        "\"\\q\"",
+        "\"\\'\"",
+        "\"\\400\"",
+        "\"\\xZ0\"",
+        "\"\\uDFFF\"",
+    ];
+
+    for test in invalid_tests {
+        assert_error(test, codes::INVALID_ESCAPE);
+    }
+
+    let diagnostics = lex_diagnostics(b"s := \"ab\\qc\"");
+    let span = diagnostics[0].primary_span().unwrap();
+    assert_eq!(
+        (span.start, span.end, span.line, span.column),
+        (8, 10, 1, 9)
+    );
+}
+
+#[test]
+fn test_string_with_newline_is_unterminated() {
+    let data: Vec<u8> = b"\"abc\ndef\"".to_vec();
+    let tokens: Vec<(TokenTypes, &[u8], usize)> = lexer::Lexer::new(&data)
+        .map(|t| (t.token_type, t.lexeme, t.span.line))
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            (TokenTypes::Illegal, &b"\"abc"[..], 1),
+            (TokenTypes::Semicolon, &b"\n"[..], 1),
+            (TokenTypes::Identifier, &b"def"[..], 2),
+            (TokenTypes::Illegal, &b"\""[..], 2),
+            (TokenTypes::Semicolon, &b"\n"[..], 2),
+            (TokenTypes::Eof, &b""[..], 2),
+        ]
+    );
+}
+
+#[test]
+fn illegal_tokens_carry_their_diagnostic() {
+    let data: Vec<u8> = b"x := \"abc\ny := `def".to_vec();
+    let mut lexer = lexer::Lexer::new(&data);
+    let illegal: Vec<_> = lexer
+        .by_ref()
+        .filter(|t| t.token_type == TokenTypes::Illegal)
+        .map(|t| t.error.unwrap())
+        .collect();
+
+    assert_eq!(illegal.len(), 2);
+    assert_eq!(illegal[0].code, Some(codes::UNTERMINATED_STRING));
+    assert_eq!(illegal[0].primary_span().unwrap().line, 1);
+    assert_eq!(illegal[1].primary_span().unwrap().line, 2);
+    assert_eq!(
+        lexer.diagnostics(),
+        illegal.iter().map(|d| (**d).clone()).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_rune_literals() {
+    let rune_tests: Vec<&str> = vec![
+        "'a'",
+        "'ä'",
+        "'本'",
+        "'\\t'",
+        "'\\000'",
+        "'\\007'",
+        "'\\377'",
+        "'\\x07'",
+        "'\\xff'",
+        "'\\u12e4'",
+        "'\\U00101234'",
+        "'\\''",
+        "'\\\\'",
+    ];
+
+    for test in rune_tests {
+        assert_token(test, TokenTypes::Char);
+    }
+
+    assert_tokens(
+        "x := 'a'\nreturn '\\n'",
+        vec![
+            TokenTypes::Identifier,
+            TokenTypes::Define,
+            TokenTypes::Char,
+            TokenTypes::Semicolon,
+            TokenTypes::Return,
+            TokenTypes::Char,
+            TokenTypes::Semicolon,
+        ],
+    );
+}
+
+#[test]
+fn test_invalid_rune_literals() {
+    let invalid_tests: Vec<&str> = vec![
+        "''",
+        "'aa'",
+        "'\\k'",
+        "'\\xa'",
+        "'\\400'",
+        "'\\uD800'",
+        "'\\U00110000'",
+        "'\\\"'",
+    ];
+
+    for test in invalid_tests {
+        let data: Vec<u8> = test.as_bytes().to_vec();
+        let token = lexer::Lexer::new(&data).next().unwrap();
+        assert_eq!(token.token_type, TokenTypes::Char);
+    }
+
+    assert_error("''", codes::INVALID_RUNE);
+    assert_error("'aa'", codes::INVALID_RUNE);
+    assert_error("'\\k'", codes::INVALID_ESCAPE);
+    assert_error("'\\uD800'", codes::INVALID_ESCAPE);
+    assert_error("'a\nb", codes::UNTERMINATED_RUNE);
+
+    let diagnostics = lex_diagnostics(b"'ab'");
+    assert_eq!(diagnostics[0].suggestions[0].replacement, "\"ab\"");
+
+    assert_tokens(
+        "'a\nb",
+        vec![
+            TokenTypes::Illegal,
+            TokenTypes::Semicolon,
+            TokenTypes::Identifier,
+            TokenTypes::Semicolon,
+        ],
+    );
+}
+
 #[test]
 fn token_simple() {
     let pairs = vec![
@@ -98,6 +397,7 @@ fn token_simple() {
         ("==", TokenTypes::Eql),
         ("/", TokenTypes::Quo),
         ("/=", TokenTypes::QuoAssign),
+        ("~", TokenTypes::Tilde),
     ];
 
     for (src, kind) in pairs {
@@ -147,16 +447,23 @@ fn tokenize_keywords() {
 fn tokenize_package_declaration() {
     assert_tokens(
         "package main",
-        vec![TokenTypes::Package, TokenTypes::Identifier],
+        vec![
+            TokenTypes::Package,
+            TokenTypes::Identifier,
+            TokenTypes::Semicolon,
+        ],
     );
 }
 
-
 #[test]
 fn tokenize_simple_import() {
     assert_tokens(
         "import \"fmt\"",
-        vec![TokenTypes::Import, TokenTypes::String],
+        vec![
+            TokenTypes::Import,
+            TokenTypes::String,
+            TokenTypes::Semicolon,
+        ],
     );
 }
 
@@ -170,6 +477,7 @@ fn tokenize_simple_assignment() {
             TokenTypes::Int,
             TokenTypes::Add,
             TokenTypes::Int,
+            TokenTypes::Semicolon,
         ],
     );
 }
@@ -186,10 +494,10 @@ func main() {
     let expected = vec![
         TokenTypes::Package,
         TokenTypes::Identifier,
-        //TokenTypes::Semicolon,
+        TokenTypes::Semicolon,
         TokenTypes::Import,
         TokenTypes::String,
-        //TokenTypes::Semicolon,
+        TokenTypes::Semicolon,
         TokenTypes::Func,
         TokenTypes::Identifier,
         TokenTypes::LParen,
@@ -201,10 +509,10 @@ func main() {
         TokenTypes::LParen,
         TokenTypes::String,
         TokenTypes::RParen,
-        //TokenTypes::Semicolon,
+        TokenTypes::Semicolon,
         TokenTypes::RBrace,
-        //TokenTypes::Semicolon,
-        TokenTypes::Eof
+        TokenTypes::Semicolon,
+        TokenTypes::Eof,
     ];
 
     assert_tokens(src, expected);
@@ -224,6 +532,7 @@ fn tokenize_simple_assignment_with_inline_comment() {
             TokenTypes::Int,
             TokenTypes::Add,
             TokenTypes::Int,
+            TokenTypes::Semicolon,
         ],
     );
 }
@@ -244,10 +553,10 @@ func main() {
     let expected = vec![
         TokenTypes::Package,
         TokenTypes::Identifier,
-        // TokenTypes::Semicolon,
+        TokenTypes::Semicolon,
         TokenTypes::Import,
         TokenTypes::String,
-        // TokenTypes::Semicolon,
+        TokenTypes::Semicolon,
         TokenTypes::Func,
         TokenTypes::Identifier,
         TokenTypes::LParen,
@@ -259,11 +568,706 @@ func main() {
         TokenTypes::LParen,
         TokenTypes::String,
         TokenTypes::RParen,
-        // TokenTypes::Semicolon,
+        TokenTypes::Semicolon,
+        TokenTypes::RBrace,
+        TokenTypes::Semicolon,
+        TokenTypes::Eof,
+    ];
+
+    assert_tokens(src, expected);
+}
+
+// =====
+// Semicolon insertion
+// =====
+
+#[test]
+fn semicolon_after_line_ending_tokens() {
+    let src = r#"x
+42
+"s"
+return
+break
+continue
+fallthrough
+i++
+j--
+f()
+a[i]
+}
+"#;
+
+    let expected = vec![
+        TokenTypes::Identifier,
+        TokenTypes::Semicolon,
+        TokenTypes::Int,
+        TokenTypes::Semicolon,
+        TokenTypes::String,
+        TokenTypes::Semicolon,
+        TokenTypes::Return,
+        TokenTypes::Semicolon,
+        TokenTypes::Break,
+        TokenTypes::Semicolon,
+        TokenTypes::Continue,
+        TokenTypes::Semicolon,
+        TokenTypes::Fallthrough,
+        TokenTypes::Semicolon,
+        TokenTypes::Identifier,
+        TokenTypes::Increment,
+        TokenTypes::Semicolon,
+        TokenTypes::Identifier,
+        TokenTypes::Decrement,
+        TokenTypes::Semicolon,
+        TokenTypes::Identifier,
+        TokenTypes::LParen,
+        TokenTypes::RParen,
+        TokenTypes::Semicolon,
+        TokenTypes::Identifier,
+        TokenTypes::LBrack,
+        TokenTypes::Identifier,
+        TokenTypes::RBrack,
+        TokenTypes::Semicolon,
         TokenTypes::RBrace,
-        // TokenTypes::Semicolon,
-        TokenTypes::Eof
+        TokenTypes::Semicolon,
     ];
 
     assert_tokens(src, expected);
 }
+
+#[test]
+fn no_semicolon_after_operators_and_openers() {
+    assert_tokens(
+        "a +\nb {\n\n(\n",
+        vec![
+            TokenTypes::Identifier,
+            TokenTypes::Add,
+            TokenTypes::Identifier,
+            TokenTypes::LBrace,
+            TokenTypes::LParen,
+            TokenTypes::Eof,
+        ],
+    );
+}
+
+#[test]
+fn semicolon_at_eof() {
+    assert_tokens("return", vec![TokenTypes::Return, TokenTypes::Semicolon]);
+}
+
+#[test]
+fn semicolon_before_line_comment() {
+    assert_tokens(
+        "x // trailing comment\ny",
+        vec![
+            TokenTypes::Identifier,
+            TokenTypes::Semicolon,
+            TokenTypes::Identifier,
+            TokenTypes::Semicolon,
+        ],
+    );
+}
+
+#[test]
+fn semicolon_for_multiline_block_comment() {
+    assert_tokens(
+        "x /* spans\nlines */ y /* same line */ z",
+        vec![
+            TokenTypes::Identifier,
+            TokenTypes::Semicolon,
+            TokenTypes::Identifier,
+            TokenTypes::Identifier,
+            TokenTypes::Semicolon,
+        ],
+    );
+}
+
+// =====
+// Unicode
+// =====
+
+#[test]
+fn tokenize_unicode_identifiers() {
+    assert_tokens(
+        "π := 3.14\nnaïve, 日本語, x٣ = 1, 2, 3",
+        vec![
+            TokenTypes::Identifier,
+            TokenTypes::Define,
+            TokenTypes::Float,
+            TokenTypes::Semicolon,
+            TokenTypes::Identifier,
+            TokenTypes::Comma,
+            TokenTypes::Identifier,
+            TokenTypes::Comma,
+            TokenTypes::Identifier,
+            TokenTypes::Assign,
+            TokenTypes::Int,
+            TokenTypes::Comma,
+            TokenTypes::Int,
+            TokenTypes::Comma,
+            TokenTypes::Int,
+            TokenTypes::Semicolon,
+        ],
+    );
+
+    let data: Vec<u8> = "über".as_bytes().to_vec();
+    let token = lexer::Lexer::new(&data).next().unwrap();
+    assert_eq!(token.lexeme, "über".as_bytes());
+}
+
+#[test]
+fn unicode_digit_cannot_start_identifier() {
+    assert_tokens(
+        "٣x",
+        vec![
+            TokenTypes::Illegal,
+            TokenTypes::Identifier,
+            TokenTypes::Semicolon,
+        ],
+    );
+}
+
+#[test]
+fn illegal_non_letter_characters() {
+    assert_error("a € b", codes::UNEXPECTED_CHARACTER);
+    assert_tokens(
+        "a € b",
+        vec![
+            TokenTypes::Identifier,
+            TokenTypes::Illegal,
+            TokenTypes::Identifier,
+            TokenTypes::Semicolon,
+        ],
+    );
+}
+
+#[test]
+fn illegal_invalid_utf8() {
+    let data: Vec<u8> = b"a \xff\xfe b \"\xc3\"".to_vec();
+    let diagnostics = lex_diagnostics(&data);
+    assert_eq!(diagnostics.len(), 3);
+    assert!(diagnostics
+        .iter()
+        .all(|d| d.code == Some(codes::INVALID_UTF8)));
+
+    let tokens: Vec<TokenTypes> = lexer::Lexer::new(&data).map(|t| t.token_type).collect();
+    assert_eq!(
+        tokens,
+        vec![
+            TokenTypes::Identifier,
+            TokenTypes::Illegal,
+            TokenTypes::Illegal,
+            TokenTypes::Identifier,
+            TokenTypes::String,
+            TokenTypes::Semicolon,
+            TokenTypes::Eof,
+        ]
+    );
+}
+
+#[test]
+fn byte_order_mark() {
+    assert_tokens(
+        "\u{FEFF}package main",
+        vec![
+            TokenTypes::Package,
+            TokenTypes::Identifier,
+            TokenTypes::Semicolon,
+        ],
+    );
+    assert!(lex_diagnostics("\u{FEFF}package main".as_bytes()).is_empty());
+    assert_error("package \u{FEFF}main", codes::INVALID_BOM);
+    assert_tokens(
+        "package \u{FEFF}main",
+        vec![
+            TokenTypes::Package,
+            TokenTypes::Illegal,
+            TokenTypes::Identifier,
+            TokenTypes::Semicolon,
+        ],
+    );
+}
+
+// =====
+// Spans
+// =====
+
+#[test]
+fn token_spans() {
+    let src = "package main\n\nfunc main() {\n\tx := `a\nb`\n}";
+    let data: Vec<u8> = src.as_bytes().to_vec();
+    let spans: Vec<(TokenTypes, Span)> = lexer::Lexer::new(&data)
+        .with_file(FileId(7))
+        .map(|t| (t.token_type, t.span))
+        .collect();
+
+    let expected = vec![
+        (TokenTypes::Package, Span::new(FileId(7), 0, 7, 1, 1)),
+        (TokenTypes::Identifier, Span::new(FileId(7), 8, 12, 1, 9)),
+        (TokenTypes::Semicolon, Span::new(FileId(7), 12, 13, 1, 13)),
+        (TokenTypes::Func, Span::new(FileId(7), 14, 18, 3, 1)),
+        (TokenTypes::Identifier, Span::new(FileId(7), 19, 23, 3, 6)),
+        (TokenTypes::LParen, Span::new(FileId(7), 23, 24, 3, 10)),
+        (TokenTypes::RParen, Span::new(FileId(7), 24, 25, 3, 11)),
+        (TokenTypes::LBrace, Span::new(FileId(7), 26, 27, 3, 13)),
+        (TokenTypes::Identifier, Span::new(FileId(7), 29, 30, 4, 2)),
+        (TokenTypes::Define, Span::new(FileId(7), 31, 33, 4, 4)),
+        (TokenTypes::RawString, Span::new(FileId(7), 34, 39, 4, 7)),
+        (TokenTypes::Semicolon, Span::new(FileId(7), 39, 40, 5, 3)),
+        (TokenTypes::RBrace, Span::new(FileId(7), 40, 41, 6, 1)),
+        (TokenTypes::Semicolon, Span::new(FileId(7), 41, 41, 6, 2)),
+        (TokenTypes::Eof, Span::new(FileId(7), 41, 41, 6, 2)),
+    ];
+
+    assert_eq!(spans, expected);
+}
+
+#[test]
+fn token_spans_agree_with_line_index() {
+    let data: Vec<u8> = std::fs::read("src/test_files/hugeparams.go").unwrap();
+    let index = LineIndex::new(&data);
+    for token in lexer::Lexer::new(&data) {
+        assert_eq!(
+            (token.span.line, token.span.column),
+            index.line_col(token.span.start)
+        );
+        // Automatic semicolons have the lexeme "\n" wherever they are inserted.
+        if token.lexeme != b"\n" {
+            assert_eq!(&data[token.span.start..token.span.end], token.lexeme);
+        }
+    }
+}
+
+// =====
+// Comment tokens
+// =====
+
+fn comment_tokens(src: &str) -> Vec<(TokenTypes, String)> {
+    let data: Vec<u8> = src.as_bytes().to_vec();
+    return lexer::Lexer::new(&data)
+        .with_comments(true)
+        .map(|t| (t.token_type, String::from_utf8(t.lexeme.to_vec()).unwrap()))
+        .collect();
+}
+
+#[test]
+fn comments_are_skipped_by_default() {
+    assert_tokens(
+        "x // a\n/* b */ y",
+        vec![
+            TokenTypes::Identifier,
+            TokenTypes::Semicolon,
+            TokenTypes::Identifier,
+            TokenTypes::Semicolon,
+        ],
+    );
+}
+
+#[test]
+fn emit_comment_tokens() {
+    let tokens = comment_tokens("x /* a */ + y // b\n/* c\nd */");
+    let expected = vec![
+        (TokenTypes::Identifier, "x"),
+        (TokenTypes::Comment, "/* a */"),
+        (TokenTypes::Add, "+"),
+        (TokenTypes::Identifier, "y"),
+        (TokenTypes::Semicolon, "\n"),
+        (TokenTypes::Comment, "// b"),
+        (TokenTypes::Comment, "/* c\nd */"),
+        (TokenTypes::Eof, ""),
+    ];
+    let expected: Vec<(TokenTypes, String)> = expected
+        .into_iter()
+        .map(|(t, l)| (t, l.to_string()))
+        .collect();
+    assert_eq!(tokens, expected);
+}
+
+#[test]
+fn semicolon_precedes_comment_at_line_end() {
+    let data: Vec<u8> = b"x /* a */ // b\ny".to_vec();
+    let tokens: Vec<(TokenTypes, usize, usize)> = lexer::Lexer::new(&data)
+        .with_comments(true)
+        .map(|t| (t.token_type, t.span.start, t.span.end))
+        .collect();
+    assert_eq!(
+        tokens,
+        vec![
+            (TokenTypes::Identifier, 0, 1),
+            (TokenTypes::Semicolon, 2, 2),
+            (TokenTypes::Comment, 2, 9),
+            (TokenTypes::Comment, 10, 14),
+            (TokenTypes::Identifier, 15, 16),
+            (TokenTypes::Semicolon, 16, 16),
+            (TokenTypes::Eof, 16, 16),
+        ]
+    );
+}
+
+#[test]
+fn mark_doc_comments() {
+    let src = r#"// Package doc.
+package main
+
+var x int // trailing
+
+// Not attached.
+
+// F does things.
+/* More about F. */
+func F() {
+	// Inside a body.
+	x++
+}
+
+// Before a non-declaration.
+x = 1
+"#;
+    let comments: Vec<(TokenTypes, String)> = comment_tokens(src)
+        .into_iter()
+        .filter(|(t, _)| matches!(t, TokenTypes::Comment | TokenTypes::DocComment))
+        .collect();
+    let expected = vec![
+        (TokenTypes::DocComment, "// Package doc."),
+        (TokenTypes::Comment, "// trailing"),
+        (TokenTypes::Comment, "// Not attached."),
+        (TokenTypes::DocComment, "// F does things."),
+        (TokenTypes::DocComment, "/* More about F. */"),
+        (TokenTypes::Comment, "// Inside a body."),
+        (TokenTypes::Comment, "// Before a non-declaration."),
+    ];
+    let expected: Vec<(TokenTypes, String)> = expected
+        .into_iter()
+        .map(|(t, l)| (t, l.to_string()))
+        .collect();
+    assert_eq!(comments, expected);
+}
+
+#[test]
+fn trailing_comment_is_not_doc_comment() {
+    let tokens = comment_tokens("var x int // x\nfunc f()");
+    assert_eq!(tokens[4], (TokenTypes::Comment, "// x".to_string()));
+}
+
+// =====
+// Directives
+// =====
+
+fn directives(src: &str) -> Vec<DirectiveKind> {
+    let data: Vec<u8> = src.as_bytes().to_vec();
+    let mut lexer = lexer::Lexer::new(&data);
+    for _ in lexer.by_ref() {}
+    assert!(!lexer.has_errors(), "{:?}", lexer.diagnostics());
+    return lexer
+        .take_directives()
+        .into_iter()
+        .map(|d| d.kind)
+        .collect();
+}
+
+#[test]
+fn recognize_go_directives() {
+    let src = r#"//go:build linux && (amd64 || arm64)
+
+package p
+
+import _ "embed"
+
+//go:embed "hello world.txt" static/*.html `a b`
+var content string
+
+	//go:noinline
+//go:nosplit
+//go:linkname now runtime.now
+func f() {}
+
+// go:noinline is not a directive with the space.
+x := 1 //go:noinline is not one either after code.
+"#;
+    assert_eq!(
+        directives(src),
+        vec![
+            DirectiveKind::Build("linux && (amd64 || arm64)".to_string()),
+            DirectiveKind::Embed(vec![
+                "hello world.txt".to_string(),
+                "static/*.html".to_string(),
+                "a b".to_string(),
+            ]),
+            DirectiveKind::NoInline,
+            DirectiveKind::NoSplit,
+            DirectiveKind::Other {
+                name: "linkname".to_string(),
+                args: "now runtime.now".to_string(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn recognize_line_directives() {
+    let src = "//line gen.y:10\nx /*line :20:5*/ y\n //line ignored.go:1\n//line c:\\dir\\f.go:3:4\n//line no colon\n";
+    assert_eq!(
+        directives(src),
+        vec![
+            DirectiveKind::Line(LineDirective {
+                offset: 16,
+                file: Some("gen.y".to_string()),
+                line: 10,
+                column: None,
+            }),
+            DirectiveKind::Line(LineDirective {
+                offset: 32,
+                file: None,
+                line: 20,
+                column: Some(5),
+            }),
+            DirectiveKind::Line(LineDirective {
+                offset: 79,
+                file: Some("c:\\dir\\f.go".to_string()),
+                line: 3,
+                column: Some(4),
+            }),
+        ]
+    );
+}
+
+#[test]
+fn report_malformed_directives() {
+    assert_error("//go:build\n", codes::INVALID_DIRECTIVE);
+    assert_error("//go:embed\n", codes::INVALID_DIRECTIVE);
+    assert_error("//go:embed \"unterminated\n", codes::INVALID_DIRECTIVE);
+    assert_error("//line x.go:0\n", codes::INVALID_DIRECTIVE);
+    assert_error("//line x.go:ten\n", codes::INVALID_DIRECTIVE);
+}
+
+// =====
+// Stress
+// =====
+
+// Generates several megabytes of source in the style of hugeparams.go, with long runs of
+// whitespace, blank lines and comments between the tokens.
+fn generated_source() -> Vec<u8> {
+    let mut src = String::from("package main\n\n");
+    src.push_str(&"// A generated comment line.\n".repeat(100_000));
+    src.push_str(&"\n".repeat(200_000));
+    src.push_str("func PrintHugeParams(");
+    for i in 0..20_000 {
+        src.push_str(&format!("p{} [{}]int64, ", i, i));
+    }
+    src.push(')');
+    src.push_str(&" ".repeat(1_000_000));
+    src.push_str(&"\t/* a block comment */\r".repeat(50_000));
+    src.push_str("{\n}\n");
+    return src.into_bytes();
+}
+
+#[test]
+fn lex_multi_megabyte_input_on_a_small_stack() {
+    let data = generated_source();
+    assert!(data.len() > 3_000_000);
+
+    // A 64 KiB stack is far too small for one frame per skipped byte.
+    let counts = std::thread::Builder::new()
+        .stack_size(64 * 1024)
+        .spawn(move || {
+            let count = lexer::Lexer::new(&data).count();
+            let with_comments = lexer::Lexer::new(&data).with_comments(true).count();
+            return (count, with_comments);
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+
+    // package main ; func PrintHugeParams ( then six tokens per parameter, then ) { } ; Eof
+    let tokens = 3 + 3 + 20_000 * 6 + 1 + 3 + 1;
+    assert_eq!(counts, (tokens, tokens + 150_000));
+}
+
+// =====
+// Symbols
+// =====
+
+#[test]
+fn identifiers_are_interned() {
+    let data: Vec<u8> = b"x := y + x\nfunc".to_vec();
+    let mut lexer = lexer::Lexer::new(&data);
+    let symbols: Vec<Option<Symbol>> = lexer.by_ref().map(|t| t.symbol).collect();
+    let interner = lexer.take_interner();
+
+    assert_eq!(symbols[0], symbols[4]);
+    assert_ne!(symbols[0], symbols[2]);
+    assert_eq!(symbols[1], None);
+    assert_eq!(interner.resolve(symbols[2].unwrap()), "y");
+    assert_eq!(symbols[6], interner.lookup("func"));
+}
+
+#[test]
+fn share_an_interner_between_files() {
+    let a: Vec<u8> = b"fmt.Println".to_vec();
+    let b: Vec<u8> = b"Println".to_vec();
+    let mut lexer = lexer::Lexer::new(&a);
+    let first = lexer.by_ref().nth(2).unwrap().symbol;
+
+    let mut lexer = lexer::Lexer::new(&b).with_interner(lexer.take_interner());
+    let second = lexer.next().unwrap().symbol;
+    assert_eq!(first, second);
+}
+
+// =====
+// End of input
+// =====
+
+#[test]
+fn single_eof_token_at_end() {
+    for src in ["", "x", "x\n", "x // c", "/* c */", "\"open", "x\n\n\t"] {
+        let data: Vec<u8> = src.as_bytes().to_vec();
+        let mut lexer = lexer::Lexer::new(&data).with_comments(true);
+        let tokens: Vec<_> = lexer.by_ref().collect();
+        let eof: Vec<_> = tokens
+            .iter()
+            .filter(|t| t.token_type == TokenTypes::Eof)
+            .collect();
+
+        assert_eq!(eof.len(), 1, "{:?}", src);
+        let last = tokens.last().unwrap();
+        assert_eq!(last.token_type, TokenTypes::Eof, "{:?}", src);
+        assert_eq!((last.span.start, last.span.end), (src.len(), src.len()));
+        assert!(last.lexeme.is_empty());
+        assert!(lexer.next().is_none());
+        assert!(lexer.next().is_none());
+    }
+}
+
+#[test]
+fn eof_position_is_end_of_source() {
+    let data: Vec<u8> = b"package p\n\nvar x".to_vec();
+    let eof = lexer::Lexer::new(&data).last().unwrap();
+    assert_eq!(eof.token_type, TokenTypes::Eof);
+    assert_eq!(eof.span, Span::new(FileId(0), 16, 16, 3, 6));
+}
+
+// =====
+// Incremental re-lexing
+// =====
+
+// Applies `edit` to `src`, re-lexes incrementally and checks the result against lexing the
+// new source from scratch. Returns the changed old and new token ranges.
+fn assert_relex(src: &[u8], edit: &Edit) -> (Range<usize>, Range<usize>) {
+    let mut interner = Interner::new();
+    let mut lexer = lexer::Lexer::new(src).with_interner(interner);
+    let old: Vec<Token> = lexer.by_ref().collect();
+    interner = lexer.take_interner();
+
+    let new_src = edit.apply(src);
+    let relexed = relex(&old, edit, &new_src, &mut interner);
+    let mut lexer = lexer::Lexer::new(&new_src).with_interner(interner);
+    let expected: Vec<Token> = lexer.by_ref().collect();
+
+    let describe = |tokens: &[Token]| -> Vec<String> {
+        return tokens
+            .iter()
+            .map(|t| format!("{} {:?} {:?} {:?}", t, t.span, t.symbol, t.value))
+            .collect();
+    };
+    assert_eq!(describe(&relexed.tokens), describe(&expected), "{:?}", edit);
+    assert_eq!(
+        old.len() - relexed.old.len(),
+        expected.len() - relexed.new.len()
+    );
+    return (relexed.old, relexed.new);
+}
+
+#[test]
+fn relex_within_a_line() {
+    let src = b"x := foo + 1\ny := 2\nz := 3\n";
+    // Tokens: x := foo + 1 ; y := 2 ; z := 3 ; Eof
+    let (old, new) = assert_relex(src, &Edit::new(5..8, b"foobar"));
+    assert_eq!((old, new), (2..3, 2..3));
+    let (old, new) = assert_relex(src, &Edit::new(9..10, b"-"));
+    assert_eq!((old, new), (3..4, 3..4));
+    let (old, new) = assert_relex(src, &Edit::new(8..8, b"(a, b)"));
+    assert_eq!((old, new), (3..3, 3..8));
+}
+
+#[test]
+fn relex_changes_semicolons() {
+    // Removing the `+` ends the first statement at the newline.
+    let (old, new) = assert_relex(b"x := a +\nb\ny := 1\n", &Edit::new(7..8, b""));
+    assert_eq!((old, new), (3..4, 3..4));
+    assert_relex(b"x := a\ny := 1\n", &Edit::new(6..6, b" +"));
+    assert_relex(b"x := a /* c */ b\n", &Edit::new(14..16, b""));
+    // The semicolon before a comment depends on what follows the comment.
+    assert_relex(b"a = x /* c */\nb = y\n", &Edit::new(13..13, b" z"));
+    assert_relex(b"a = x /* c\n d */ b = y\n", &Edit::new(10..11, b""));
+    assert_relex(b"return\n", &Edit::new(0..6, b"x"));
+}
+
+#[test]
+fn relex_tokens_that_merge_or_split() {
+    assert_relex(b"a . . b\n", &Edit::new(3..4, b""));
+    assert_relex(b"a ... b\n", &Edit::new(3..3, b" "));
+    assert_relex(b"x := 1\n", &Edit::new(6..6, b".5e3"));
+    assert_relex(b"x := ab\n", &Edit::new(6..6, b"\n"));
+}
+
+#[test]
+fn relex_comments_and_raw_strings_spanning_the_rest() {
+    let src = b"x := 1\ny := `a\nb`\nz := 2 /* c\nd */ + 3\n";
+    assert_relex(src, &Edit::new(7..7, b"/*"));
+    assert_relex(src, &Edit::new(7..7, b"`"));
+    assert_relex(src, &Edit::new(14..16, b""));
+    assert_relex(src, &Edit::new(30..31, b""));
+    assert_relex(src, &Edit::new(38..38, b"4"));
+}
+
+#[test]
+fn relex_at_the_ends() {
+    let src = b"\xEF\xBB\xBFpackage p\n";
+    assert_relex(src, &Edit::new(3..3, b"// c\n"));
+    assert_relex(src, &Edit::new(src.len()..src.len(), b"var x int"));
+    assert_relex(b"", &Edit::new(0..0, b"x"));
+    assert_relex(b"x", &Edit::new(0..1, b""));
+}
+
+#[test]
+fn relex_around_illegal_tokens() {
+    let src = b"x := $ + 1\ny := \"abc\nz := 'q\n";
+    assert_relex(src, &Edit::new(5..6, b"2"));
+    assert_relex(src, &Edit::new(11..11, b"w := 0\n"));
+    assert_relex(src, &Edit::new(20..20, b"\""));
+    assert_relex(src, &Edit::new(26..26, b"'"));
+}
+
+#[test]
+fn relex_random_edits_to_test_files() {
+    let snippets: [&[u8]; 10] = [
+        b"", b"\n", b" ", b"x", b"+", b".", b"/*", b"*/", b"\"", b"`",
+    ];
+    for path in [
+        "src/test_files/implements.go",
+        "src/test_files/hugeparams.go",
+        "src/test_files/generics.go",
+    ] {
+        let src = std::fs::read(path).unwrap();
+        let mut seed: u64 = 1;
+        for _ in 0..200 {
+            seed = seed
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            let start = (seed >> 33) as usize % src.len();
+            let len = (seed >> 20) as usize % 4;
+            let end = usize::min(start + len, src.len());
+            let text = snippets[(seed >> 8) as usize % snippets.len()];
+            assert_relex(&src, &Edit::new(start..end, text));
+        }
+    }
+}
+
+#[test]
+fn relex_only_scans_near_the_edit() {
+    let src = std::fs::read("src/test_files/hugeparams.go").unwrap();
+    let at = src.len() / 2;
+    let at = at + src[at..].iter().position(|&b| b == b'\n').unwrap();
+    let (old, new) = assert_relex(&src, &Edit::new(at..at, b"\nvar x = 1"));
+    assert!(old.len() <= 1, "{:?}", old);
+    assert!(new.len() <= 6, "{:?}", new);
+}
@@ -0,0 +1,13 @@
+#![allow(clippy::needless_return)]
+
+pub mod ast;
+pub mod build;
+pub mod diagnostic;
+pub mod driver;
+pub mod error;
+pub mod intern;
+pub mod lexer;
+pub mod parser;
+pub mod printer;
+pub mod source;
+pub mod token;
@@ -1,23 +1,18 @@
-use std::fs;
-
-mod error;
-mod lexer;
-mod token;
+use go_compiler::driver;
+use go_compiler::parser;
+use std::env;
+use std::io;
+use std::process;
+use std::thread;
 
 fn main() {
-    // let src_file: String = env::args().nth(1).unwrap();
-    // println!("File: {}", src_file);
-
-    // let data: Vec<u8> = fs::read(src_file).expect("Unable to read file");
-    // println!("{}", data.len());
-    let data: Vec<u8> = fs::read("C:\\code\\Personal Github\\go-compiler\\src\\test_files\\implements.go").expect("Unable to read file");
-    for token in lexer::Lexer::new(&data) {
-        println!("{}", token);
-    };
-
-    let data: Vec<u8> = fs::read("C:\\code\\Personal Github\\go-compiler\\src\\test_files\\hugeparams.go").expect("Unable to read file");
-    for token in lexer::Lexer::new(&data) {
-        println!("{}", token);
-    };
-    
+    let args: Vec<String> = env::args().skip(1).collect();
+    // Deeply nested source needs more stack than the main thread has.
+    let compiler = thread::Builder::new()
+        .stack_size(parser::STACK_SIZE)
+        .spawn(move || driver::run(&args, &mut io::stdout().lock(), &mut io::stderr().lock()))
+        .expect("Unable to start the compiler thread");
+    // A panic has already been reported by the thread; exit as Rust does after one.
+    let status = compiler.join().unwrap_or(101);
+    process::exit(status);
 }
@@ -0,0 +1,2295 @@
+// A recursive-descent parser for Go source files. It follows the grammar of the Go
+// specification and builds the syntax tree defined in `ast`, taking the same decisions
+// as go/parser wherever the grammar is ambiguous.
+
+use crate::ast::*;
+use crate::diagnostic::{codes, Diagnostic};
+use crate::error::ErrorHandler;
+use crate::intern::Interner;
+use crate::lexer::Lexer;
+use crate::source::{FileId, Span};
+use crate::token::{Token, TokenTypes};
+
+#[cfg(test)]
+mod test;
+
+type PResult<T> = Result<T, Diagnostic>;
+
+// The deepest nesting of expressions, types and statements that is parsed, as go/parser's
+// maxNestLev. The parser recurses on every level and Rust's stack does not grow like Go's,
+// so the limit is much lower.
+pub const MAX_NEST_LEV: u32 = 1000;
+
+// The stack a thread needs to parse input nested up to MAX_NEST_LEV levels, even in a
+// debug build.
+pub const STACK_SIZE: usize = 256 << 20;
+
+pub struct Parser<'src> {
+    tokens: Vec<Token<'src>>,
+    comments: Vec<Comment>,
+    pos: usize,
+    // The end of the last token consumed, where the node being parsed ends.
+    prev_end: usize,
+    // The nesting depth of parentheses and brackets, or -1 in the header of an if, for or
+    // switch statement, where a `{` after a type name opens the body and not a composite
+    // literal.
+    expr_lev: i32,
+    // The nesting depth of expressions, types and statements, limited to MAX_NEST_LEV.
+    nest_lev: u32,
+    // Set once the nesting limit is exceeded; the rest of the file is skipped and no more
+    // errors are reported.
+    bailed_out: bool,
+    file: FileId,
+    interner: Interner,
+    error_handler: ErrorHandler,
+}
+
+impl<'src> Parser<'src> {
+    // Scans all tokens of `lexer`, taking over its diagnostics and interner. Comments are
+    // set aside for the file being parsed. A lexer that has already been drained gives an
+    // empty file.
+    pub fn new(lexer: &mut Lexer<'src>) -> Parser<'src> {
+        let (comments, mut tokens): (Vec<Token<'src>>, Vec<Token<'src>>) = lexer
+            .by_ref()
+            .partition(|t| matches!(t.token_type, TokenTypes::Comment | TokenTypes::DocComment));
+        let comments = comments
+            .into_iter()
+            .map(|t| Comment {
+                text: strip_cr(t.lexeme, t.lexeme.starts_with(b"/*")),
+                doc: t.token_type == TokenTypes::DocComment,
+                span: t.span,
+            })
+            .collect();
+        if tokens.is_empty() {
+            let span = Span::new(lexer.file(), 0, 0, 1, 1);
+            tokens.push(Token::new(TokenTypes::Eof, b"", span));
+        }
+        let mut error_handler = ErrorHandler::new();
+        error_handler.extend(lexer.take_diagnostics());
+        Parser {
+            file: tokens[0].span.file,
+            tokens,
+            comments,
+            pos: 0,
+            prev_end: 0,
+            expr_lev: 0,
+            nest_lev: 0,
+            bailed_out: false,
+            interner: lexer.take_interner(),
+            error_handler,
+        }
+    }
+
+    pub fn interner(&self) -> &Interner {
+        return &self.interner;
+    }
+
+    pub fn take_interner(&mut self) -> Interner {
+        return std::mem::take(&mut self.interner);
+    }
+
+    // The diagnostics of the lexer followed by those of the parser.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        return self.error_handler.diagnostics();
+    }
+
+    pub fn has_errors(&self) -> bool {
+        return self.error_handler.has_errors();
+    }
+
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        return self.error_handler.take_diagnostics();
+    }
+
+    // Parses a complete source file. A declaration or statement with a syntax error is
+    // reported and replaced by a bad node, and parsing goes on after it.
+    pub fn parse_file(&mut self) -> File {
+        return self.source_file();
+    }
+
+    // Parses a single expression that makes up the whole input.
+    pub fn parse_expr(&mut self) -> Option<Expr> {
+        let result = self.expr().and_then(|x| {
+            self.got(TokenTypes::Semicolon);
+            if self.tok() != TokenTypes::Eof {
+                return Err(self.unexpected("end of expression"));
+            }
+            return Ok(x);
+        });
+        match result {
+            Ok(x) => return Some(x),
+            Err(diagnostic) => {
+                self.report(diagnostic);
+                return None;
+            }
+        }
+    }
+
+    // ========
+    // Tokens
+    // ========
+
+    fn token(&self) -> &Token<'src> {
+        return &self.tokens[self.pos];
+    }
+
+    fn tok(&self) -> TokenTypes {
+        return self.tokens[self.pos].token_type;
+    }
+
+    // The type of the token `n` tokens ahead, or Eof past the end.
+    fn peek(&self, n: usize) -> TokenTypes {
+        let i = usize::min(self.pos + n, self.tokens.len() - 1);
+        return self.tokens[i].token_type;
+    }
+
+    // Consumes the current token and returns its span. The Eof token is never consumed.
+    fn next(&mut self) -> Span {
+        let span = self.token().span;
+        self.prev_end = span.end;
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        return span;
+    }
+
+    fn got(&mut self, token_type: TokenTypes) -> bool {
+        if self.tok() == token_type {
+            self.next();
+            return true;
+        }
+        return false;
+    }
+
+    fn expect(&mut self, token_type: TokenTypes) -> PResult<Span> {
+        if self.tok() != token_type {
+            return Err(self.unexpected(&format!("'{}'", token_type.go_name())));
+        }
+        return Ok(self.next());
+    }
+
+    // Expects the semicolon that ends a statement or declaration, which may be left out
+    // before a closing `)` or `}`. A missing semicolon before a top-level declaration is
+    // only reported, so that the node before it is kept.
+    fn expect_semi(&mut self) -> PResult<()> {
+        match self.tok() {
+            TokenTypes::RParen | TokenTypes::RBrace => return Ok(()),
+            TokenTypes::Semicolon => {
+                self.next();
+                return Ok(());
+            }
+            _ if self.at_decl() => {
+                self.report(self.unexpected("';'"));
+                return Ok(());
+            }
+            _ => return Err(self.unexpected("';'")),
+        }
+    }
+
+    // Consumes the comma after a list element and returns true, or returns false at the
+    // token that closes the list.
+    fn at_comma(&mut self, context: &str, closing: TokenTypes) -> PResult<bool> {
+        if self.got(TokenTypes::Comma) {
+            return Ok(true);
+        }
+        if self.tok() == closing {
+            return Ok(false);
+        }
+        if self.tok() == TokenTypes::Semicolon && self.token().lexeme == b"\n" {
+            return Err(
+                Diagnostic::error(&format!("Missing ',' before newline in {}.", context))
+                    .with_code(codes::UNEXPECTED_TOKEN)
+                    .with_primary(self.token().span, ""),
+            );
+        }
+        return Err(self.unexpected(&format!("',' or '{}'", closing.go_name())));
+    }
+
+    // The span from `start` to the end of the last token consumed.
+    fn span_from(&self, start: Span) -> Span {
+        return Span::new(
+            self.file,
+            start.start,
+            usize::max(start.end, self.prev_end),
+            start.line,
+            start.column,
+        );
+    }
+
+    fn unexpected(&self, expected: &str) -> Diagnostic {
+        let token = self.token();
+        // The lexer has already reported what is wrong with an illegal token.
+        if let Some(error) = &token.error {
+            return (**error).clone();
+        }
+        let found = match token.token_type {
+            TokenTypes::Semicolon if token.lexeme == b"\n" => "newline".to_string(),
+            TokenTypes::Eof => "end of file".to_string(),
+            tok if is_ident(tok) => {
+                format!("identifier {}", String::from_utf8_lossy(token.lexeme))
+            }
+            TokenTypes::Int
+            | TokenTypes::Octal
+            | TokenTypes::Hex
+            | TokenTypes::Binary
+            | TokenTypes::Float
+            | TokenTypes::Imag
+            | TokenTypes::Char
+            | TokenTypes::String
+            | TokenTypes::RawString => {
+                format!("literal {}", String::from_utf8_lossy(token.lexeme))
+            }
+            other => format!("'{}'", other.go_name()),
+        };
+        return Diagnostic::error(&format!("Expected {}, found {}.", expected, found))
+            .with_code(codes::UNEXPECTED_TOKEN)
+            .with_primary(token.span, "");
+    }
+
+    // Reports a syntax error unless there is already an error on its line, which is
+    // usually its cause.
+    fn report(&mut self, diagnostic: Diagnostic) {
+        if self.bailed_out {
+            return;
+        }
+        let line = diagnostic.primary_span().map(|span| span.line);
+        let reported = self
+            .error_handler
+            .diagnostics()
+            .iter()
+            .any(|d| d.is_error() && d.primary_span().map(|span| span.line) == line);
+        if !reported {
+            self.error_handler.emit(diagnostic);
+        }
+    }
+
+    // ========
+    // Recovery
+    // ========
+
+    // Whether the current token starts a top-level declaration, which ends a function
+    // body that is missing its closing brace. `func` followed by a name cannot start a
+    // function literal.
+    fn at_decl(&self) -> bool {
+        match self.tok() {
+            TokenTypes::Import => return true,
+            TokenTypes::Func => return is_ident(self.peek(1)),
+            _ => return false,
+        }
+    }
+
+    // The number of braces opened and not closed since the token at `from`.
+    fn open_braces(&self, from: usize) -> usize {
+        let mut depth = 0usize;
+        for token in &self.tokens[from..self.pos] {
+            match token.token_type {
+                TokenTypes::LBrace => depth += 1,
+                TokenTypes::RBrace => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+        return depth;
+    }
+
+    // Skips the rest of a statement that started at `from` and could not be parsed: up to
+    // and including the next semicolon, or up to the `}` of the enclosing block, the next
+    // case clause or a top-level declaration. Blocks inside the statement are skipped as
+    // a whole.
+    fn sync_stmt(&mut self, from: usize) {
+        let mut depth = self.open_braces(from);
+        loop {
+            match self.tok() {
+                TokenTypes::Eof => return,
+                TokenTypes::Semicolon if depth == 0 => {
+                    self.next();
+                    return;
+                }
+                TokenTypes::RBrace | TokenTypes::Case | TokenTypes::Default if depth == 0 => return,
+                _ if depth == 0 && self.at_decl() => return,
+                TokenTypes::LBrace => depth += 1,
+                TokenTypes::RBrace => depth -= 1,
+                _ => {}
+            }
+            self.next();
+        }
+    }
+
+    // Skips the rest of a declaration that started at `from` and could not be parsed, up
+    // to the keyword of the next top-level declaration.
+    fn sync_decl(&mut self, from: usize) {
+        let mut depth = self.open_braces(from);
+        loop {
+            match self.tok() {
+                TokenTypes::Eof => return,
+                TokenTypes::Const
+                | TokenTypes::Type
+                | TokenTypes::Var
+                | TokenTypes::Func
+                | TokenTypes::Import
+                    if depth == 0 =>
+                {
+                    return
+                }
+                TokenTypes::LBrace => depth += 1,
+                TokenTypes::RBrace => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            self.next();
+        }
+    }
+
+    // Parses a declaration with `parse`. If that fails, the error is reported and the
+    // declaration is skipped and replaced by a BadDecl.
+    fn recover_decl(&mut self, parse: fn(&mut Self) -> PResult<Decl>) -> Decl {
+        let (from, start) = (self.pos, self.token().span);
+        match parse(self) {
+            Ok(decl) => return decl,
+            Err(diagnostic) => {
+                self.report(diagnostic);
+                self.expr_lev = 0;
+                if self.pos == from {
+                    self.next();
+                }
+                self.sync_decl(from);
+                return Decl::Bad(BadDecl {
+                    span: self.span_from(start),
+                });
+            }
+        }
+    }
+
+    // The expression `x` was found where a different kind of expression is required;
+    // reports `diagnostic` and replaces it by a BadExpr.
+    fn bad_expr(&mut self, x: &Expr, diagnostic: Diagnostic) -> Expr {
+        self.report(diagnostic);
+        return Expr::Bad(BadExpr { span: x.span() });
+    }
+
+    // ========
+    // Nesting
+    // ========
+
+    // Enters one more level of nesting. Past MAX_NEST_LEV the error is reported and, as
+    // go/parser bails out, the rest of the file is skipped; false is returned and the
+    // caller stands in a bad node for the nested one.
+    fn enter(&mut self) -> bool {
+        if self.nest_lev < MAX_NEST_LEV {
+            self.nest_lev += 1;
+            return true;
+        }
+        let diagnostic = Diagnostic::error(&format!(
+            "Expression or statement nested more than {} levels deep.",
+            MAX_NEST_LEV
+        ))
+        .with_code(codes::UNEXPECTED_TOKEN)
+        .with_primary(self.token().span, "");
+        self.report(diagnostic);
+        self.bailed_out = true;
+        self.pos = self.tokens.len() - 1;
+        return false;
+    }
+
+    // Parses a nested node with `parse`, or makes a bad node with `bad` past the nesting
+    // limit.
+    fn nested<T>(
+        &mut self,
+        bad: impl FnOnce(Span) -> T,
+        parse: impl FnOnce(&mut Self) -> PResult<T>,
+    ) -> PResult<T> {
+        let span = self.token().span;
+        if !self.enter() {
+            return Ok(bad(span));
+        }
+        let result = parse(self);
+        self.nest_lev -= 1;
+        return result;
+    }
+
+    // ========
+    // Declarations
+    // ========
+
+    fn source_file(&mut self) -> File {
+        let (from, start) = (self.pos, self.token().span);
+        let clause = self.package_clause();
+        let (package, name) = clause.unwrap_or_else(|diagnostic| {
+            // Without a package clause the file is parsed as if it were in package `_`.
+            self.report(diagnostic);
+            self.sync_decl(from);
+            let name = self.interner.intern("_");
+            return (start, Ident { name, span: start });
+        });
+
+        let mut decls = Vec::new();
+        while self.tok() == TokenTypes::Import {
+            decls.push(self.recover_decl(|p| Ok(Decl::Gen(p.gen_decl(TokenTypes::Import)?))));
+        }
+        while self.tok() != TokenTypes::Eof {
+            decls.push(self.recover_decl(Self::decl));
+        }
+        return File {
+            package,
+            name,
+            decls,
+            comments: std::mem::take(&mut self.comments),
+            span: Span::new(self.file, 0, self.token().span.end, 1, 1),
+        };
+    }
+
+    fn package_clause(&mut self) -> PResult<(Span, Ident)> {
+        let package = self.expect(TokenTypes::Package)?;
+        let name = self.ident()?;
+        self.expect_semi()?;
+        return Ok((package, name));
+    }
+
+    fn decl(&mut self) -> PResult<Decl> {
+        match self.tok() {
+            TokenTypes::Const | TokenTypes::Type | TokenTypes::Var => {
+                return Ok(Decl::Gen(self.gen_decl(self.tok())?));
+            }
+            TokenTypes::Func => return Ok(Decl::Func(Box::new(self.func_decl()?))),
+            TokenTypes::Import => {
+                // The import is still parsed, so that it is not reported again as a bad
+                // declaration.
+                self.report(
+                    Diagnostic::error("Imports must appear before other declarations.")
+                        .with_code(codes::UNEXPECTED_TOKEN)
+                        .with_primary(self.token().span, ""),
+                );
+                return Ok(Decl::Gen(self.gen_decl(TokenTypes::Import)?));
+            }
+            _ => return Err(self.unexpected("declaration")),
+        }
+    }
+
+    // An import, const, type or var declaration, including the semicolon after it.
+    fn gen_decl(&mut self, keyword: TokenTypes) -> PResult<GenDecl> {
+        let start = self.expect(keyword)?;
+        let mut specs = Vec::new();
+        let (mut lparen, mut rparen) = (None, None);
+        if self.tok() == TokenTypes::LParen {
+            lparen = Some(self.next());
+            while self.tok() != TokenTypes::RParen && self.tok() != TokenTypes::Eof {
+                specs.push(self.spec(keyword)?);
+                self.expect_semi()?;
+            }
+            rparen = Some(self.expect(TokenTypes::RParen)?);
+        } else {
+            specs.push(self.spec(keyword)?);
+        }
+        let span = self.span_from(start);
+        self.expect_semi()?;
+        return Ok(GenDecl {
+            tok: keyword,
+            lparen,
+            specs,
+            rparen,
+            span,
+        });
+    }
+
+    fn spec(&mut self, keyword: TokenTypes) -> PResult<Spec> {
+        match keyword {
+            TokenTypes::Import => return Ok(Spec::Import(self.import_spec()?)),
+            TokenTypes::Type => return Ok(Spec::Type(self.type_spec()?)),
+            _ => return Ok(Spec::Value(self.value_spec(keyword)?)),
+        }
+    }
+
+    fn import_spec(&mut self) -> PResult<ImportSpec> {
+        let start = self.token().span;
+        let name = match self.tok() {
+            tok if is_ident(tok) => Some(self.ident()?),
+            TokenTypes::Dot => {
+                let span = self.next();
+                Some(Ident {
+                    name: self.interner.intern("."),
+                    span,
+                })
+            }
+            _ => None,
+        };
+        if !matches!(self.tok(), TokenTypes::String | TokenTypes::RawString) {
+            return Err(self.unexpected("import path"));
+        }
+        let path = self.basic_lit();
+        return Ok(ImportSpec {
+            name,
+            path,
+            span: self.span_from(start),
+        });
+    }
+
+    // A type declaration. In `type T[P C] ...` the `[` may start either a type parameter
+    // list or an array length, which is decided as in go/parser: the expression after it
+    // is parsed and split into a parameter name and a constraint if it can be.
+    fn type_spec(&mut self) -> PResult<TypeSpec> {
+        let name = self.ident()?;
+        let mut type_params = None;
+        let mut array = None;
+        if self.tok() == TokenTypes::LBrack {
+            let lbrack = self.next();
+            if is_ident(self.tok()) {
+                let pname = self.ident()?;
+                let mut x = Expr::Ident(pname);
+                if self.tok() != TokenTypes::LBrack {
+                    self.expr_lev += 1;
+                    let lhs = self.primary_expr_from(x)?;
+                    x = self.binary_expr_from(lhs, 1)?;
+                    self.expr_lev -= 1;
+                }
+                // A single name before `]` is an array length, while a constraint that
+                // could also be a value is taken as one if a comma follows it.
+                match self.extract_name(x, self.tok() == TokenTypes::Comma) {
+                    Ok((pname, ptype)) if ptype.is_some() || self.tok() != TokenTypes::RBrack => {
+                        type_params = Some(self.param_list(
+                            lbrack,
+                            Some(pname),
+                            ptype,
+                            TokenTypes::RBrack,
+                        )?);
+                    }
+                    Ok((pname, _)) => {
+                        array = Some(self.array_type_from(lbrack, Some(Expr::Ident(pname)))?)
+                    }
+                    Err(len) => array = Some(self.array_type_from(lbrack, Some(len))?),
+                }
+            } else {
+                array = Some(self.array_type_from(lbrack, None)?);
+            }
+        }
+        let assign = if array.is_none() && self.tok() == TokenTypes::Assign {
+            Some(self.next())
+        } else {
+            None
+        };
+        let ty = match array {
+            Some(ty) => ty,
+            None => self.ty()?,
+        };
+        return Ok(TypeSpec {
+            name,
+            type_params,
+            assign,
+            ty,
+            span: self.span_from(name.span),
+        });
+    }
+
+    fn value_spec(&mut self, keyword: TokenTypes) -> PResult<ValueSpec> {
+        let names = self.ident_list()?;
+        let ty = match self.tok() {
+            TokenTypes::Assign | TokenTypes::Semicolon | TokenTypes::RParen => None,
+            _ => Some(self.ty()?),
+        };
+        let mut values = Vec::new();
+        if self.got(TokenTypes::Assign) {
+            values = self.expr_list()?;
+        }
+        if keyword == TokenTypes::Var && ty.is_none() && values.is_empty() {
+            return Err(self.unexpected("type or '='"));
+        }
+        let span = self.span_from(names[0].span);
+        return Ok(ValueSpec {
+            names,
+            ty,
+            values,
+            span,
+        });
+    }
+
+    fn func_decl(&mut self) -> PResult<FuncDecl> {
+        let start = self.expect(TokenTypes::Func)?;
+        let recv = if self.tok() == TokenTypes::LParen {
+            Some(self.parameters()?)
+        } else {
+            None
+        };
+        let name = self.ident()?;
+        let type_params = if self.tok() == TokenTypes::LBrack {
+            let lbrack = self.next();
+            Some(self.param_list(lbrack, None, None, TokenTypes::RBrack)?)
+        } else {
+            None
+        };
+        let mut ty = self.signature(Some(start), start)?;
+        ty.type_params = type_params;
+        let body = if self.tok() == TokenTypes::LBrace {
+            Some(self.block()?)
+        } else {
+            None
+        };
+        let span = self.span_from(start);
+        self.expect_semi()?;
+        return Ok(FuncDecl {
+            recv,
+            name,
+            ty,
+            body,
+            span,
+        });
+    }
+
+    // ========
+    // Types
+    // ========
+
+    fn ty(&mut self) -> PResult<Expr> {
+        match self.nested_try_type()? {
+            Some(ty) => return Ok(ty),
+            None => return Err(self.unexpected("type")),
+        }
+    }
+
+    // A type one level deeper, such as the element type of another.
+    fn nested_try_type(&mut self) -> PResult<Option<Expr>> {
+        return self.nested(|span| Some(Expr::Bad(BadExpr { span })), Self::try_type);
+    }
+
+    // Parses a type if one starts at the current token.
+    fn try_type(&mut self) -> PResult<Option<Expr>> {
+        let start = self.token().span;
+        let ty = match self.tok() {
+            tok if is_ident(tok) => self.type_name()?,
+            TokenTypes::LBrack => self.array_type()?,
+            TokenTypes::Struct => self.struct_type()?,
+            TokenTypes::Mul => {
+                self.next();
+                let x = self.ty()?;
+                Expr::Star(Box::new(StarExpr {
+                    x,
+                    span: self.span_from(start),
+                }))
+            }
+            TokenTypes::Func => {
+                self.next();
+                Expr::FuncType(Box::new(self.signature(Some(start), start)?))
+            }
+            TokenTypes::Interface => self.interface_type()?,
+            TokenTypes::Map => self.map_type()?,
+            TokenTypes::Chan | TokenTypes::Arrow => self.chan_type()?,
+            TokenTypes::LParen => {
+                self.next();
+                let x = self.ty()?;
+                self.expect(TokenTypes::RParen)?;
+                Expr::Paren(Box::new(ParenExpr {
+                    x,
+                    span: self.span_from(start),
+                }))
+            }
+            _ => return Ok(None),
+        };
+        return Ok(Some(ty));
+    }
+
+    // A type name, possibly qualified with a package name and instantiated.
+    fn type_name(&mut self) -> PResult<Expr> {
+        let name = self.ident()?;
+        let x = self.qualified(name)?;
+        return self.type_args(x);
+    }
+
+    // Instantiates the generic type `x` with the type arguments `[A, B]` after it, if
+    // there are any.
+    fn type_args(&mut self, x: Expr) -> PResult<Expr> {
+        if self.tok() != TokenTypes::LBrack {
+            return Ok(x);
+        }
+        self.next();
+        self.expr_lev += 1;
+        let mut args = vec![self.ty()?];
+        while self.at_comma("type argument list", TokenTypes::RBrack)?
+            && self.tok() != TokenTypes::RBrack
+        {
+            args.push(self.ty()?);
+        }
+        self.expr_lev -= 1;
+        self.expect(TokenTypes::RBrack)?;
+        return Ok(self.index_expr(x, args));
+    }
+
+    fn qualified(&mut self, name: Ident) -> PResult<Expr> {
+        let x = Expr::Ident(name);
+        if !self.got(TokenTypes::Dot) {
+            return Ok(x);
+        }
+        let sel = self.ident()?;
+        return Ok(Expr::Selector(Box::new(SelectorExpr {
+            x,
+            sel,
+            span: self.span_from(name.span),
+        })));
+    }
+
+    // `[]T`, `[N]T` or `[...]T`.
+    fn array_type(&mut self) -> PResult<Expr> {
+        let start = self.expect(TokenTypes::LBrack)?;
+        return self.array_type_from(start, None);
+    }
+
+    // The rest of an array or slice type after its `[`, and after its length if that has
+    // already been parsed.
+    fn array_type_from(&mut self, start: Span, len: Option<Expr>) -> PResult<Expr> {
+        let len = match self.tok() {
+            _ if len.is_some() => len,
+            TokenTypes::RBrack => None,
+            TokenTypes::Ellipsis if self.peek(1) == TokenTypes::RBrack => {
+                let span = self.next();
+                Some(Expr::Ellipsis(Box::new(Ellipsis { elt: None, span })))
+            }
+            _ => {
+                self.expr_lev += 1;
+                let len = self.expr()?;
+                self.expr_lev -= 1;
+                Some(len)
+            }
+        };
+        self.expect(TokenTypes::RBrack)?;
+        let elt = self.ty()?;
+        return Ok(Expr::ArrayType(Box::new(ArrayType {
+            len,
+            elt,
+            span: self.span_from(start),
+        })));
+    }
+
+    fn struct_type(&mut self) -> PResult<Expr> {
+        let start = self.expect(TokenTypes::Struct)?;
+        let opening = self.expect(TokenTypes::LBrace)?;
+        let mut fields = Vec::new();
+        while is_ident(self.tok()) || matches!(self.tok(), TokenTypes::Mul | TokenTypes::LParen) {
+            fields.push(self.field_decl()?);
+            self.expect_semi()?;
+        }
+        let closing = self.expect(TokenTypes::RBrace)?;
+        return Ok(Expr::StructType(Box::new(StructType {
+            fields: FieldList {
+                opening: Some(opening),
+                fields,
+                closing: Some(closing),
+                span: self.span_from(opening),
+            },
+            span: self.span_from(start),
+        })));
+    }
+
+    fn field_decl(&mut self) -> PResult<Field> {
+        let start = self.token().span;
+        let mut names = Vec::new();
+        let ty = match self.tok() {
+            tok if is_ident(tok) => {
+                let name = self.ident()?;
+                match self.tok() {
+                    TokenTypes::Dot
+                    | TokenTypes::String
+                    | TokenTypes::RawString
+                    | TokenTypes::Semicolon
+                    | TokenTypes::RBrace => {
+                        let x = self.qualified(name)?;
+                        self.type_args(x)?
+                    }
+                    TokenTypes::LBrack => match self.array_field_or_instance(name)? {
+                        (Some(name), ty) => {
+                            names.push(name);
+                            ty
+                        }
+                        (None, ty) => ty,
+                    },
+                    _ => {
+                        names.push(name);
+                        while self.got(TokenTypes::Comma) {
+                            names.push(self.ident()?);
+                        }
+                        self.ty()?
+                    }
+                }
+            }
+            TokenTypes::Mul => {
+                self.next();
+                let x = self.type_name()?;
+                Expr::Star(Box::new(StarExpr {
+                    x,
+                    span: self.span_from(start),
+                }))
+            }
+            _ => return Err(self.unexpected("field name or embedded type")),
+        };
+        let tag = match self.tok() {
+            TokenTypes::String | TokenTypes::RawString => Some(self.basic_lit()),
+            _ => None,
+        };
+        return Ok(Field {
+            names,
+            ty,
+            tag,
+            span: self.span_from(start),
+        });
+    }
+
+    fn interface_type(&mut self) -> PResult<Expr> {
+        let start = self.expect(TokenTypes::Interface)?;
+        let opening = self.expect(TokenTypes::LBrace)?;
+        let mut methods = Vec::new();
+        while !matches!(self.tok(), TokenTypes::RBrace | TokenTypes::Eof) {
+            if is_ident(self.tok()) {
+                methods.push(self.method_spec()?);
+            } else {
+                let start = self.token().span;
+                let ty = self.type_elem()?;
+                methods.push(Field {
+                    names: Vec::new(),
+                    ty,
+                    tag: None,
+                    span: self.span_from(start),
+                });
+            }
+            self.expect_semi()?;
+        }
+        let closing = self.expect(TokenTypes::RBrace)?;
+        return Ok(Expr::InterfaceType(Box::new(InterfaceType {
+            methods: FieldList {
+                opening: Some(opening),
+                fields: methods,
+                closing: Some(closing),
+                span: self.span_from(opening),
+            },
+            span: self.span_from(start),
+        })));
+    }
+
+    // A method, or an embedded interface or union of types.
+    fn method_spec(&mut self) -> PResult<Field> {
+        let name = self.ident()?;
+        if self.tok() == TokenTypes::LParen {
+            let ty = self.signature(None, self.token().span)?;
+            return Ok(Field {
+                names: vec![name],
+                ty: Expr::FuncType(Box::new(ty)),
+                tag: None,
+                span: self.span_from(name.span),
+            });
+        }
+        let x = self.qualified(name)?;
+        let x = self.type_args(x)?;
+        let ty = self.union(x)?;
+        return Ok(Field {
+            names: Vec::new(),
+            ty,
+            tag: None,
+            span: self.span_from(name.span),
+        });
+    }
+
+    // A union of type terms such as `~int | ~string`, as found in constraints.
+    fn type_elem(&mut self) -> PResult<Expr> {
+        let x = self.type_term()?;
+        return self.union(x);
+    }
+
+    // The rest of a union whose first term `x` has already been parsed.
+    fn union(&mut self, mut x: Expr) -> PResult<Expr> {
+        while self.got(TokenTypes::Or) {
+            let y = self.type_term()?;
+            let span = self.span_from(x.span());
+            x = Expr::Binary(Box::new(BinaryExpr {
+                op: TokenTypes::Or,
+                x,
+                y,
+                span,
+            }));
+        }
+        return Ok(x);
+    }
+
+    // A type, or `~T` for the types whose underlying type is T.
+    fn type_term(&mut self) -> PResult<Expr> {
+        if self.tok() != TokenTypes::Tilde {
+            return self.ty();
+        }
+        let start = self.next();
+        let x = self.ty()?;
+        return Ok(Expr::Unary(Box::new(UnaryExpr {
+            op: TokenTypes::Tilde,
+            x,
+            span: self.span_from(start),
+        })));
+    }
+
+    fn map_type(&mut self) -> PResult<Expr> {
+        let start = self.expect(TokenTypes::Map)?;
+        self.expect(TokenTypes::LBrack)?;
+        let key = self.ty()?;
+        self.expect(TokenTypes::RBrack)?;
+        let value = self.ty()?;
+        return Ok(Expr::MapType(Box::new(MapType {
+            key,
+            value,
+            span: self.span_from(start),
+        })));
+    }
+
+    // `chan T`, `chan<- T` or `<-chan T`.
+    fn chan_type(&mut self) -> PResult<Expr> {
+        let start = self.token().span;
+        let dir = if self.got(TokenTypes::Arrow) {
+            self.expect(TokenTypes::Chan)?;
+            ChanDir::Recv
+        } else {
+            self.expect(TokenTypes::Chan)?;
+            if self.got(TokenTypes::Arrow) {
+                ChanDir::Send
+            } else {
+                ChanDir::Both
+            }
+        };
+        let value = self.ty()?;
+        return Ok(Expr::ChanType(Box::new(ChanType {
+            dir,
+            value,
+            span: self.span_from(start),
+        })));
+    }
+
+    // The parameters and results of a function, after the `func` keyword and name if
+    // there are any. `start` is where the FuncType begins.
+    fn signature(&mut self, func: Option<Span>, start: Span) -> PResult<FuncType> {
+        let params = self.parameters()?;
+        let results = self.results()?;
+        return Ok(FuncType {
+            func,
+            type_params: None,
+            params,
+            results,
+            span: self.span_from(start),
+        });
+    }
+
+    fn results(&mut self) -> PResult<Option<FieldList>> {
+        if self.tok() == TokenTypes::LParen {
+            return Ok(Some(self.parameters()?));
+        }
+        let Some(ty) = self.nested_try_type()? else {
+            return Ok(None);
+        };
+        let span = ty.span();
+        return Ok(Some(FieldList {
+            opening: None,
+            fields: vec![Field {
+                names: Vec::new(),
+                ty,
+                tag: None,
+                span,
+            }],
+            closing: None,
+            span,
+        }));
+    }
+
+    fn parameters(&mut self) -> PResult<FieldList> {
+        let opening = self.expect(TokenTypes::LParen)?;
+        return self.param_list(opening, None, None, TokenTypes::RParen);
+    }
+
+    // The rest of a parameter list after its `(`, or of a type parameter list after its
+    // `[`. Whether the parameters are named is only known once the whole list has been
+    // seen, so each entry is parsed as an optional name and an optional type first, as in
+    // go/parser. The first name and type of a type parameter list may already have been
+    // parsed as `name0` and `ty0`.
+    fn param_list(
+        &mut self,
+        opening: Span,
+        mut name0: Option<Ident>,
+        mut ty0: Option<Expr>,
+        closing: TokenTypes,
+    ) -> PResult<FieldList> {
+        let type_params = closing == TokenTypes::RBrack;
+        let mut list = Vec::new();
+        while name0.is_some() || !matches!(self.tok(), TokenTypes::Eof) && self.tok() != closing {
+            let param = match ty0.take() {
+                Some(ty) => (name0.take(), Some(self.union(ty)?)),
+                None => self.param_decl(name0.take(), type_params)?,
+            };
+            list.push(param);
+            if !self.at_comma("parameter list", closing)? {
+                break;
+            }
+        }
+        let closing = self.expect(closing)?;
+
+        let mut fields = Vec::new();
+        let named = list
+            .iter()
+            .filter(|(n, t)| n.is_some() && t.is_some())
+            .count();
+        if named == 0 {
+            // Without a name followed by a type, every name is a type name.
+            for (name, ty) in list {
+                let ty = match (name, ty) {
+                    (_, Some(ty)) if !type_params => ty,
+                    (Some(name), None) if !type_params => Expr::Ident(name),
+                    (Some(name), _) => return Err(self.param_error(name.span, type_params, true)),
+                    (None, ty) => {
+                        let ty = ty.expect("a parameter has a name or a type");
+                        return Err(self.param_error(ty.span(), type_params, false));
+                    }
+                };
+                let span = ty.span();
+                fields.push(Field {
+                    names: Vec::new(),
+                    ty,
+                    tag: None,
+                    span,
+                });
+            }
+        } else {
+            // Names without a type share the type of the next parameter.
+            let mut names = Vec::new();
+            for (name, ty) in list {
+                names.extend(name);
+                let Some(ty) = ty else {
+                    continue;
+                };
+                if name.is_none() {
+                    return Err(self.param_error(ty.span(), type_params, false));
+                }
+                fields.push(param_field(std::mem::take(&mut names), ty));
+            }
+            if let Some(name) = names.first() {
+                return Err(self.param_error(name.span, type_params, true));
+            }
+        }
+        if type_params && fields.is_empty() {
+            return Err(Diagnostic::error("Empty type parameter list.")
+                .with_code(codes::UNEXPECTED_TOKEN)
+                .with_primary(opening.to(closing), ""));
+        }
+        return Ok(FieldList {
+            opening: Some(opening),
+            fields,
+            closing: Some(closing),
+            span: self.span_from(opening),
+        });
+    }
+
+    // The parameter at `span` is missing its type if `named`, or its name otherwise.
+    fn param_error(&self, span: Span, type_params: bool, named: bool) -> Diagnostic {
+        let message = match (type_params, named) {
+            (false, _) => "Mixed named and unnamed parameters.",
+            (true, true) => "Missing type constraint.",
+            (true, false) => "Type parameters must be named.",
+        };
+        return Diagnostic::error(message)
+            .with_code(codes::UNEXPECTED_TOKEN)
+            .with_primary(span, "");
+    }
+
+    // An entry of a parameter list: a name, a type, or a name followed by a type. In a
+    // type parameter list the type may be a union of terms.
+    fn param_decl(
+        &mut self,
+        name0: Option<Ident>,
+        type_params: bool,
+    ) -> PResult<(Option<Ident>, Option<Expr>)> {
+        let (mut name, mut ty) = (None, None);
+        if name0.is_some() || is_ident(self.tok()) {
+            let first = match name0 {
+                Some(name) => name,
+                None => self.ident()?,
+            };
+            name = Some(first);
+            match self.tok() {
+                tok if is_ident(tok) => ty = Some(self.ty()?),
+                TokenTypes::Mul
+                | TokenTypes::Arrow
+                | TokenTypes::Func
+                | TokenTypes::Chan
+                | TokenTypes::Map
+                | TokenTypes::Struct
+                | TokenTypes::Interface
+                | TokenTypes::LParen => ty = Some(self.ty()?),
+                TokenTypes::LBrack => {
+                    let (n, t) = self.array_field_or_instance(first)?;
+                    (name, ty) = (n, Some(t));
+                }
+                TokenTypes::Ellipsis => return Ok((name, Some(self.var_type()?))),
+                TokenTypes::Dot => {
+                    let x = self.qualified(first)?;
+                    (name, ty) = (None, Some(self.type_args(x)?));
+                }
+                TokenTypes::Tilde if type_params => return Ok((name, Some(self.type_elem()?))),
+                TokenTypes::Or if type_params => {
+                    return Ok((None, Some(self.union(Expr::Ident(first))?)));
+                }
+                _ => {}
+            }
+        } else {
+            match self.tok() {
+                TokenTypes::Mul
+                | TokenTypes::Arrow
+                | TokenTypes::Func
+                | TokenTypes::LBrack
+                | TokenTypes::Chan
+                | TokenTypes::Map
+                | TokenTypes::Struct
+                | TokenTypes::Interface
+                | TokenTypes::LParen => ty = Some(self.ty()?),
+                TokenTypes::Ellipsis => return Ok((None, Some(self.var_type()?))),
+                TokenTypes::Tilde if type_params => return Ok((None, Some(self.type_elem()?))),
+                _ => return Err(self.unexpected("parameter")),
+            }
+        }
+        if type_params && self.tok() == TokenTypes::Or {
+            if let Some(x) = ty {
+                ty = Some(self.union(x)?);
+            }
+        }
+        return Ok((name, ty));
+    }
+
+    // `name []T`, `name [N]T` or the instantiation `name[A, B]`, which start alike in a
+    // parameter list or a struct.
+    fn array_field_or_instance(&mut self, name: Ident) -> PResult<(Option<Ident>, Expr)> {
+        let lbrack = self.expect(TokenTypes::LBrack)?;
+        let mut args = Vec::new();
+        let mut trailing_comma = None;
+        if self.tok() != TokenTypes::RBrack {
+            self.expr_lev += 1;
+            args.push(self.expr()?);
+            while self.tok() == TokenTypes::Comma {
+                let comma = self.next();
+                if self.tok() == TokenTypes::RBrack {
+                    trailing_comma = Some(comma);
+                    break;
+                }
+                args.push(self.expr()?);
+            }
+            self.expr_lev -= 1;
+        }
+        self.expect(TokenTypes::RBrack)?;
+        if args.is_empty() {
+            let elt = self.ty()?;
+            return Ok((
+                Some(name),
+                Expr::ArrayType(Box::new(ArrayType {
+                    len: None,
+                    elt,
+                    span: self.span_from(lbrack),
+                })),
+            ));
+        }
+        if args.len() == 1 {
+            if let Some(elt) = self.nested_try_type()? {
+                if let Some(comma) = trailing_comma {
+                    return Err(Diagnostic::error("Unexpected comma in array length.")
+                        .with_code(codes::UNEXPECTED_TOKEN)
+                        .with_primary(comma, ""));
+                }
+                return Ok((
+                    Some(name),
+                    Expr::ArrayType(Box::new(ArrayType {
+                        len: args.pop(),
+                        elt,
+                        span: self.span_from(lbrack),
+                    })),
+                ));
+            }
+        }
+        return Ok((None, self.index_expr(Expr::Ident(name), args)));
+    }
+
+    // A parameter type, which may be variadic.
+    fn var_type(&mut self) -> PResult<Expr> {
+        match self.try_var_type()? {
+            Some(ty) => return Ok(ty),
+            None => return Err(self.unexpected("type")),
+        }
+    }
+
+    fn try_var_type(&mut self) -> PResult<Option<Expr>> {
+        if self.tok() != TokenTypes::Ellipsis {
+            return self.nested_try_type();
+        }
+        let start = self.next();
+        let elt = self.ty()?;
+        return Ok(Some(Expr::Ellipsis(Box::new(Ellipsis {
+            elt: Some(elt),
+            span: self.span_from(start),
+        }))));
+    }
+
+    // ========
+    // Expressions
+    // ========
+
+    fn ident(&mut self) -> PResult<Ident> {
+        match self.tok() {
+            tok if is_ident(tok) => {
+                let name = self.token().symbol.expect("identifiers are interned");
+                return Ok(Ident {
+                    name,
+                    span: self.next(),
+                });
+            }
+            _ => return Err(self.unexpected("identifier")),
+        }
+    }
+
+    fn ident_list(&mut self) -> PResult<Vec<Ident>> {
+        let mut names = vec![self.ident()?];
+        while self.got(TokenTypes::Comma) {
+            names.push(self.ident()?);
+        }
+        return Ok(names);
+    }
+
+    fn basic_lit(&mut self) -> BasicLit {
+        let token = self.token();
+        let kind = token.token_type;
+        let value = match kind {
+            TokenTypes::RawString => strip_cr(token.lexeme, false),
+            _ => String::from_utf8_lossy(token.lexeme).into_owned(),
+        };
+        return BasicLit {
+            kind,
+            value,
+            span: self.next(),
+        };
+    }
+
+    fn expr(&mut self) -> PResult<Expr> {
+        return self.binary_expr(1);
+    }
+
+    fn expr_list(&mut self) -> PResult<Vec<Expr>> {
+        let mut list = vec![self.expr()?];
+        while self.got(TokenTypes::Comma) {
+            list.push(self.expr()?);
+        }
+        return Ok(list);
+    }
+
+    // Parses operators of precedence `prec1` and higher, which are left-associative.
+    fn binary_expr(&mut self, prec1: u8) -> PResult<Expr> {
+        let x = self.nested_unary_expr()?;
+        return self.binary_expr_from(x, prec1);
+    }
+
+    // The rest of a binary expression whose first operand `x` has already been parsed. The
+    // chain is parsed in a loop, but each operator nests `x` one level deeper in the tree,
+    // so it counts towards the nesting limit as in go/parser.
+    fn binary_expr_from(&mut self, mut x: Expr, prec1: u8) -> PResult<Expr> {
+        let nest_lev = self.nest_lev;
+        loop {
+            let op = self.tok();
+            let prec = op.precedence();
+            if prec < prec1 {
+                self.nest_lev = nest_lev;
+                return Ok(x);
+            }
+            if !self.enter() {
+                self.nest_lev = nest_lev;
+                return Ok(Expr::Bad(BadExpr { span: x.span() }));
+            }
+            self.next();
+            let y = match self.binary_expr(prec + 1) {
+                Ok(y) => y,
+                Err(diagnostic) => {
+                    self.nest_lev = nest_lev;
+                    return Err(diagnostic);
+                }
+            };
+            let span = self.span_from(x.span());
+            x = Expr::Binary(Box::new(BinaryExpr { op, x, y, span }));
+        }
+    }
+
+    fn nested_unary_expr(&mut self) -> PResult<Expr> {
+        return self.nested(|span| Expr::Bad(BadExpr { span }), Self::unary_expr);
+    }
+
+    fn unary_expr(&mut self) -> PResult<Expr> {
+        let start = self.token().span;
+        match self.tok() {
+            TokenTypes::Add
+            | TokenTypes::Sub
+            | TokenTypes::Not
+            | TokenTypes::Caret
+            | TokenTypes::Amp
+            | TokenTypes::Tilde => {
+                let op = self.tok();
+                self.next();
+                let x = self.nested_unary_expr()?;
+                return Ok(Expr::Unary(Box::new(UnaryExpr {
+                    op,
+                    x,
+                    span: self.span_from(start),
+                })));
+            }
+            TokenTypes::Arrow => {
+                self.next();
+                let x = self.nested_unary_expr()?;
+                // `<-chan T` is a channel type, not a receive from `chan T`.
+                if let Expr::ChanType(mut chan) = x {
+                    recv_chan(&mut chan)?;
+                    chan.span = self.span_from(start);
+                    return Ok(Expr::ChanType(chan));
+                }
+                return Ok(Expr::Unary(Box::new(UnaryExpr {
+                    op: TokenTypes::Arrow,
+                    x,
+                    span: self.span_from(start),
+                })));
+            }
+            TokenTypes::Mul => {
+                self.next();
+                let x = self.nested_unary_expr()?;
+                return Ok(Expr::Star(Box::new(StarExpr {
+                    x,
+                    span: self.span_from(start),
+                })));
+            }
+            _ => return self.primary_expr(),
+        }
+    }
+
+    fn operand(&mut self) -> PResult<Expr> {
+        let start = self.token().span;
+        match self.tok() {
+            tok if is_ident(tok) => return Ok(Expr::Ident(self.ident()?)),
+            TokenTypes::Int
+            | TokenTypes::Octal
+            | TokenTypes::Hex
+            | TokenTypes::Binary
+            | TokenTypes::Float
+            | TokenTypes::Imag
+            | TokenTypes::Char
+            | TokenTypes::String
+            | TokenTypes::RawString => return Ok(Expr::BasicLit(self.basic_lit())),
+            TokenTypes::LParen => {
+                self.next();
+                self.expr_lev += 1;
+                let x = self.expr()?;
+                self.expr_lev -= 1;
+                self.expect(TokenTypes::RParen)?;
+                return Ok(Expr::Paren(Box::new(ParenExpr {
+                    x,
+                    span: self.span_from(start),
+                })));
+            }
+            TokenTypes::Func => {
+                self.next();
+                let ty = self.signature(Some(start), start)?;
+                if self.tok() != TokenTypes::LBrace {
+                    return Ok(Expr::FuncType(Box::new(ty)));
+                }
+                self.expr_lev += 1;
+                let body = self.block()?;
+                self.expr_lev -= 1;
+                return Ok(Expr::FuncLit(Box::new(FuncLit {
+                    ty,
+                    body,
+                    span: self.span_from(start),
+                })));
+            }
+            TokenTypes::LBrack
+            | TokenTypes::Struct
+            | TokenTypes::Map
+            | TokenTypes::Chan
+            | TokenTypes::Interface => return self.ty(),
+            _ => return Err(self.unexpected("expression")),
+        }
+    }
+
+    fn primary_expr(&mut self) -> PResult<Expr> {
+        let x = self.operand()?;
+        return self.primary_expr_from(x);
+    }
+
+    // The selectors, indices, calls and composite literal after the operand `x`.
+    fn primary_expr_from(&mut self, mut x: Expr) -> PResult<Expr> {
+        loop {
+            let start = x.span();
+            match self.tok() {
+                TokenTypes::Dot => {
+                    self.next();
+                    match self.tok() {
+                        tok if is_ident(tok) => {
+                            let sel = self.ident()?;
+                            x = Expr::Selector(Box::new(SelectorExpr {
+                                x,
+                                sel,
+                                span: self.span_from(start),
+                            }));
+                        }
+                        TokenTypes::LParen => {
+                            self.next();
+                            let ty = if self.got(TokenTypes::Type) {
+                                None
+                            } else {
+                                Some(self.ty()?)
+                            };
+                            self.expect(TokenTypes::RParen)?;
+                            x = Expr::TypeAssert(Box::new(TypeAssertExpr {
+                                x,
+                                ty,
+                                span: self.span_from(start),
+                            }));
+                        }
+                        _ => return Err(self.unexpected("selector or type assertion")),
+                    }
+                }
+                TokenTypes::LBrack => x = self.index_or_slice(x)?,
+                TokenTypes::LParen => x = self.call(x)?,
+                TokenTypes::LBrace
+                    if is_literal_type(&x) && (self.expr_lev >= 0 || !is_type_name(&x)) =>
+                {
+                    x = self.composite_lit(Some(x))?;
+                }
+                _ => return Ok(x),
+            }
+        }
+    }
+
+    fn index_or_slice(&mut self, x: Expr) -> PResult<Expr> {
+        let start = x.span();
+        self.expect(TokenTypes::LBrack)?;
+        self.expr_lev += 1;
+        let mut index: [Option<Expr>; 3] = [None, None, None];
+        let mut colons = 0;
+        if self.tok() != TokenTypes::Colon {
+            index[0] = Some(self.expr()?);
+        }
+        // `x[A, B]` instantiates a generic function or type.
+        let mut args = Vec::new();
+        if self.tok() == TokenTypes::Comma {
+            args.extend(index[0].take());
+            while self.got(TokenTypes::Comma) && self.tok() != TokenTypes::RBrack {
+                args.push(self.ty()?);
+            }
+        }
+        while self.tok() == TokenTypes::Colon && colons < 2 {
+            colons += 1;
+            self.next();
+            if self.tok() != TokenTypes::Colon && self.tok() != TokenTypes::RBrack {
+                index[colons] = Some(self.expr()?);
+            }
+        }
+        self.expr_lev -= 1;
+        self.expect(TokenTypes::RBrack)?;
+
+        if !args.is_empty() {
+            return Ok(self.index_expr(x, args));
+        }
+        let [low, high, max] = index;
+        if colons == 0 {
+            return Ok(Expr::Index(Box::new(IndexExpr {
+                x,
+                index: low.expect("an index without colons is not empty"),
+                span: self.span_from(start),
+            })));
+        }
+        return Ok(Expr::Slice(Box::new(SliceExpr {
+            x,
+            low,
+            high,
+            max,
+            slice3: colons == 2,
+            span: self.span_from(start),
+        })));
+    }
+
+    // `x[A]` or `x[A, B]`.
+    fn index_expr(&self, x: Expr, mut indices: Vec<Expr>) -> Expr {
+        let span = self.span_from(x.span());
+        if indices.len() == 1 {
+            let index = indices.pop().expect("there is one index");
+            return Expr::Index(Box::new(IndexExpr { x, index, span }));
+        }
+        return Expr::IndexList(Box::new(IndexListExpr { x, indices, span }));
+    }
+
+    // Splits `x`, parsed after the `[` of a type declaration, into the name of a type
+    // parameter and the start of its constraint, if `x` can be read that way. The split
+    // is only made if the constraint cannot be a value, unless `force` is set. Returns
+    // `x` itself if it cannot be split, as go/parser's extractName does.
+    fn extract_name(&self, x: Expr, force: bool) -> Result<(Ident, Option<Expr>), Expr> {
+        match x {
+            Expr::Ident(name) => return Ok((name, None)),
+            Expr::Binary(b) if b.op == TokenTypes::Mul => {
+                let Expr::Ident(name) = b.x else {
+                    return Err(Expr::Binary(b));
+                };
+                if !force && !is_type_elem(&b.y) {
+                    return Err(Expr::Binary(b));
+                }
+                // `P *T` declares P with the pointer type `*T`.
+                let star = self.token_after(name.span.end);
+                let span = star.to(b.y.span());
+                return Ok((name, Some(Expr::Star(Box::new(StarExpr { x: b.y, span })))));
+            }
+            Expr::Binary(b) if b.op == TokenTypes::Or => {
+                let BinaryExpr { op, x, y, span } = *b;
+                match self.extract_name(x, force || is_type_elem(&y)) {
+                    Ok((name, Some(lhs))) => {
+                        let span = lhs.span().to(y.span());
+                        let x = Expr::Binary(Box::new(BinaryExpr {
+                            op,
+                            x: lhs,
+                            y,
+                            span,
+                        }));
+                        return Ok((name, Some(x)));
+                    }
+                    Ok((name, None)) => {
+                        let x = Expr::Ident(name);
+                        return Err(Expr::Binary(Box::new(BinaryExpr { op, x, y, span })));
+                    }
+                    Err(x) => return Err(Expr::Binary(Box::new(BinaryExpr { op, x, y, span }))),
+                }
+            }
+            Expr::Call(c)
+                if matches!(c.fun, Expr::Ident(_))
+                    && c.args.len() == 1
+                    && c.ellipsis.is_none()
+                    && (force || is_type_elem(&c.args[0])) =>
+            {
+                // `P (T)` declares P with the parenthesized type `(T)`.
+                let CallExpr { fun, mut args, .. } = *c;
+                let Expr::Ident(name) = fun else {
+                    unreachable!("the function is a name");
+                };
+                let lparen = self.token_after(name.span.end);
+                let x = args.pop().expect("there is one argument");
+                let span = lparen.to(c.span);
+                return Ok((name, Some(Expr::Paren(Box::new(ParenExpr { x, span })))));
+            }
+            x => return Err(x),
+        }
+    }
+
+    // The span of the first token at or after `offset`, such as the operator after an
+    // operand.
+    fn token_after(&self, offset: usize) -> Span {
+        let i = self.tokens.partition_point(|t| t.span.start < offset);
+        return self.tokens[i].span;
+    }
+
+    fn call(&mut self, fun: Expr) -> PResult<Expr> {
+        let start = fun.span();
+        self.expect(TokenTypes::LParen)?;
+        self.expr_lev += 1;
+        let mut args = Vec::new();
+        let mut ellipsis = None;
+        while self.tok() != TokenTypes::RParen {
+            args.push(self.expr()?);
+            if self.tok() == TokenTypes::Ellipsis {
+                ellipsis = Some(self.next());
+            }
+            if !self.at_comma("argument list", TokenTypes::RParen)? {
+                break;
+            }
+        }
+        self.expr_lev -= 1;
+        self.expect(TokenTypes::RParen)?;
+        return Ok(Expr::Call(Box::new(CallExpr {
+            fun,
+            args,
+            ellipsis,
+            span: self.span_from(start),
+        })));
+    }
+
+    fn composite_lit(&mut self, ty: Option<Expr>) -> PResult<Expr> {
+        let lbrace = self.expect(TokenTypes::LBrace)?;
+        let start = ty.as_ref().map_or(lbrace, |ty| ty.span());
+        self.expr_lev += 1;
+        let mut elts = Vec::new();
+        while self.tok() != TokenTypes::RBrace {
+            elts.push(self.element()?);
+            if !self.at_comma("composite literal", TokenTypes::RBrace)? {
+                break;
+            }
+        }
+        self.expr_lev -= 1;
+        let rbrace = self.expect(TokenTypes::RBrace)?;
+        return Ok(Expr::CompositeLit(Box::new(CompositeLit {
+            ty,
+            lbrace,
+            elts,
+            rbrace,
+            span: self.span_from(start),
+        })));
+    }
+
+    fn element(&mut self) -> PResult<Expr> {
+        let key = self.element_value()?;
+        if !self.got(TokenTypes::Colon) {
+            return Ok(key);
+        }
+        let value = self.element_value()?;
+        let span = self.span_from(key.span());
+        return Ok(Expr::KeyValue(Box::new(KeyValueExpr { key, value, span })));
+    }
+
+    // An element or key, where a composite literal may leave out its type.
+    fn element_value(&mut self) -> PResult<Expr> {
+        if self.tok() == TokenTypes::LBrace {
+            return self.nested(
+                |span| Expr::Bad(BadExpr { span }),
+                |p| p.composite_lit(None),
+            );
+        }
+        return self.expr();
+    }
+
+    // ========
+    // Statements
+    // ========
+
+    // A block whose closing brace is missing is reported, and ends where the statements in
+    // it do.
+    fn block(&mut self) -> PResult<BlockStmt> {
+        let start = self.expect(TokenTypes::LBrace)?;
+        let stmts = self.stmt_list();
+        if let Err(diagnostic) = self.expect(TokenTypes::RBrace) {
+            self.report(diagnostic);
+        }
+        return Ok(BlockStmt {
+            stmts,
+            span: self.span_from(start),
+        });
+    }
+
+    // Parses statements up to the end of a block or case clause. A statement that cannot
+    // be parsed is reported, skipped and replaced by a BadStmt.
+    fn stmt_list(&mut self) -> Vec<Stmt> {
+        let mut stmts = Vec::new();
+        while !matches!(
+            self.tok(),
+            TokenTypes::Case | TokenTypes::Default | TokenTypes::RBrace | TokenTypes::Eof
+        ) && !self.at_decl()
+        {
+            let (from, start, expr_lev) = (self.pos, self.token().span, self.expr_lev);
+            match self.nested_stmt() {
+                Ok(stmt) => stmts.push(stmt),
+                Err(diagnostic) => {
+                    self.report(diagnostic);
+                    self.expr_lev = expr_lev;
+                    self.sync_stmt(from);
+                    stmts.push(Stmt::Bad(BadStmt {
+                        span: self.span_from(start),
+                    }));
+                }
+            }
+        }
+        return stmts;
+    }
+
+    // A statement one level deeper, such as one in a block or after a label.
+    fn nested_stmt(&mut self) -> PResult<Stmt> {
+        return self.nested(|span| Stmt::Bad(BadStmt { span }), Self::stmt);
+    }
+
+    // A statement including the semicolon after it.
+    fn stmt(&mut self) -> PResult<Stmt> {
+        let start = self.token().span;
+        let stmt = match self.tok() {
+            TokenTypes::Const | TokenTypes::Type | TokenTypes::Var => {
+                return Ok(Stmt::Decl(self.gen_decl(self.tok())?));
+            }
+            TokenTypes::Identifier
+            | TokenTypes::True
+            | TokenTypes::False
+            | TokenTypes::Nil
+            | TokenTypes::Int
+            | TokenTypes::Octal
+            | TokenTypes::Hex
+            | TokenTypes::Binary
+            | TokenTypes::Float
+            | TokenTypes::Imag
+            | TokenTypes::Char
+            | TokenTypes::String
+            | TokenTypes::RawString
+            | TokenTypes::Func
+            | TokenTypes::LParen
+            | TokenTypes::LBrack
+            | TokenTypes::Struct
+            | TokenTypes::Map
+            | TokenTypes::Chan
+            | TokenTypes::Interface
+            | TokenTypes::Add
+            | TokenTypes::Sub
+            | TokenTypes::Mul
+            | TokenTypes::Amp
+            | TokenTypes::Caret
+            | TokenTypes::Arrow
+            | TokenTypes::Not => {
+                let (stmt, _) = self.simple_stmt(Mode::LabelOk)?;
+                // A labeled statement has already consumed its semicolon.
+                if let Stmt::Labeled(_) = stmt {
+                    return Ok(stmt);
+                }
+                stmt
+            }
+            TokenTypes::Go => {
+                self.next();
+                let call = self.call_expr("go")?;
+                Stmt::Go(Box::new(GoStmt {
+                    call,
+                    span: self.span_from(start),
+                }))
+            }
+            TokenTypes::Defer => {
+                self.next();
+                let call = self.call_expr("defer")?;
+                Stmt::Defer(Box::new(DeferStmt {
+                    call,
+                    span: self.span_from(start),
+                }))
+            }
+            TokenTypes::Return => {
+                self.next();
+                let mut results = Vec::new();
+                if self.tok() != TokenTypes::Semicolon && self.tok() != TokenTypes::RBrace {
+                    results = self.expr_list()?;
+                }
+                Stmt::Return(ReturnStmt {
+                    results,
+                    span: self.span_from(start),
+                })
+            }
+            TokenTypes::Break
+            | TokenTypes::Continue
+            | TokenTypes::Goto
+            | TokenTypes::Fallthrough => {
+                let tok = self.tok();
+                self.next();
+                let mut label = None;
+                if tok != TokenTypes::Fallthrough && is_ident(self.tok()) {
+                    label = Some(self.ident()?);
+                }
+                Stmt::Branch(BranchStmt {
+                    tok,
+                    label,
+                    span: self.span_from(start),
+                })
+            }
+            TokenTypes::LBrace => Stmt::Block(self.block()?),
+            TokenTypes::If => Stmt::If(Box::new(self.if_stmt()?)),
+            TokenTypes::Switch => self.switch_stmt()?,
+            TokenTypes::Select => Stmt::Select(self.select_stmt()?),
+            TokenTypes::For => self.for_stmt()?,
+            TokenTypes::Semicolon => {
+                let implicit = self.token().lexeme == b"\n";
+                return Ok(Stmt::Empty(EmptyStmt {
+                    implicit,
+                    span: self.next(),
+                }));
+            }
+            TokenTypes::RBrace => {
+                // An empty statement before a closing brace, as in `{ L: }`.
+                let span = Span::new(
+                    self.file,
+                    start.start,
+                    start.start,
+                    start.line,
+                    start.column,
+                );
+                return Ok(Stmt::Empty(EmptyStmt {
+                    implicit: true,
+                    span,
+                }));
+            }
+            _ => return Err(self.unexpected("statement")),
+        };
+        self.expect_semi()?;
+        return Ok(stmt);
+    }
+
+    // The call of a go or defer statement.
+    fn call_expr(&mut self, keyword: &str) -> PResult<Expr> {
+        let x = self.expr()?;
+        if let Expr::Call(_) = x {
+            return Ok(x);
+        }
+        let diagnostic = Diagnostic::error(&format!(
+            "Expression in {} must be a function call.",
+            keyword
+        ))
+        .with_code(codes::UNEXPECTED_TOKEN)
+        .with_primary(x.span(), "");
+        return Ok(self.bad_expr(&x, diagnostic));
+    }
+
+    // Parses a simple statement. With `Mode::RangeOk`, `k, v := range x` is parsed as an
+    // assignment of `x` and reported by returning true.
+    fn simple_stmt(&mut self, mode: Mode) -> PResult<(Stmt, bool)> {
+        let lhs = self.expr_list()?;
+        let start = lhs[0].span();
+        match self.tok() {
+            TokenTypes::Define
+            | TokenTypes::Assign
+            | TokenTypes::AddAssign
+            | TokenTypes::SubAssign
+            | TokenTypes::MulAssign
+            | TokenTypes::QuoAssign
+            | TokenTypes::PercentAssign
+            | TokenTypes::AndAssign
+            | TokenTypes::OrAssign
+            | TokenTypes::CaretAssign
+            | TokenTypes::LshiftAssign
+            | TokenTypes::RshiftAssign
+            | TokenTypes::BitClearAssign => {
+                let tok = self.tok();
+                self.next();
+                let is_range = mode == Mode::RangeOk
+                    && self.tok() == TokenTypes::Range
+                    && matches!(tok, TokenTypes::Define | TokenTypes::Assign);
+                let rhs = if is_range {
+                    self.next();
+                    vec![self.expr()?]
+                } else {
+                    self.expr_list()?
+                };
+                let span = self.span_from(start);
+                return Ok((
+                    Stmt::Assign(Box::new(AssignStmt {
+                        lhs,
+                        tok,
+                        rhs,
+                        span,
+                    })),
+                    is_range,
+                ));
+            }
+            _ => {}
+        }
+
+        let x = self.single(lhs)?;
+        match self.tok() {
+            TokenTypes::Colon if mode == Mode::LabelOk => {
+                if let Expr::Ident(label) = x {
+                    self.next();
+                    let stmt = self.nested_stmt()?;
+                    return Ok((
+                        Stmt::Labeled(Box::new(LabeledStmt {
+                            label,
+                            stmt,
+                            span: self.span_from(start),
+                        })),
+                        false,
+                    ));
+                }
+                return Ok((Stmt::Expr(x), false));
+            }
+            TokenTypes::Arrow => {
+                self.next();
+                let value = self.expr()?;
+                return Ok((
+                    Stmt::Send(Box::new(SendStmt {
+                        chan: x,
+                        value,
+                        span: self.span_from(start),
+                    })),
+                    false,
+                ));
+            }
+            TokenTypes::Increment | TokenTypes::Decrement => {
+                let tok = self.tok();
+                self.next();
+                return Ok((
+                    Stmt::IncDec(Box::new(IncDecStmt {
+                        x,
+                        tok,
+                        span: self.span_from(start),
+                    })),
+                    false,
+                ));
+            }
+            _ => return Ok((Stmt::Expr(x), false)),
+        }
+    }
+
+    // The only expression of a list that must have exactly one.
+    fn single(&self, mut list: Vec<Expr>) -> PResult<Expr> {
+        if list.len() > 1 {
+            return Err(Diagnostic::error("Expected 1 expression.")
+                .with_code(codes::UNEXPECTED_TOKEN)
+                .with_primary(list[1].span(), ""));
+        }
+        return Ok(list.remove(0));
+    }
+
+    // The expression of a simple statement used as a condition or switch tag, or a
+    // BadExpr for any other statement.
+    fn stmt_expr(&mut self, stmt: Stmt, context: &str) -> Expr {
+        match stmt {
+            Stmt::Expr(x) => return x,
+            stmt => {
+                self.report(
+                    Diagnostic::error(&format!("Cannot use {} as value.", context))
+                        .with_code(codes::UNEXPECTED_TOKEN)
+                        .with_primary(stmt.span(), ""),
+                );
+                return Expr::Bad(BadExpr { span: stmt.span() });
+            }
+        }
+    }
+
+    fn if_stmt(&mut self) -> PResult<IfStmt> {
+        let start = self.expect(TokenTypes::If)?;
+        let (init, cond) = self.if_header()?;
+        let body = self.block()?;
+        let els = if self.got(TokenTypes::Else) {
+            match self.tok() {
+                TokenTypes::If => Some(self.nested(
+                    |span| Stmt::Bad(BadStmt { span }),
+                    |p| Ok(Stmt::If(Box::new(p.if_stmt()?))),
+                )?),
+                TokenTypes::LBrace => Some(Stmt::Block(self.block()?)),
+                _ => return Err(self.unexpected("if statement or block")),
+            }
+        } else {
+            None
+        };
+        return Ok(IfStmt {
+            init,
+            cond,
+            body,
+            els,
+            span: self.span_from(start),
+        });
+    }
+
+    fn if_header(&mut self) -> PResult<(Option<Stmt>, Expr)> {
+        if self.tok() == TokenTypes::LBrace {
+            return Ok((None, self.missing_condition()));
+        }
+        let prev_lev = self.expr_lev;
+        self.expr_lev = -1;
+        let mut init = None;
+        if self.tok() != TokenTypes::Semicolon {
+            init = Some(self.simple_stmt(Mode::Basic)?.0);
+        }
+        let cond = if self.tok() == TokenTypes::Semicolon {
+            self.next();
+            if self.tok() == TokenTypes::LBrace {
+                self.missing_condition()
+            } else {
+                let (stmt, _) = self.simple_stmt(Mode::Basic)?;
+                self.stmt_expr(stmt, "assignment")
+            }
+        } else {
+            let stmt = init
+                .take()
+                .expect("an if header without a semicolon has a condition");
+            self.stmt_expr(stmt, "assignment")
+        };
+        self.expr_lev = prev_lev;
+        return Ok((init, cond));
+    }
+
+    // Reports the missing condition before the body of an if statement and returns a
+    // BadExpr in its place.
+    fn missing_condition(&mut self) -> Expr {
+        let span = self.token().span;
+        self.report(
+            Diagnostic::error("Missing condition in if statement.")
+                .with_code(codes::UNEXPECTED_TOKEN)
+                .with_primary(span, ""),
+        );
+        return Expr::Bad(BadExpr {
+            span: Span::new(self.file, span.start, span.start, span.line, span.column),
+        });
+    }
+
+    fn switch_stmt(&mut self) -> PResult<Stmt> {
+        let start = self.expect(TokenTypes::Switch)?;
+        let prev_lev = self.expr_lev;
+        self.expr_lev = -1;
+        let (mut init, mut tag) = (None, None);
+        if self.tok() != TokenTypes::LBrace {
+            if self.tok() != TokenTypes::Semicolon {
+                tag = Some(self.simple_stmt(Mode::Basic)?.0);
+            }
+            if self.tok() == TokenTypes::Semicolon {
+                self.next();
+                init = tag.take();
+                if self.tok() != TokenTypes::LBrace {
+                    tag = Some(self.simple_stmt(Mode::Basic)?.0);
+                }
+            }
+        }
+        self.expr_lev = prev_lev;
+
+        let type_switch = tag.as_ref().is_some_and(is_type_switch_guard);
+        self.expect(TokenTypes::LBrace)?;
+        let mut body = Vec::new();
+        while matches!(self.tok(), TokenTypes::Case | TokenTypes::Default) {
+            body.push(self.case_clause()?);
+        }
+        self.expect(TokenTypes::RBrace)?;
+        let span = self.span_from(start);
+        if type_switch {
+            return Ok(Stmt::TypeSwitch(Box::new(TypeSwitchStmt {
+                init,
+                assign: tag.expect("a type switch has a guard"),
+                body,
+                span,
+            })));
+        }
+        let tag = tag.map(|stmt| self.stmt_expr(stmt, "assignment"));
+        return Ok(Stmt::Switch(Box::new(SwitchStmt {
+            init,
+            tag,
+            body,
+            span,
+        })));
+    }
+
+    fn case_clause(&mut self) -> PResult<CaseClause> {
+        let start = self.token().span;
+        let mut list = Vec::new();
+        if self.got(TokenTypes::Case) {
+            list = self.expr_list()?;
+        } else {
+            self.expect(TokenTypes::Default)?;
+        }
+        self.expect(TokenTypes::Colon)?;
+        let body = self.stmt_list();
+        return Ok(CaseClause {
+            list,
+            body,
+            span: self.span_from(start),
+        });
+    }
+
+    fn select_stmt(&mut self) -> PResult<SelectStmt> {
+        let start = self.expect(TokenTypes::Select)?;
+        self.expect(TokenTypes::LBrace)?;
+        let mut body = Vec::new();
+        while matches!(self.tok(), TokenTypes::Case | TokenTypes::Default) {
+            body.push(self.comm_clause()?);
+        }
+        self.expect(TokenTypes::RBrace)?;
+        return Ok(SelectStmt {
+            body,
+            span: self.span_from(start),
+        });
+    }
+
+    fn comm_clause(&mut self) -> PResult<CommClause> {
+        let start = self.token().span;
+        let mut comm = None;
+        if self.got(TokenTypes::Case) {
+            comm = Some(self.simple_stmt(Mode::Basic)?.0);
+        } else {
+            self.expect(TokenTypes::Default)?;
+        }
+        self.expect(TokenTypes::Colon)?;
+        let body = self.stmt_list();
+        return Ok(CommClause {
+            comm,
+            body,
+            span: self.span_from(start),
+        });
+    }
+
+    fn for_stmt(&mut self) -> PResult<Stmt> {
+        let start = self.expect(TokenTypes::For)?;
+        let prev_lev = self.expr_lev;
+        self.expr_lev = -1;
+        let (mut init, mut cond, mut post) = (None, None, None);
+        let mut is_range = false;
+        if self.tok() != TokenTypes::LBrace {
+            if self.tok() == TokenTypes::Range {
+                // `for range x` has neither key nor value.
+                let range = self.next();
+                let x = self.expr()?;
+                cond = Some(Stmt::Assign(Box::new(AssignStmt {
+                    lhs: Vec::new(),
+                    tok: TokenTypes::Range,
+                    rhs: vec![x],
+                    span: self.span_from(range),
+                })));
+                is_range = true;
+            } else if self.tok() != TokenTypes::Semicolon {
+                let (stmt, range) = self.simple_stmt(Mode::RangeOk)?;
+                cond = Some(stmt);
+                is_range = range;
+            }
+            if !is_range && self.tok() == TokenTypes::Semicolon {
+                self.next();
+                init = cond.take();
+                if self.tok() != TokenTypes::Semicolon {
+                    cond = Some(self.simple_stmt(Mode::Basic)?.0);
+                }
+                self.expect(TokenTypes::Semicolon)?;
+                if self.tok() != TokenTypes::LBrace {
+                    post = Some(self.simple_stmt(Mode::Basic)?.0);
+                }
+            }
+        }
+        self.expr_lev = prev_lev;
+        let body = self.block()?;
+        let span = self.span_from(start);
+
+        if is_range {
+            let Some(Stmt::Assign(assign)) = cond else {
+                unreachable!("a range clause is parsed as an assignment");
+            };
+            let AssignStmt { lhs, tok, rhs, .. } = *assign;
+            let mut lhs = lhs.into_iter();
+            let key = lhs.next();
+            let value = lhs.next();
+            if let Some(extra) = lhs.next() {
+                self.report(
+                    Diagnostic::error("Range clause permits at most two iteration variables.")
+                        .with_code(codes::UNEXPECTED_TOKEN)
+                        .with_primary(extra.span(), ""),
+                );
+            }
+            let tok = if tok == TokenTypes::Range {
+                None
+            } else {
+                Some(tok)
+            };
+            return Ok(Stmt::Range(Box::new(RangeStmt {
+                key,
+                value,
+                tok,
+                x: rhs
+                    .into_iter()
+                    .next()
+                    .expect("a range clause has an operand"),
+                body,
+                span,
+            })));
+        }
+        let cond = cond.map(|stmt| self.stmt_expr(stmt, "assignment"));
+        return Ok(Stmt::For(Box::new(ForStmt {
+            init,
+            cond,
+            post,
+            body,
+            span,
+        })));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Basic,
+    LabelOk,
+    RangeOk,
+}
+
+// Whether `tok` is an identifier. The lexer gives the predeclared `true`, `false` and `nil`
+// tokens of their own, but they are ordinary names that can be declared again.
+fn is_ident(tok: TokenTypes) -> bool {
+    return matches!(
+        tok,
+        TokenTypes::Identifier | TokenTypes::True | TokenTypes::False | TokenTypes::Nil
+    );
+}
+
+// Applies a `<-` to the channel type after it. In `<-chan<- chan T` the arrow after the
+// first `chan` moves on to the next one, giving `<-chan (<-chan T)`.
+fn recv_chan(chan: &mut ChanType) -> PResult<()> {
+    match chan.dir {
+        ChanDir::Both => {
+            chan.dir = ChanDir::Recv;
+            return Ok(());
+        }
+        ChanDir::Send => {
+            chan.dir = ChanDir::Recv;
+            if let Expr::ChanType(inner) = &mut chan.value {
+                return recv_chan(inner);
+            }
+        }
+        ChanDir::Recv => {}
+    }
+    return Err(Diagnostic::error("Expected channel type.")
+        .with_code(codes::UNEXPECTED_TOKEN)
+        .with_primary(chan.value.span(), ""));
+}
+
+fn param_field(names: Vec<Ident>, ty: Expr) -> Field {
+    let span = names
+        .first()
+        .map_or(ty.span(), |name| name.span.to(ty.span()));
+    return Field {
+        names,
+        ty,
+        tag: None,
+        span,
+    };
+}
+
+// Whether `x` is a type name, possibly qualified and instantiated.
+fn is_type_name(x: &Expr) -> bool {
+    match x {
+        Expr::Ident(_) => return true,
+        Expr::Selector(s) => return matches!(s.x, Expr::Ident(_)),
+        Expr::Index(i) => return is_type_name(&i.x),
+        Expr::IndexList(i) => return is_type_name(&i.x),
+        _ => return false,
+    }
+}
+
+// Whether `x` can only be a type element and not a value, such as `[]int` or `~int`.
+fn is_type_elem(x: &Expr) -> bool {
+    match x {
+        Expr::ArrayType(_)
+        | Expr::StructType(_)
+        | Expr::FuncType(_)
+        | Expr::InterfaceType(_)
+        | Expr::MapType(_)
+        | Expr::ChanType(_) => return true,
+        Expr::Binary(b) => return is_type_elem(&b.x) || is_type_elem(&b.y),
+        Expr::Unary(u) => return u.op == TokenTypes::Tilde,
+        Expr::Paren(p) => return is_type_elem(&p.x),
+        _ => return false,
+    }
+}
+
+// Whether `x` may be the type of a composite literal.
+fn is_literal_type(x: &Expr) -> bool {
+    match x {
+        Expr::ArrayType(_) | Expr::StructType(_) | Expr::MapType(_) => return true,
+        x => return is_type_name(x),
+    }
+}
+
+fn is_type_switch_guard(stmt: &Stmt) -> bool {
+    let x = match stmt {
+        Stmt::Expr(x) => x,
+        Stmt::Assign(assign)
+            if assign.tok == TokenTypes::Define
+                && assign.lhs.len() == 1
+                && assign.rhs.len() == 1 =>
+        {
+            &assign.rhs[0]
+        }
+        _ => return false,
+    };
+    return matches!(x, Expr::TypeAssert(assert) if assert.ty.is_none());
+}
+
+// The text of a raw string literal or comment without carriage returns, as go/scanner
+// keeps it, so that files with CRLF line endings give the same tree. In a /*-style comment
+// the `\r` in `*\r/` is kept, since removing it would end the comment early.
+fn strip_cr(lexeme: &[u8], comment: bool) -> String {
+    let mut text = Vec::with_capacity(lexeme.len());
+    for (i, &c) in lexeme.iter().enumerate() {
+        let ends_comment = comment
+            && text.len() > 2
+            && text.last() == Some(&b'*')
+            && lexeme.get(i + 1) == Some(&b'/');
+        if c != b'\r' || ends_comment {
+            text.push(c);
+        }
+    }
+    return String::from_utf8_lossy(&text).into_owned();
+}
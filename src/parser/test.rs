@@ -0,0 +1,1003 @@
+use crate::ast::*;
+use crate::diagnostic::codes;
+use crate::intern::Interner;
+use crate::lexer::Lexer;
+use crate::parser::{Parser, MAX_NEST_LEV, STACK_SIZE};
+use crate::source::{FileId, Span};
+use crate::token::TokenTypes;
+use std::fs;
+use std::thread;
+
+fn parse(src: &[u8]) -> (File, Interner) {
+    let mut lexer = Lexer::new(src);
+    let mut parser = Parser::new(&mut lexer);
+    let file = parser.parse_file();
+    assert!(!parser.has_errors(), "{:?}", parser.diagnostics());
+    return (file, parser.take_interner());
+}
+
+// The body of the only function in `src`.
+fn parse_body(body: &str) -> (Vec<Stmt>, Interner) {
+    let src = format!("package p\n\nfunc f() {{\n{}\n}}\n", body);
+    let (mut file, interner) = parse(src.as_bytes());
+    let Some(Decl::Func(func)) = file.decls.pop() else {
+        panic!("expected a function");
+    };
+    return (func.body.unwrap().stmts, interner);
+}
+
+// Writes an expression fully parenthesized, to check how it was grouped.
+fn show(x: &Expr, interner: &Interner) -> String {
+    match x {
+        Expr::Ident(id) => return interner.resolve(id.name).to_string(),
+        Expr::BasicLit(lit) => return lit.value.clone(),
+        Expr::Paren(p) => return show(&p.x, interner),
+        Expr::Selector(s) => {
+            return format!("{}.{}", show(&s.x, interner), interner.resolve(s.sel.name))
+        }
+        Expr::Index(i) => return format!("{}[{}]", show(&i.x, interner), show(&i.index, interner)),
+        Expr::IndexList(i) => {
+            let indices: Vec<String> = i.indices.iter().map(|x| show(x, interner)).collect();
+            return format!("{}[{}]", show(&i.x, interner), indices.join(", "));
+        }
+        Expr::Call(c) => {
+            let args: Vec<String> = c.args.iter().map(|a| show(a, interner)).collect();
+            return format!("{}({})", show(&c.fun, interner), args.join(", "));
+        }
+        Expr::Star(s) => return format!("(*{})", show(&s.x, interner)),
+        Expr::Unary(u) => return format!("({}{})", u.op.go_name(), show(&u.x, interner)),
+        Expr::Binary(b) => {
+            return format!(
+                "({} {} {})",
+                show(&b.x, interner),
+                b.op.go_name(),
+                show(&b.y, interner)
+            )
+        }
+        Expr::CompositeLit(c) => {
+            let ty = c.ty.as_ref().map_or(String::new(), |t| show(t, interner));
+            let elts: Vec<String> = c.elts.iter().map(|e| show(e, interner)).collect();
+            return format!("{}{{{}}}", ty, elts.join(", "));
+        }
+        Expr::KeyValue(kv) => {
+            return format!("{}: {}", show(&kv.key, interner), show(&kv.value, interner))
+        }
+        Expr::ArrayType(a) => match &a.len {
+            Some(len) => return format!("[{}]{}", show(len, interner), show(&a.elt, interner)),
+            None => return format!("[]{}", show(&a.elt, interner)),
+        },
+        Expr::MapType(m) => {
+            return format!(
+                "map[{}]{}",
+                show(&m.key, interner),
+                show(&m.value, interner)
+            )
+        }
+        Expr::ChanType(c) => {
+            let dir = match c.dir {
+                ChanDir::Both => "chan ",
+                ChanDir::Send => "chan<- ",
+                ChanDir::Recv => "<-chan ",
+            };
+            return format!("{}{}", dir, show(&c.value, interner));
+        }
+        Expr::Ellipsis(e) => match &e.elt {
+            Some(elt) => return format!("...{}", show(elt, interner)),
+            None => return "...".to_string(),
+        },
+        other => return format!("<{:?}>", std::mem::discriminant(other)),
+    }
+}
+
+fn parse_expr(src: &str) -> String {
+    let mut lexer = Lexer::new(src.as_bytes());
+    let mut parser = Parser::new(&mut lexer);
+    let x = parser.parse_expr();
+    assert!(!parser.has_errors(), "{:?}", parser.diagnostics());
+    return show(&x.unwrap(), parser.interner());
+}
+
+// =====
+// Files
+// =====
+
+#[test]
+fn parse_test_files() {
+    for (path, decls) in [
+        ("src/test_files/implements.go", 3),
+        ("src/test_files/hugeparams.go", 5),
+        ("src/test_files/generics.go", 8),
+    ] {
+        let src = fs::read(path).unwrap();
+        let (file, interner) = parse(&src);
+        assert_eq!(interner.resolve(file.name.name), "main");
+        assert_eq!(file.decls.len(), decls, "{}", path);
+        assert_eq!(file.span.end, src.len());
+    }
+}
+
+#[test]
+fn parse_imports() {
+    let (file, interner) = parse(
+        b"package p\nimport \"fmt\"\nimport (\n\tstr \"strings\"\n\t. \"math\"\n\t_ `embed`\n)\n",
+    );
+    let imports: Vec<(Option<&str>, &str)> = file
+        .imports()
+        .map(|i| {
+            (
+                i.name.map(|n| interner.resolve(n.name)),
+                i.path.value.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        imports,
+        vec![
+            (None, "\"fmt\""),
+            (Some("str"), "\"strings\""),
+            (Some("."), "\"math\""),
+            (Some("_"), "`embed`"),
+        ]
+    );
+}
+
+// As in go/scanner, raw strings and comments lose their carriage returns, except for one
+// that keeps `*\r/` from closing a comment.
+#[test]
+fn strip_carriage_returns() {
+    let src = b"package p\r\n\r\n// a\r\n/* b\r\n*\r/ */\r\nvar x = `c\r\nd`\r\n";
+    let mut lexer = Lexer::new(src).with_comments(true);
+    let mut parser = Parser::new(&mut lexer);
+    let file = parser.parse_file();
+    assert!(!parser.has_errors(), "{:?}", parser.diagnostics());
+    let comments: Vec<&str> = file.comments.iter().map(|c| c.text.as_str()).collect();
+    assert_eq!(comments, ["// a", "/* b\n*\r/ */"]);
+    let Decl::Gen(var) = &file.decls[0] else {
+        panic!("expected var");
+    };
+    let Spec::Value(x) = &var.specs[0] else {
+        panic!("expected value spec");
+    };
+    let Expr::BasicLit(lit) = &x.values[0] else {
+        panic!("expected literal");
+    };
+    assert_eq!(lit.value, "`c\nd`");
+}
+
+// `true`, `false` and `nil` are predeclared names, not keywords, and can be declared again.
+#[test]
+fn predeclared_names_can_be_redeclared() {
+    let (file, _) = parse(
+        b"package p
+import nil \"fmt\"
+type T[true any] struct{ false int }
+type I interface{ nil() }
+func true(nil int) {
+\t_ = x.nil
+nil:
+\tgoto nil
+}
+",
+    );
+    assert_eq!(file.decls.len(), 4);
+}
+
+#[test]
+fn parse_declarations() {
+    let src = b"package p
+const (
+\tA, B = iota, 1
+\tC
+)
+var x, y int = 1, 2
+type (
+\tT struct {
+\t\tio.Reader
+\t\t*Embedded
+\t\ta, b int `json:\"a\"`
+\t\tf func(int) (string, error)
+\t}
+\tAlias = map[string][]chan<- int
+\tI interface {
+\t\tfmt.Stringer
+\t\tM(a, b int, c ...string) bool
+\t}
+)
+func (t *T) Method(x int) {}
+func external()
+";
+    let (file, _) = parse(src);
+    assert_eq!(file.decls.len(), 5);
+
+    let Decl::Gen(consts) = &file.decls[0] else {
+        panic!("expected const");
+    };
+    assert_eq!(consts.tok, TokenTypes::Const);
+    let Spec::Value(c) = &consts.specs[1] else {
+        panic!("expected value spec");
+    };
+    assert!(c.ty.is_none() && c.values.is_empty());
+
+    let Decl::Gen(types) = &file.decls[2] else {
+        panic!("expected type");
+    };
+    let Spec::Type(t) = &types.specs[0] else {
+        panic!("expected type spec");
+    };
+    let Expr::StructType(st) = &t.ty else {
+        panic!("expected struct");
+    };
+    let names: Vec<usize> = st.fields.fields.iter().map(|f| f.names.len()).collect();
+    assert_eq!(names, vec![0, 0, 2, 1]);
+    assert!(st.fields.fields[2].tag.is_some());
+    let Spec::Type(alias) = &types.specs[1] else {
+        panic!("expected type spec");
+    };
+    assert!(alias.assign.is_some());
+    let Spec::Type(i) = &types.specs[2] else {
+        panic!("expected type spec");
+    };
+    let Expr::InterfaceType(it) = &i.ty else {
+        panic!("expected interface");
+    };
+    let Expr::FuncType(m) = &it.methods.fields[1].ty else {
+        panic!("expected method");
+    };
+    let params: Vec<usize> = m.params.fields.iter().map(|f| f.names.len()).collect();
+    assert_eq!(params, vec![2, 1]);
+    assert!(matches!(m.params.fields[1].ty, Expr::Ellipsis(_)));
+    assert!(m.results.as_ref().unwrap().opening.is_none());
+
+    let Decl::Func(method) = &file.decls[3] else {
+        panic!("expected func");
+    };
+    assert!(method.recv.is_some());
+    let Decl::Func(external) = &file.decls[4] else {
+        panic!("expected func");
+    };
+    assert!(external.body.is_none());
+}
+
+#[test]
+fn parse_unnamed_parameters() {
+    let (file, _) = parse(b"package p\nfunc f(int, []string, ...any) (x, y int) { return }\n");
+    let Decl::Func(f) = &file.decls[0] else {
+        panic!("expected func");
+    };
+    let params: Vec<usize> = f.ty.params.fields.iter().map(|f| f.names.len()).collect();
+    assert_eq!(params, vec![0, 0, 0]);
+    let results = f.ty.results.as_ref().unwrap();
+    assert_eq!(results.fields[0].names.len(), 2);
+}
+
+// The type parameters of a generic function or type as names and constraints.
+fn type_params(list: Option<&FieldList>, interner: &Interner) -> Vec<(Vec<String>, String)> {
+    return list
+        .unwrap()
+        .fields
+        .iter()
+        .map(|f| {
+            let names = f.names.iter().map(|n| interner.resolve(n.name).to_string());
+            return (names.collect(), show(&f.ty, interner));
+        })
+        .collect();
+}
+
+fn params(names: &[&str], constraint: &str) -> (Vec<String>, String) {
+    return (
+        names.iter().map(|n| n.to_string()).collect(),
+        constraint.to_string(),
+    );
+}
+
+#[test]
+fn parse_type_parameters() {
+    let (file, interner) = parse(
+        b"package p
+func Map[T, U any](xs []T, f func(T) U) []U { return nil }
+func Sum[S ~[]E, E int | ~float64](xs S) E { return 0 }
+func (l *List[T]) Push(v T) {}
+",
+    );
+    let funcs: Vec<&FuncDecl> = file
+        .decls
+        .iter()
+        .map(|d| match d {
+            Decl::Func(f) => return f.as_ref(),
+            _ => panic!("expected func"),
+        })
+        .collect();
+    assert_eq!(
+        type_params(funcs[0].ty.type_params.as_ref(), &interner),
+        vec![params(&["T", "U"], "any")]
+    );
+    assert_eq!(
+        type_params(funcs[1].ty.type_params.as_ref(), &interner),
+        vec![
+            params(&["S"], "(~[]E)"),
+            params(&["E"], "(int | (~float64))")
+        ]
+    );
+    assert!(funcs[2].ty.type_params.is_none());
+    let recv = &funcs[2].recv.as_ref().unwrap().fields[0];
+    assert_eq!(show(&recv.ty, &interner), "(*List[T])");
+}
+
+#[test]
+fn type_parameters_or_array_lengths() {
+    // Which declarations go/parser reads as generic types and which as arrays.
+    for (decl, generic) in [
+        ("type T [N]int", false),
+        ("type T [N * 2]int", false),
+        ("type T [P * C]int", false),
+        ("type T [P (C)]int", false),
+        ("type T[P any] int", true),
+        ("type T[P *C,] int", true),
+        ("type T[P *[]int] int", true),
+        ("type T[P (~int)] int", true),
+        ("type T[P ~int | string] int", true),
+        ("type T[P []int] int", true),
+        ("type T[K comparable, V any] map[K]V", true),
+    ] {
+        let (file, _) = parse(
+            format!(
+                "package p
+{}
+",
+                decl
+            )
+            .as_bytes(),
+        );
+        let Decl::Gen(gen) = &file.decls[0] else {
+            panic!("expected type");
+        };
+        let Spec::Type(spec) = &gen.specs[0] else {
+            panic!("expected type spec");
+        };
+        assert_eq!(spec.type_params.is_some(), generic, "{}", decl);
+        assert_eq!(matches!(spec.ty, Expr::ArrayType(_)), !generic, "{}", decl);
+    }
+
+    let (file, interner) = parse(
+        b"package p
+type T[P *C,] int
+",
+    );
+    let Decl::Gen(gen) = &file.decls[0] else {
+        panic!("expected type");
+    };
+    let Spec::Type(spec) = &gen.specs[0] else {
+        panic!("expected type spec");
+    };
+    let list = spec.type_params.as_ref().unwrap();
+    assert_eq!(
+        type_params(Some(list), &interner),
+        vec![params(&["P"], "(*C)")]
+    );
+    let star = list.fields[0].ty.span();
+    assert_eq!((star.start, star.end), (19, 21));
+}
+
+#[test]
+fn constraint_interfaces() {
+    let (file, interner) = parse(
+        b"package p
+type Number interface {
+	~int | ~int64 | float64
+	fmt.Stringer
+	String() string
+}
+",
+    );
+    let Decl::Gen(gen) = &file.decls[0] else {
+        panic!("expected type");
+    };
+    let Spec::Type(spec) = &gen.specs[0] else {
+        panic!("expected type spec");
+    };
+    let Expr::InterfaceType(it) = &spec.ty else {
+        panic!("expected interface");
+    };
+    let elems: Vec<String> = it
+        .methods
+        .fields
+        .iter()
+        .map(|f| show(&f.ty, &interner))
+        .collect();
+    assert_eq!(
+        &elems[..2],
+        &["(((~int) | (~int64)) | float64)", "fmt.Stringer"]
+    );
+    assert_eq!(it.methods.fields[2].names.len(), 1);
+}
+
+#[test]
+fn generic_instantiations() {
+    assert_eq!(
+        parse_expr("Map[int, string](xs, f)"),
+        "Map[int, string](xs, f)"
+    );
+    assert_eq!(parse_expr("Map[int,](xs)"), "Map[int](xs)");
+    assert_eq!(parse_expr("List[int]{1}"), "List[int]{1}");
+    assert_eq!(parse_expr("pkg.Pair[K, []V]{}"), "pkg.Pair[K, []V]{}");
+    assert_eq!(parse_expr("m[k]"), "m[k]");
+
+    let (file, interner) = parse(
+        b"package p
+func f(a []int, b [2]T, l List[int], m pkg.Map[K, V]) {}
+func g(List[string], []int) {}
+type S struct {
+\tList[int]
+\ta [2]int
+\t*pkg.Set[T]
+}
+",
+    );
+    let fields = |list: &FieldList| -> Vec<(usize, String)> {
+        return list
+            .fields
+            .iter()
+            .map(|f| (f.names.len(), show(&f.ty, &interner)))
+            .collect();
+    };
+    let field = |names: usize, ty: &str| (names, ty.to_string());
+    let Decl::Func(f) = &file.decls[0] else {
+        panic!("expected func");
+    };
+    assert_eq!(
+        fields(&f.ty.params),
+        vec![
+            field(1, "[]int"),
+            field(1, "[2]T"),
+            field(1, "List[int]"),
+            field(1, "pkg.Map[K, V]"),
+        ]
+    );
+    let Decl::Func(g) = &file.decls[1] else {
+        panic!("expected func");
+    };
+    assert_eq!(
+        fields(&g.ty.params),
+        vec![field(0, "List[string]"), field(0, "[]int")]
+    );
+    let Decl::Gen(gen) = &file.decls[2] else {
+        panic!("expected type");
+    };
+    let Spec::Type(spec) = &gen.specs[0] else {
+        panic!("expected type spec");
+    };
+    let Expr::StructType(st) = &spec.ty else {
+        panic!("expected struct");
+    };
+    assert_eq!(
+        fields(&st.fields),
+        vec![
+            field(0, "List[int]"),
+            field(1, "[2]int"),
+            field(0, "(*pkg.Set[T])"),
+        ]
+    );
+}
+
+// =====
+// Expressions
+// =====
+
+#[test]
+fn binary_operator_precedence() {
+    assert_eq!(parse_expr("a + b * c"), "(a + (b * c))");
+    assert_eq!(parse_expr("a * b + c"), "((a * b) + c)");
+    assert_eq!(parse_expr("a - b - c"), "((a - b) - c)");
+    assert_eq!(
+        parse_expr("a || b && c == d + e * f"),
+        "(a || (b && (c == (d + (e * f)))))"
+    );
+    assert_eq!(parse_expr("x << 1 | y &^ z"), "((x << 1) | (y &^ z))");
+    assert_eq!(parse_expr("-a * !b"), "((-a) * (!b))");
+    assert_eq!(parse_expr("*p.x + <-ch"), "((*p.x) + (<-ch))");
+    assert_eq!(parse_expr("(a + b) * c"), "((a + b) * c)");
+}
+
+#[test]
+fn primary_expressions() {
+    assert_eq!(parse_expr("a.b.c(d, e[f])"), "a.b.c(d, e[f])");
+    assert_eq!(parse_expr("f(g(x))[0].y"), "f(g(x))[0].y");
+    assert_eq!(parse_expr("[]int{1, 2, 3}"), "[]int{1, 2, 3}");
+    assert_eq!(
+        parse_expr("map[string][]T{\"a\": {x}, \"b\": nil}"),
+        "map[string][]T{\"a\": {x}, \"b\": nil}"
+    );
+    assert_eq!(parse_expr("[...]int{1}"), "[...]int{1}");
+    assert_eq!(parse_expr("make(chan<- int, n)"), "make(chan<- int, n)");
+    // As in the spec, this receives from a conversion.
+    assert_eq!(parse_expr("<-chan int(nil)"), "(<-chan int(nil))");
+    assert_eq!(parse_expr("<-chan <-chan int"), "<-chan <-chan int");
+    assert_eq!(parse_expr("(*T)(p)"), "(*T)(p)");
+}
+
+#[test]
+fn slices_and_type_assertions() {
+    let mut lexer = Lexer::new(b"s[a:b:c][:n][i:].(T)");
+    let mut parser = Parser::new(&mut lexer);
+    let Some(Expr::TypeAssert(assert)) = parser.parse_expr() else {
+        panic!("expected a type assertion");
+    };
+    let Expr::Slice(open) = &assert.x else {
+        panic!("expected a slice");
+    };
+    assert!(open.low.is_some() && open.high.is_none() && !open.slice3);
+    let Expr::Slice(low) = &open.x else {
+        panic!("expected a slice");
+    };
+    assert!(low.low.is_none() && low.high.is_some());
+    let Expr::Slice(full) = &low.x else {
+        panic!("expected a slice");
+    };
+    assert!(full.slice3 && full.max.is_some());
+}
+
+#[test]
+fn composite_literals_in_control_clauses() {
+    let (stmts, _) =
+        parse_body("if x == (T{}) {\n}\nfor _, v := range []T{a} {\n}\nif v := f(T{1}); v {\n}");
+    assert_eq!(stmts.len(), 3);
+    assert!(matches!(stmts[0], Stmt::If(_)));
+    assert!(matches!(stmts[1], Stmt::Range(_)));
+    let Stmt::If(s) = &stmts[2] else {
+        panic!("expected if");
+    };
+    assert!(s.init.is_some());
+}
+
+#[test]
+fn function_literals() {
+    let (stmts, interner) =
+        parse_body("f := func(x int) bool { return x > 0 }\ngo func() {}()\ndefer g()");
+    let Stmt::Assign(assign) = &stmts[0] else {
+        panic!("expected assignment");
+    };
+    assert!(matches!(assign.rhs[0], Expr::FuncLit(_)));
+    assert!(matches!(stmts[1], Stmt::Go(_)));
+    let Stmt::Defer(defer) = &stmts[2] else {
+        panic!("expected defer");
+    };
+    assert_eq!(show(&defer.call, &interner), "g()");
+}
+
+// =====
+// Statements
+// =====
+
+#[test]
+fn simple_statements() {
+    let (stmts, _) = parse_body("x := 1\nx, y = y, x\nx += 2\nx++\nch <- x\nf()\n;");
+    let kinds: Vec<&str> = stmts
+        .iter()
+        .map(|s| match s {
+            Stmt::Assign(a) if a.tok == TokenTypes::Define => "define",
+            Stmt::Assign(a) if a.tok == TokenTypes::Assign => "assign",
+            Stmt::Assign(_) => "op-assign",
+            Stmt::IncDec(_) => "incdec",
+            Stmt::Send(_) => "send",
+            Stmt::Expr(_) => "expr",
+            Stmt::Empty(e) if !e.implicit => "empty",
+            _ => "other",
+        })
+        .collect();
+    assert_eq!(
+        kinds,
+        vec![
+            "define",
+            "assign",
+            "op-assign",
+            "incdec",
+            "send",
+            "expr",
+            "empty"
+        ]
+    );
+}
+
+#[test]
+fn control_statements() {
+    let body = "for {\n}
+for i := 0; i < n; i++ {\n\tcontinue\n}
+for x < 10 {\n\tbreak\n}
+for range ch {\n}
+for k := range m {\n}
+switch x := f(); x {\ncase 1, 2:\n\tfallthrough\ndefault:\n}
+switch v := x.(type) {\ncase int, *T:\ncase nil:\n}
+select {\ncase v := <-ch:\n\t_ = v\ncase ch <- 1:\ndefault:\n}
+outer:\n\tfor {\n\t\tgoto outer\n\t}
+if a {\n} else if b {\n} else {\n}";
+    let (stmts, _) = parse_body(body);
+    assert_eq!(stmts.len(), 10);
+    let Stmt::For(three) = &stmts[1] else {
+        panic!("expected for");
+    };
+    assert!(three.init.is_some() && three.cond.is_some() && three.post.is_some());
+    let Stmt::For(cond) = &stmts[2] else {
+        panic!("expected for");
+    };
+    assert!(cond.init.is_none() && cond.cond.is_some());
+    let Stmt::Range(bare) = &stmts[3] else {
+        panic!("expected range");
+    };
+    assert!(bare.key.is_none() && bare.tok.is_none());
+    let Stmt::Range(keyed) = &stmts[4] else {
+        panic!("expected range");
+    };
+    assert!(keyed.key.is_some() && keyed.value.is_none());
+    assert_eq!(keyed.tok, Some(TokenTypes::Define));
+    let Stmt::Switch(switch) = &stmts[5] else {
+        panic!("expected switch");
+    };
+    assert!(switch.init.is_some() && switch.tag.is_some());
+    assert_eq!(switch.body[0].list.len(), 2);
+    assert!(switch.body[1].list.is_empty());
+    let Stmt::TypeSwitch(type_switch) = &stmts[6] else {
+        panic!("expected type switch");
+    };
+    assert_eq!(type_switch.body.len(), 2);
+    let Stmt::Select(select) = &stmts[7] else {
+        panic!("expected select");
+    };
+    assert_eq!(select.body.len(), 3);
+    assert!(select.body[2].comm.is_none());
+    let Stmt::Labeled(labeled) = &stmts[8] else {
+        panic!("expected label");
+    };
+    assert!(matches!(labeled.stmt, Stmt::For(_)));
+    let Stmt::If(if_stmt) = &stmts[9] else {
+        panic!("expected if");
+    };
+    let Some(Stmt::If(else_if)) = &if_stmt.els else {
+        panic!("expected else if");
+    };
+    assert!(matches!(else_if.els, Some(Stmt::Block(_))));
+}
+
+#[test]
+fn nodes_carry_positions() {
+    let src = b"package p\n\nfunc f() {\n\tx := a + b*c\n}\n";
+    let (file, _) = parse(src);
+    assert_eq!(file.package, Span::new(file.package.file, 0, 7, 1, 1));
+    let Decl::Func(f) = &file.decls[0] else {
+        panic!("expected func");
+    };
+    assert_eq!((f.span.start, f.span.end, f.span.line), (11, 37, 3));
+    let Stmt::Assign(assign) = &f.body.as_ref().unwrap().stmts[0] else {
+        panic!("expected assignment");
+    };
+    assert_eq!((assign.span.line, assign.span.column), (4, 2));
+    let Expr::Binary(sum) = &assign.rhs[0] else {
+        panic!("expected binary");
+    };
+    assert_eq!(&src[sum.span.start..sum.span.end], b"a + b*c");
+    let Expr::Binary(product) = &sum.y else {
+        panic!("expected binary");
+    };
+    assert_eq!(&src[product.span.start..product.span.end], b"b*c");
+    assert_eq!(product.span.column, 11);
+}
+
+// =====
+// Errors
+// =====
+
+fn first_error(src: &str) -> String {
+    let mut lexer = Lexer::new(src.as_bytes());
+    let mut parser = Parser::new(&mut lexer);
+    parser.parse_file();
+    let diagnostic = &parser.diagnostics()[0];
+    return diagnostic.message.clone();
+}
+
+#[test]
+fn report_syntax_errors() {
+    assert_eq!(
+        first_error("func f() {}"),
+        "Expected 'package', found 'func'."
+    );
+    assert_eq!(
+        first_error("package p\nfunc f() {\n\tx := \n}"),
+        "Expected expression, found '}'."
+    );
+    assert_eq!(
+        first_error("package p\nfunc f() {\n\tf(x\n}"),
+        "Missing ',' before newline in argument list."
+    );
+    assert_eq!(
+        first_error("package p\nvar x = []int{\n\t1\n}"),
+        "Missing ',' before newline in composite literal."
+    );
+    assert_eq!(
+        first_error("package p\nfunc f() {}\nimport \"fmt\""),
+        "Imports must appear before other declarations."
+    );
+    assert_eq!(
+        first_error("package p\nfunc f() {\n\tgo x\n}"),
+        "Expression in go must be a function call."
+    );
+    assert_eq!(
+        first_error("package p\nfunc f() {\n\tif {\n\t}\n}"),
+        "Missing condition in if statement."
+    );
+    assert_eq!(
+        first_error("package p\nfunc f[]() {}"),
+        "Empty type parameter list."
+    );
+    assert_eq!(
+        first_error("package p\nfunc f[T]() {}"),
+        "Missing type constraint."
+    );
+    assert_eq!(
+        first_error("package p\nfunc f[T any, *int]() {}"),
+        "Type parameters must be named."
+    );
+    assert_eq!(
+        first_error("package p\nfunc f(a int, string) {}"),
+        "Mixed named and unnamed parameters."
+    );
+}
+
+#[test]
+fn illegal_tokens_are_reported_once() {
+    let mut lexer = Lexer::new(b"package p\nvar x = $\n");
+    let mut parser = Parser::new(&mut lexer);
+    parser.parse_file();
+    assert_eq!(parser.diagnostics().len(), 1);
+    assert_eq!(
+        parser.diagnostics()[0].code,
+        Some(codes::UNEXPECTED_CHARACTER)
+    );
+}
+
+#[test]
+fn parse_a_drained_lexer() {
+    let mut lexer = Lexer::new(b"package p\n").with_file(FileId(3));
+    lexer.by_ref().for_each(drop);
+    let mut parser = Parser::new(&mut lexer);
+    let file = parser.parse_file();
+    assert_eq!(file.span.file, FileId(3));
+    assert!(file.decls.is_empty());
+    assert_eq!(
+        parser.diagnostics()[0].message,
+        "Expected 'package', found end of file."
+    );
+}
+
+// =====
+// Recovery
+// =====
+
+// Parses `src` and returns the file with the line and message of each error.
+fn parse_with_errors(src: &str) -> (File, Vec<(usize, String)>, Interner) {
+    let mut lexer = Lexer::new(src.as_bytes());
+    let mut parser = Parser::new(&mut lexer);
+    let file = parser.parse_file();
+    let errors = parser
+        .diagnostics()
+        .iter()
+        .map(|d| (d.primary_span().unwrap().line, d.message.clone()))
+        .collect();
+    return (file, errors, parser.take_interner());
+}
+
+fn func_body(decl: &Decl) -> &[Stmt] {
+    let Decl::Func(func) = decl else {
+        panic!("expected a function, found {:?}", decl);
+    };
+    return &func.body.as_ref().unwrap().stmts;
+}
+
+#[test]
+fn recover_at_statements() {
+    let (file, errors, _) =
+        parse_with_errors("package p\nfunc f() {\n\tx = 1 1\n\ty = )\n\tz++\n}\nfunc g() {}\n");
+    assert_eq!(
+        errors,
+        vec![
+            (3, "Expected ';', found literal 1.".to_string()),
+            (4, "Expected expression, found ')'.".to_string()),
+        ]
+    );
+    assert_eq!(file.decls.len(), 2);
+    let body = func_body(&file.decls[0]);
+    assert!(matches!(
+        body,
+        [Stmt::Bad(_), Stmt::Bad(_), Stmt::IncDec(_)]
+    ));
+    let Stmt::Bad(bad) = &body[1] else {
+        unreachable!()
+    };
+    assert_eq!((bad.span.line, bad.span.column), (4, 2));
+}
+
+#[test]
+fn recover_at_declarations() {
+    let (file, errors, _) =
+        parse_with_errors("package p\nvar = 1\ntype T struct{ a int }\nfunc (x) {}\nconst c = 1\n");
+    assert_eq!(
+        errors,
+        vec![
+            (2, "Expected identifier, found '='.".to_string()),
+            (4, "Expected identifier, found '{'.".to_string()),
+        ]
+    );
+    assert!(matches!(
+        file.decls.as_slice(),
+        [Decl::Bad(_), Decl::Gen(_), Decl::Bad(_), Decl::Gen(_)]
+    ));
+}
+
+#[test]
+fn recover_from_missing_braces() {
+    let (file, errors, _) =
+        parse_with_errors("package p\nfunc f() {\n\tif x {\n\t\tg()\nfunc h() {}\n");
+    assert_eq!(errors, vec![(5, "Expected '}', found 'func'.".to_string())]);
+    assert_eq!(file.decls.len(), 2);
+    assert!(matches!(func_body(&file.decls[0]), [Stmt::If(_)]));
+}
+
+#[test]
+fn skip_blocks_of_bad_statements() {
+    let (file, errors, _) =
+        parse_with_errors("package p\nfunc f() {\n\tfor x := range {\n\t\ty()\n\t}\n\tz()\n}\n");
+    assert_eq!(
+        errors,
+        vec![(3, "Expected expression, found '{'.".to_string())]
+    );
+    assert!(matches!(
+        func_body(&file.decls[0]),
+        [Stmt::Bad(_), Stmt::Expr(_)]
+    ));
+}
+
+#[test]
+fn bad_expressions_in_statements() {
+    let (file, errors, _) =
+        parse_with_errors("package p\nfunc f() {\n\tif {\n\t}\n\tgo x\n\tif x := 1 {\n\t}\n}\n");
+    assert_eq!(
+        errors,
+        vec![
+            (3, "Missing condition in if statement.".to_string()),
+            (5, "Expression in go must be a function call.".to_string()),
+            (6, "Cannot use assignment as value.".to_string()),
+        ]
+    );
+    let body = func_body(&file.decls[0]);
+    let [Stmt::If(missing), Stmt::Go(go), Stmt::If(assign)] = body else {
+        panic!("unexpected statements {:?}", body);
+    };
+    assert!(matches!(missing.cond, Expr::Bad(_)));
+    assert!(matches!(go.call, Expr::Bad(_)));
+    assert!(matches!(assign.cond, Expr::Bad(_)));
+}
+
+#[test]
+fn one_error_per_line() {
+    let (_, errors, _) = parse_with_errors("package p\nvar x = ) + )\nvar y = ]\n");
+    assert_eq!(
+        errors,
+        vec![
+            (2, "Expected expression, found ')'.".to_string()),
+            (3, "Expected expression, found ']'.".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn recover_from_missing_package_clause() {
+    let (file, errors, interner) = parse_with_errors("func f() {}\n");
+    assert_eq!(
+        errors,
+        vec![(1, "Expected 'package', found 'func'.".to_string())]
+    );
+    assert_eq!(interner.resolve(file.name.name), "_");
+    assert!(matches!(file.decls.as_slice(), [Decl::Func(_)]));
+}
+
+#[test]
+fn recover_from_truncated_test_files() {
+    for entry in fs::read_dir("src/test_files").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "go") {
+            continue;
+        }
+        let src = fs::read(&path).unwrap();
+        for end in (0..src.len()).step_by(97) {
+            let mut lexer = Lexer::new(&src[..end]);
+            let mut parser = Parser::new(&mut lexer);
+            let file = parser.parse_file();
+            assert!(file.span.end <= end, "{}", path.display());
+        }
+    }
+}
+
+// =====
+// Nesting
+// =====
+
+// The errors of `src`, parsed on a thread with the stack the parser needs at the limit.
+fn nesting_errors(src: String) -> Vec<(usize, String)> {
+    return thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || parse_with_errors(&src).1)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+// Makes a source file with something nested `n` levels deep.
+type Nested = fn(usize) -> String;
+
+#[test]
+fn limit_nesting() {
+    let too_deep = format!(
+        "Expression or statement nested more than {} levels deep.",
+        MAX_NEST_LEV
+    );
+    let cases: [(&str, Nested, usize); 6] = [
+        (
+            "parentheses",
+            |n| format!("package p\nvar x = {}1{}", "(".repeat(n), ")".repeat(n)),
+            2,
+        ),
+        (
+            "composite literals",
+            |n| {
+                format!(
+                    "package p\nvar x = []int{}1{}",
+                    "{".repeat(n),
+                    "}".repeat(n)
+                )
+            },
+            2,
+        ),
+        (
+            "types",
+            |n| format!("package p\nvar x {}int", "*".repeat(n)),
+            2,
+        ),
+        (
+            "blocks",
+            |n| {
+                format!(
+                    "package p\nfunc f() {{\n{}{}}}",
+                    "{\n".repeat(n),
+                    "}\n".repeat(n)
+                )
+            },
+            3 + MAX_NEST_LEV as usize,
+        ),
+        (
+            "if statements",
+            |n| {
+                format!(
+                    "package p\nfunc f() {{\n{}\n}}",
+                    "if x { } else ".repeat(n) + "{}"
+                )
+            },
+            3,
+        ),
+        (
+            "binary operators",
+            |n| format!("package p\nvar x = {}", vec!["1"; n + 1].join(" + ")),
+            2,
+        ),
+    ];
+    for (name, src, line) in cases {
+        let limit = MAX_NEST_LEV as usize;
+        assert_eq!(nesting_errors(src(limit / 2)), vec![], "{}", name);
+        for n in [limit + 1, 20 * limit] {
+            assert_eq!(
+                nesting_errors(src(n)),
+                vec![(line, too_deep.clone())],
+                "{} nested {} levels deep",
+                name,
+                n
+            );
+        }
+    }
+}
@@ -0,0 +1,432 @@
+// Copyright 2009 The Go Authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be found in the
+// LICENSE-go file.
+
+// Reformats doc comments the way gofmt does, following go/doc/comment. The text of a
+// comment is split into paragraphs, headings, lists and code blocks, which are written
+// back with one blank line between blocks, `# ` before headings, a tab before each line
+// of code and the canonical indentation before list items.
+
+enum Block {
+    Paragraph(Vec<String>),
+    Heading(String),
+    Code(Vec<String>),
+    List(List),
+}
+
+struct List {
+    // The number of each item, or "" for a bullet list.
+    items: Vec<(String, Vec<Vec<String>>)>,
+    force_blank_before: bool,
+    force_blank_between: bool,
+}
+
+impl List {
+    fn blank_before(&self) -> bool {
+        return self.force_blank_before || self.blank_between();
+    }
+
+    fn blank_between(&self) -> bool {
+        return self.force_blank_between || self.items.iter().any(|(_, paras)| paras.len() != 1);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SpanKind {
+    Paragraph,
+    Heading,
+    OldHeading,
+    Code,
+    List,
+}
+
+// Returns the canonical form of a doc comment made of the comments in `list`, or None
+// when it should be left as written.
+pub fn format_doc_comment(list: &[&str]) -> Option<Vec<String>> {
+    let first = *list.first()?;
+    if list.len() == 1 && first.starts_with("/*") {
+        // A single-line or star-prefixed block comment would only get worse.
+        if !first.contains('\n') || all_stars(first) {
+            return None;
+        }
+        let text = print(&parse(&first[2..first.len() - 2]));
+        return Some(vec![format!("/*\n{}*/", text)]);
+    }
+
+    let mut text = String::new();
+    let mut directives = Vec::new();
+    for comment in list {
+        let after = comment.strip_prefix("//")?;
+        // Directives such as `//go:build` are kept apart, after the text.
+        if is_directive(after) {
+            directives.push(comment.to_string());
+            continue;
+        }
+        text.push_str(after.strip_prefix(' ').unwrap_or(after));
+        text.push('\n');
+    }
+    if text.is_empty() {
+        return None;
+    }
+
+    let mut lines: Vec<String> = print(&parse(&text))
+        .lines()
+        .map(|line| match line {
+            "" => "//".to_string(),
+            _ if line.starts_with('\t') => format!("//{}", line),
+            _ => format!("// {}", line),
+        })
+        .collect();
+    if lines.is_empty() {
+        return None;
+    }
+    if !directives.is_empty() {
+        lines.push("//".to_string());
+        lines.extend(directives);
+    }
+    return Some(lines);
+}
+
+// Whether the text of a `//` comment after the slashes is a directive such as
+// `go:generate` or `line 10`.
+pub fn is_directive(text: &str) -> bool {
+    if ["line ", "extern ", "export "]
+        .iter()
+        .any(|p| text.starts_with(p))
+    {
+        return true;
+    }
+    let bytes = text.as_bytes();
+    let colon = match text.find(':') {
+        Some(colon) if colon > 0 && colon + 1 < bytes.len() => colon,
+        _ => return false,
+    };
+    return (0..=colon + 1)
+        .filter(|&i| i != colon)
+        .all(|i| bytes[i].is_ascii_lowercase() || bytes[i].is_ascii_digit());
+}
+
+// Whether every line after the first of a block comment starts with a star.
+fn all_stars(text: &str) -> bool {
+    return text
+        .split('\n')
+        .skip(1)
+        .map(|line| line.trim_start_matches([' ', '\t']))
+        .all(|line| line.is_empty() || line.starts_with('*'));
+}
+
+// ========
+// Parsing
+// ========
+
+fn parse(text: &str) -> Vec<Block> {
+    let lines = unindent(&text.split('\n').collect::<Vec<_>>());
+    let mut blocks = Vec::new();
+    let mut prev_end = 0;
+    for (start, end, kind) in parse_spans(&lines) {
+        let span = &lines[start..end];
+        let block = match kind {
+            SpanKind::List => Block::List(parse_list(span, prev_end < start)),
+            SpanKind::Code => Block::Code(unindent(span)),
+            SpanKind::Heading => Block::Heading(span[0][1..].trim().to_string()),
+            SpanKind::OldHeading => Block::Heading(span[0].trim().to_string()),
+            SpanKind::Paragraph => Block::Paragraph(span.to_vec()),
+        };
+        blocks.push(block);
+        prev_end = end;
+    }
+    return blocks;
+}
+
+// Splits the lines of a comment into the spans of its blocks.
+fn parse_spans(lines: &[String]) -> Vec<(usize, usize, SpanKind)> {
+    let mut spans = Vec::new();
+    let mut i = 0;
+    let mut force_indent = 0;
+    loop {
+        while i < lines.len() && lines[i].is_empty() {
+            i += 1;
+        }
+        if i >= lines.len() {
+            break;
+        }
+
+        let start = i;
+        let mut end;
+        let kind;
+        if i < force_indent || indented(&lines[i]) {
+            // An indented span runs until the next unindented line. A list that was
+            // forced to be indented also takes the unindented items after it.
+            let mut unindented_list_ok = is_list(&lines[i]) && i < force_indent;
+            i += 1;
+            while i < lines.len()
+                && (lines[i].is_empty()
+                    || i < force_indent
+                    || indented(&lines[i])
+                    || (unindented_list_ok && is_list(&lines[i])))
+            {
+                if lines[i].is_empty() {
+                    unindented_list_ok = false;
+                }
+                i += 1;
+            }
+            end = i;
+            while end > start && lines[end - 1].is_empty() {
+                end -= 1;
+            }
+            // The closing brace of a pasted block of code is often not indented.
+            if end < lines.len() && lines[end].starts_with('}') {
+                end += 1;
+            }
+            kind = match is_list(&lines[start]) {
+                true => SpanKind::List,
+                false => SpanKind::Code,
+            };
+        } else {
+            // An unindented span runs until the next blank or indented line.
+            i += 1;
+            while i < lines.len() && !lines[i].is_empty() && !indented(&lines[i]) {
+                i += 1;
+            }
+            end = i;
+            // Unindented list items or a line opening a block of code that run into an
+            // indented line are left to the indented span.
+            if i < lines.len() && !lines[i].is_empty() && !is_list(&lines[i]) {
+                if is_list(&lines[i - 1]) {
+                    force_indent = end;
+                    end -= 1;
+                    while end > start && is_list(&lines[end - 1]) {
+                        end -= 1;
+                    }
+                } else if lines[i - 1].ends_with('{') || lines[i - 1].ends_with('\\') {
+                    force_indent = end;
+                    end -= 1;
+                }
+                if start == end && force_indent > start {
+                    i = start;
+                    continue;
+                }
+            }
+            kind = if end - start == 1 && is_heading(&lines[start]) {
+                SpanKind::Heading
+            } else if end - start == 1 && is_old_heading(&lines[start], lines, start) {
+                SpanKind::OldHeading
+            } else {
+                SpanKind::Paragraph
+            };
+        }
+        spans.push((start, end, kind));
+        i = end;
+    }
+    return spans;
+}
+
+fn parse_list(lines: &[String], force_blank_before: bool) -> List {
+    let numbered = !list_marker(&lines[0]).unwrap().0.is_empty();
+    let mut list = List {
+        items: Vec::new(),
+        force_blank_before,
+        force_blank_between: false,
+    };
+    let mut text: Vec<String> = Vec::new();
+    for line in lines {
+        let mut line = line.as_str();
+        if let Some((number, rest)) = list_marker(line) {
+            if number.is_empty() != numbered {
+                flush_item(&mut list, &mut text);
+                list.items.push((number.to_string(), Vec::new()));
+                line = rest;
+            }
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            list.force_blank_between = true;
+            flush_item(&mut list, &mut text);
+            continue;
+        }
+        text.push(line.to_string());
+    }
+    flush_item(&mut list, &mut text);
+    return list;
+}
+
+fn flush_item(list: &mut List, text: &mut Vec<String>) {
+    if let Some(item) = list.items.last_mut() {
+        if !text.is_empty() {
+            item.1.push(std::mem::take(text));
+        }
+    }
+    text.clear();
+}
+
+// Splits a list item into its number ("" for a bullet) and the text after the marker.
+fn list_marker(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    let (number, rest) = if let Some(rest) = line
+        .strip_prefix(['*', '+', '-'])
+        .or_else(|| line.strip_prefix('•'))
+    {
+        ("", rest)
+    } else {
+        let digits = line.bytes().take_while(u8::is_ascii_digit).count();
+        match line.as_bytes().get(digits) {
+            Some(b'.' | b')') if digits > 0 => (&line[..digits], &line[digits + 1..]),
+            _ => return None,
+        }
+    };
+    if !indented(rest) || rest.trim().is_empty() {
+        return None;
+    }
+    return Some((number, rest));
+}
+
+fn is_list(line: &str) -> bool {
+    return list_marker(line).is_some();
+}
+
+fn indented(line: &str) -> bool {
+    return line.starts_with([' ', '\t']);
+}
+
+fn is_heading(line: &str) -> bool {
+    let bytes = line.as_bytes();
+    return bytes.len() >= 2
+        && bytes[0] == b'#'
+        && (bytes[1] == b' ' || bytes[1] == b'\t')
+        && line.trim() != "#";
+}
+
+// Whether `line` is a heading written the old way: a single capitalized line without
+// punctuation, between blank lines and followed by unindented text.
+fn is_old_heading(line: &str, all: &[String], off: usize) -> bool {
+    if off == 0
+        || !all[off - 1].is_empty()
+        || off + 2 >= all.len()
+        || !all[off + 1].is_empty()
+        || indented(&all[off + 2])
+    {
+        return false;
+    }
+    let line = line.trim();
+    if !line.chars().next().is_some_and(char::is_uppercase) {
+        return false;
+    }
+    if !line.chars().last().is_some_and(char::is_alphanumeric) {
+        return false;
+    }
+    if line.contains(|c| ";:!?+*/=[]{}_^°&§~%#@<\">\\".contains(c)) {
+        return false;
+    }
+    // A quote is only allowed for a possessive "'s".
+    for (i, _) in line.match_indices('\'') {
+        let after = &line[i + 1..];
+        if after != "s" && !after.starts_with("s ") {
+            return false;
+        }
+    }
+    // A period must be followed by more text, as in a file name.
+    for (i, _) in line.match_indices('.') {
+        let after = &line[i + 1..];
+        if after.is_empty() || after.starts_with(' ') {
+            return false;
+        }
+    }
+    return true;
+}
+
+// Removes the indentation common to all lines and the blank lines at both ends.
+fn unindent<S: AsRef<str>>(lines: &[S]) -> Vec<String> {
+    let is_blank = |line: &str| line.trim().is_empty();
+    let lines: Vec<&str> = lines.iter().map(|line| line.as_ref()).collect();
+    let start = lines
+        .iter()
+        .position(|l| !is_blank(l))
+        .unwrap_or(lines.len());
+    let end = lines
+        .iter()
+        .rposition(|l| !is_blank(l))
+        .map_or(start, |i| i + 1);
+    let lines = &lines[start..end];
+    if lines.is_empty() {
+        return Vec::new();
+    }
+
+    let leading = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+    let mut prefix = &lines[0][..leading(lines[0])];
+    for line in &lines[1..] {
+        if !is_blank(line) {
+            let common = prefix
+                .bytes()
+                .zip(line[..leading(line)].bytes())
+                .take_while(|(a, b)| a == b)
+                .count();
+            prefix = &prefix[..common];
+        }
+    }
+    return lines
+        .iter()
+        .map(|line| match is_blank(line) {
+            true => String::new(),
+            false => line[prefix.len()..].to_string(),
+        })
+        .collect();
+}
+
+// ========
+// Printing
+// ========
+
+fn print(blocks: &[Block]) -> String {
+    let mut out = String::new();
+    for (i, block) in blocks.iter().enumerate() {
+        let blank_before = match block {
+            Block::List(list) => list.blank_before(),
+            _ => true,
+        };
+        if i > 0 && blank_before {
+            out.push('\n');
+        }
+        match block {
+            Block::Paragraph(lines) => {
+                for line in lines {
+                    out.push_str(line);
+                    out.push('\n');
+                }
+            }
+            Block::Heading(text) => {
+                out.push_str("# ");
+                out.push_str(text);
+                out.push('\n');
+            }
+            Block::Code(lines) => {
+                for line in lines {
+                    if !line.is_empty() {
+                        out.push('\t');
+                        out.push_str(line);
+                    }
+                    out.push('\n');
+                }
+            }
+            Block::List(list) => {
+                let loose = list.blank_between();
+                for (i, (number, paras)) in list.items.iter().enumerate() {
+                    if i > 0 && loose {
+                        out.push('\n');
+                    }
+                    match number.as_str() {
+                        "" => out.push_str("  - "),
+                        number => out.push_str(&format!(" {}. ", number)),
+                    }
+                    for (i, para) in paras.iter().enumerate() {
+                        if i > 0 {
+                            out.push_str("\n    ");
+                        }
+                        out.push_str(&para.join("\n    "));
+                        out.push('\n');
+                    }
+                }
+            }
+        }
+    }
+    return out;
+}
@@ -0,0 +1,159 @@
+// Copyright 2009 The Go Authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be found in the
+// LICENSE-go file.
+
+// Sorting of imports, as go/ast's SortImports does for gofmt. The specs of a parenthesized
+// import declaration are sorted by path within each run of consecutive lines, and
+// duplicates are removed. The source text is rearranged line by line, so that a spec keeps
+// the comment after it, and is parsed again.
+
+use crate::ast::{Decl, File, ImportSpec, Spec};
+use crate::token::TokenTypes;
+
+// Returns the source with its imports sorted, or None if they are sorted already.
+pub(super) fn sort_imports(file: &File, src: &[u8]) -> Option<Vec<u8>> {
+    let mut edits = Vec::new();
+    for decl in &file.decls {
+        let Decl::Gen(d) = decl else {
+            break;
+        };
+        if d.tok != TokenTypes::Import {
+            break;
+        }
+        if d.lparen.is_none() {
+            continue;
+        }
+        let specs: Vec<&ImportSpec> = d
+            .specs
+            .iter()
+            .filter_map(|s| match s {
+                Spec::Import(s) => Some(s),
+                _ => None,
+            })
+            .collect();
+
+        let mut start = 0;
+        while start < specs.len() {
+            let mut end = start + 1;
+            while end < specs.len()
+                && newlines(&src[specs[end - 1].span.end..specs[end].span.start]) <= 1
+            {
+                end += 1;
+            }
+            if let Some(edit) = sort_run(&specs[start..end], src) {
+                edits.push(edit);
+            }
+            start = end;
+        }
+    }
+    if edits.is_empty() {
+        return None;
+    }
+
+    let mut out = Vec::with_capacity(src.len());
+    let mut pos = 0;
+    for (start, end, text) in edits {
+        out.extend_from_slice(&src[pos..start]);
+        out.extend_from_slice(&text);
+        pos = end;
+    }
+    out.extend_from_slice(&src[pos..]);
+    return Some(out);
+}
+
+struct Line<'s> {
+    path: &'s [u8],
+    name: &'s [u8],
+    comment: &'s [u8],
+    text: &'s [u8],
+}
+
+// Sorts a run of specs on consecutive lines. Returns the range of the run in the source
+// and its sorted text, or None if it is sorted already or cannot be rearranged by lines.
+fn sort_run(specs: &[&ImportSpec], src: &[u8]) -> Option<(usize, usize, Vec<u8>)> {
+    if specs.len() < 2 {
+        return None;
+    }
+    let mut lines = Vec::new();
+    for spec in specs {
+        let line_start = src[..spec.span.start]
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        let line_end = src[spec.span.end..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(src.len(), |i| spec.span.end + i);
+        // Each spec has a line to itself, with at most a comment after it.
+        if !is_blank(&src[line_start..spec.span.start]) {
+            return None;
+        }
+        let rest = trim(&src[spec.span.end..line_end]);
+        let is_comment = rest.starts_with(b"//")
+            || (rest.starts_with(b"/*") && rest.ends_with(b"*/") && rest.len() >= 4);
+        if !rest.is_empty() && !is_comment {
+            return None;
+        }
+        let path = &src[spec.path.span.start..spec.path.span.end];
+        lines.push(Line {
+            path: &path[1..path.len() - 1],
+            name: spec
+                .name
+                .map_or(&[][..], |n| &src[n.span.start..n.span.end]),
+            comment: rest,
+            text: &src[line_start..line_end],
+        });
+    }
+    let start = lines[0].text.as_ptr() as usize - src.as_ptr() as usize;
+    let last = &lines[lines.len() - 1];
+    let end = last.text.as_ptr() as usize - src.as_ptr() as usize + last.text.len();
+
+    let sorted = lines.windows(2).all(|w| key(&w[0]) <= key(&w[1]));
+    lines.sort_by(|a, b| key(a).cmp(&key(b)));
+    // A duplicate is dropped unless it has a comment.
+    let len = lines.len();
+    let mut deduped = Vec::with_capacity(len);
+    for i in 0..len {
+        let duplicate = i + 1 < len
+            && lines[i].path == lines[i + 1].path
+            && lines[i].name == lines[i + 1].name
+            && lines[i].comment.is_empty();
+        if !duplicate {
+            deduped.push(&lines[i]);
+        }
+    }
+    if sorted && deduped.len() == len {
+        return None;
+    }
+
+    let text = deduped
+        .iter()
+        .map(|line| line.text)
+        .collect::<Vec<_>>()
+        .join(&b'\n');
+    return Some((start, end, text));
+}
+
+fn key<'s>(line: &Line<'s>) -> (&'s [u8], &'s [u8], &'s [u8]) {
+    return (line.path, line.name, line.comment);
+}
+
+fn newlines(text: &[u8]) -> usize {
+    return text.iter().filter(|&&b| b == b'\n').count();
+}
+
+fn is_blank(text: &[u8]) -> bool {
+    return text.iter().all(|&b| b == b' ' || b == b'\t' || b == b'\r');
+}
+
+fn trim(text: &[u8]) -> &[u8] {
+    let start = text
+        .iter()
+        .position(|b| !b.is_ascii_whitespace())
+        .unwrap_or(text.len());
+    let end = text
+        .iter()
+        .rposition(|b| !b.is_ascii_whitespace())
+        .map_or(start, |i| i + 1);
+    return &text[start..end];
+}
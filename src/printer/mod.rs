@@ -0,0 +1,1026 @@
+// Copyright 2009 The Go Authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be found in the
+// LICENSE-go file.
+
+// Formats Go source files in the canonical style of gofmt. The printer is a port of
+// go/printer: the syntax tree is walked in source order, comments are interspersed before
+// the first token that follows them in the source, line breaks follow those of the source
+// within limits, and the cells between tabs are aligned by a tabwriter afterwards.
+
+use crate::ast::{Comment, Expr, File, Stmt};
+use crate::diagnostic::Diagnostic;
+use crate::intern::Interner;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use crate::source::{FileId, LineIndex};
+use crate::token::TokenTypes;
+use std::collections::HashMap;
+use std::mem;
+use tabwriter::{TabWriter, ESCAPE};
+
+mod comment;
+mod imports;
+mod nodes;
+mod tabwriter;
+#[cfg(test)]
+mod test;
+
+// The maximum number of consecutive line breaks, which keeps at most one blank line.
+const MAX_NEWLINES: usize = 2;
+const INFINITY: usize = 1 << 30;
+
+// Formats `src` the way gofmt does, sorting its imports, or returns its syntax errors.
+pub fn format_source(src: &[u8], file: FileId) -> Result<Vec<u8>, Vec<Diagnostic>> {
+    let (ast, interner) = parse(src, file)?;
+    return match imports::sort_imports(&ast, src) {
+        Some(sorted) => {
+            let (ast, interner) = parse(&sorted, file)?;
+            Ok(format_file(&ast, &sorted, &interner))
+        }
+        None => Ok(format_file(&ast, src, &interner)),
+    };
+}
+
+fn parse(src: &[u8], file: FileId) -> Result<(File, Interner), Vec<Diagnostic>> {
+    let mut lexer = Lexer::new(src).with_file(file).with_comments(true);
+    let mut parser = Parser::new(&mut lexer);
+    let ast = parser.parse_file();
+    if parser.has_errors() {
+        return Err(parser.take_diagnostics());
+    }
+    return Ok((ast, parser.take_interner()));
+}
+
+// Prints `file`, which was parsed from `src` with its comments kept.
+pub fn format_file(file: &File, src: &[u8], interner: &Interner) -> Vec<u8> {
+    let lines = LineIndex::new(src);
+    let groups = comment_groups(src, &file.comments, &lines);
+    let mut p = Printer::new(
+        src,
+        &lines,
+        interner,
+        &file.comments,
+        &groups,
+        HashMap::new(),
+    );
+    p.file(file);
+    return p.finish(false);
+}
+
+// ========
+// Printer state
+// ========
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Whitespace {
+    Ignore,
+    Blank,
+    Vtab,
+    Newline,
+    Formfeed,
+    Indent,
+    Unindent,
+}
+
+impl Whitespace {
+    fn byte(self) -> u8 {
+        return match self {
+            Whitespace::Blank => b' ',
+            Whitespace::Vtab => b'\x0b',
+            Whitespace::Newline => b'\n',
+            Whitespace::Formfeed => b'\x0c',
+            Whitespace::Ignore | Whitespace::Indent | Whitespace::Unindent => {
+                unreachable!("{:?} is not written", self)
+            }
+        };
+    }
+}
+
+// Printer modes, toggled around the closing brace of composite literals and function
+// bodies: no extra blank or line break after a `/*` comment.
+const NO_EXTRA_BLANK: u8 = 1;
+const NO_EXTRA_LINEBREAK: u8 = 2;
+
+// A position in the source or the output. Lines and columns are 1-based; line 0 is no
+// position at all.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Position {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Position {
+    fn is_valid(&self) -> bool {
+        return self.line > 0;
+    }
+}
+
+// The comment group to be printed next.
+#[derive(Debug, Clone, Copy)]
+struct CommentInfo {
+    // The index of the group after the current one.
+    cindex: usize,
+    // The current group, as a range of comments.
+    comment: Option<(usize, usize)>,
+    comment_offset: usize,
+    comment_newline: bool,
+}
+
+// A node whose size on one line is measured by printing it on its own.
+#[derive(Clone, Copy)]
+enum Node<'a> {
+    Expr(&'a Expr),
+    Stmt(&'a Stmt),
+}
+
+impl Node<'_> {
+    fn key(&self) -> (usize, bool) {
+        return match self {
+            Node::Expr(x) => (*x as *const Expr as usize, false),
+            Node::Stmt(s) => (*s as *const Stmt as usize, true),
+        };
+    }
+}
+
+struct Printer<'a> {
+    src: &'a [u8],
+    lines: &'a LineIndex,
+    interner: &'a Interner,
+    comments: &'a [Comment],
+    groups: &'a [(usize, usize)],
+
+    output: Vec<u8>,
+    indent: usize,
+    // The nesting depth of composite literals.
+    level: usize,
+    mode: u8,
+    // Set after a line break inside a literal: alignment ends with the line.
+    end_alignment: bool,
+    // Set when a line break after the last token would end the statement.
+    implied_semi: bool,
+    // The last token printed, or Illegal after white space.
+    last_tok: TokenTypes,
+    // The `(` or `[` printed right before the last token, or Illegal.
+    prev_open: TokenTypes,
+    wsbuf: Vec<Whitespace>,
+
+    // The position of the next item in the source, which runs ahead of `out` when the
+    // output has less white space than the source.
+    pos: Position,
+    out: Position,
+    // The value of `pos` after the last token or comment written.
+    last: Position,
+    // The slot of `line_slots` that records the output line of the next token.
+    line_ptr: Option<usize>,
+    line_slots: Vec<usize>,
+
+    info: CommentInfo,
+    node_sizes: HashMap<(usize, bool), usize>,
+}
+
+impl<'a> Printer<'a> {
+    fn new(
+        src: &'a [u8],
+        lines: &'a LineIndex,
+        interner: &'a Interner,
+        comments: &'a [Comment],
+        groups: &'a [(usize, usize)],
+        node_sizes: HashMap<(usize, bool), usize>,
+    ) -> Printer<'a> {
+        let start = Position {
+            offset: 0,
+            line: 1,
+            column: 1,
+        };
+        let mut p = Printer {
+            src,
+            lines,
+            interner,
+            comments,
+            groups,
+            output: Vec::new(),
+            indent: 0,
+            level: 0,
+            mode: 0,
+            end_alignment: false,
+            implied_semi: false,
+            last_tok: TokenTypes::Illegal,
+            prev_open: TokenTypes::Illegal,
+            wsbuf: Vec::new(),
+            pos: start,
+            out: start,
+            last: Position::default(),
+            line_ptr: None,
+            line_slots: Vec::new(),
+            info: CommentInfo {
+                cindex: 0,
+                comment: None,
+                comment_offset: INFINITY,
+                comment_newline: false,
+            },
+            node_sizes,
+        };
+        p.next_comment();
+        return p;
+    }
+
+    // Writes out the pending comments and white space and returns the formatted text.
+    fn finish(&mut self, raw: bool) -> Vec<u8> {
+        // The end of the file acts like a line break.
+        self.implied_semi = false;
+        let eof = Position {
+            offset: INFINITY,
+            line: INFINITY,
+            column: 0,
+        };
+        self.flush(eof, TokenTypes::Eof);
+        if raw {
+            return trim(&self.output);
+        }
+        let mut tabwriter = TabWriter::new();
+        tabwriter.write(&self.output);
+        return trim(&tabwriter.finish());
+    }
+
+    // The size of `node` when printed on a single line, or more than `max_size` when it
+    // does not fit or spans several lines.
+    fn node_size(&mut self, node: Node, max_size: usize) -> usize {
+        let key = node.key();
+        if let Some(&size) = self.node_sizes.get(&key) {
+            return size;
+        }
+        // Nested composite literals would otherwise be measured over and over.
+        self.node_sizes.insert(key, max_size + 1);
+
+        let node_sizes = mem::take(&mut self.node_sizes);
+        let mut p = Printer::new(self.src, self.lines, self.interner, &[], &[], node_sizes);
+        match node {
+            Node::Expr(x) => p.expr(x),
+            Node::Stmt(s) => {
+                // A label is unindented, which needs some indentation to start from.
+                if matches!(s, Stmt::Labeled(_)) {
+                    p.indent = 1;
+                }
+                p.stmt(s, false);
+            }
+        }
+        let text = p.finish(true);
+        self.node_sizes = mem::take(&mut p.node_sizes);
+
+        let mut size = max_size + 1;
+        if text.len() <= max_size && !text.contains(&b'\n') {
+            size = text.len();
+            self.node_sizes.insert(key, size);
+        }
+        return size;
+    }
+
+    // ========
+    // Positions
+    // ========
+
+    fn pos_for(&self, offset: usize) -> Position {
+        let (line, column) = self.lines.line_col(offset);
+        return Position {
+            offset,
+            line,
+            column,
+        };
+    }
+
+    fn line_for(&self, offset: usize) -> usize {
+        return self.lines.line_col(offset).0;
+    }
+
+    fn set_pos(&mut self, offset: usize) {
+        self.pos = self.pos_for(offset);
+    }
+
+    // The offset of the first token at or after `offset`, past white space and comments.
+    fn token_at(&self, mut offset: usize) -> usize {
+        loop {
+            match self.src.get(offset) {
+                Some(b' ' | b'\t' | b'\r' | b'\n') => offset += 1,
+                Some(b'/') => {
+                    match self
+                        .comments
+                        .binary_search_by_key(&offset, |c| c.span.start)
+                    {
+                        Ok(i) => offset = self.comments[i].span.end,
+                        Err(_) => return offset,
+                    }
+                }
+                _ => return offset,
+            }
+        }
+    }
+
+    // Allocates a slot for recording output lines with `record_line`.
+    fn line_slot(&mut self) -> usize {
+        self.line_slots.push(0);
+        return self.line_slots.len() - 1;
+    }
+
+    // Records the output line of the next token in `slot`.
+    fn record_line(&mut self, slot: usize) {
+        self.line_ptr = Some(slot);
+    }
+
+    // The number of output lines since the line recorded in `slot`.
+    fn lines_from(&self, slot: usize) -> usize {
+        return self.out.line.saturating_sub(self.line_slots[slot]);
+    }
+
+    // ========
+    // Writing
+    // ========
+
+    fn write_indent(&mut self) {
+        // Indentation uses hard tabs, so that the tabwriter keeps the columns.
+        let n = self.indent;
+        self.output.extend(std::iter::repeat_n(b'\t', n));
+        self.pos.offset += n;
+        self.pos.column += n;
+        self.out.column += n;
+    }
+
+    // Writes a white space character `n` times.
+    fn write_byte(&mut self, mut ch: u8, n: usize) {
+        if self.end_alignment {
+            // Alignment ends with the line; a formfeed tells the tabwriter.
+            match ch {
+                b'\t' | b'\x0b' => ch = b' ',
+                b'\n' | b'\x0c' => {
+                    ch = b'\x0c';
+                    self.end_alignment = false;
+                }
+                _ => {}
+            }
+        }
+        if self.out.column == 1 {
+            self.write_indent();
+        }
+        self.output.extend(std::iter::repeat_n(ch, n));
+
+        self.pos.offset += n;
+        if ch == b'\n' || ch == b'\x0c' {
+            self.pos.line += n;
+            self.out.line += n;
+            self.pos.column = 1;
+            self.out.column = 1;
+            return;
+        }
+        self.pos.column += n;
+        self.out.column += n;
+    }
+
+    // Writes a token, literal or comment at source position `pos`. Literals are escaped
+    // so that the tabwriter leaves them alone.
+    fn write_string(&mut self, pos: Position, s: &str, is_lit: bool) {
+        if self.out.column == 1 {
+            self.write_indent();
+        }
+        if pos.is_valid() {
+            self.pos = pos;
+        }
+        if is_lit {
+            self.output.push(ESCAPE);
+        }
+        self.output.extend_from_slice(s.as_bytes());
+
+        let mut nlines = 0;
+        let mut last_newline = 0;
+        for (i, ch) in s.bytes().enumerate() {
+            if ch == b'\n' || ch == b'\x0c' {
+                nlines += 1;
+                last_newline = i;
+                // A line break inside a literal breaks any alignment on its last line.
+                self.end_alignment = true;
+            }
+        }
+        self.pos.offset += s.len();
+        if nlines > 0 {
+            self.pos.line += nlines;
+            self.out.line += nlines;
+            let column = s.len() - last_newline;
+            self.pos.column = column;
+            self.out.column = column;
+        } else {
+            self.pos.column += s.len();
+            self.out.column += s.len();
+        }
+        if is_lit {
+            self.output.push(ESCAPE);
+        }
+        self.last = self.pos;
+    }
+
+    // Writes the first `n` entries of the white space buffer.
+    fn write_whitespace(&mut self, n: usize) {
+        let mut i = 0;
+        while i < n {
+            match self.wsbuf[i] {
+                Whitespace::Ignore => {}
+                Whitespace::Indent => self.indent += 1,
+                Whitespace::Unindent => self.indent = self.indent.saturating_sub(1),
+                ws @ (Whitespace::Newline | Whitespace::Formfeed) => {
+                    // A line break followed by an unindent is swapped with it, so that a
+                    // label is positioned correctly. The formfeed ends the section, so
+                    // that a long label does not widen the indentation before it.
+                    if i + 1 < n && self.wsbuf[i + 1] == Whitespace::Unindent {
+                        self.wsbuf[i] = Whitespace::Unindent;
+                        self.wsbuf[i + 1] = Whitespace::Formfeed;
+                        continue;
+                    }
+                    self.write_byte(ws.byte(), 1);
+                }
+                ws => self.write_byte(ws.byte(), 1),
+            }
+            i += 1;
+        }
+        self.wsbuf.drain(..n);
+    }
+
+    // ========
+    // Comments
+    // ========
+
+    fn next_comment(&mut self) {
+        match self.groups.get(self.info.cindex) {
+            Some(&(start, end)) => {
+                self.info.cindex += 1;
+                self.info.comment = Some((start, end));
+                self.info.comment_offset = self.comments[start].span.start;
+                self.info.comment_newline = self.comments_have_newline(start, end);
+            }
+            None => {
+                self.info.comment = None;
+                self.info.comment_offset = INFINITY;
+            }
+        }
+    }
+
+    fn comments_have_newline(&self, start: usize, end: usize) -> bool {
+        let line = self.comments[start].span.line;
+        return self.comments[start..end]
+            .iter()
+            .any(|c| c.span.line != line || c.text.starts_with("//") || c.text.contains('\n'));
+    }
+
+    // Whether the current comment group comes before `next` and printing it does not
+    // introduce a semicolon.
+    fn comment_before(&self, next: Position) -> bool {
+        return self.info.comment_offset < next.offset
+            && (!self.implied_semi || !self.info.comment_newline);
+    }
+
+    // The size of the comments before `next`.
+    fn comment_size_before(&mut self, next: Position) -> usize {
+        let info = self.info;
+        let mut size = 0;
+        while self.comment_before(next) {
+            let (start, end) = self.info.comment.unwrap();
+            size += self.comments[start..end]
+                .iter()
+                .map(|c| c.text.len())
+                .sum::<usize>();
+            self.next_comment();
+        }
+        self.info = info;
+        return size;
+    }
+
+    // Writes the white space before a comment at `pos`, using as much of the pending
+    // white space as is likely to place the comment well. `prev` is the previous comment
+    // of the group and `next` the position of the token after the comments.
+    fn write_comment_prefix(
+        &mut self,
+        pos: Position,
+        next: Position,
+        prev: Option<&str>,
+        tok: TokenTypes,
+    ) {
+        if self.output.is_empty() {
+            return;
+        }
+        let prev_is_line = prev.is_some_and(|text| text.starts_with("//"));
+
+        if pos.line == self.last.line && !prev_is_line {
+            // The comment is on the line of the last item; separate them.
+            let mut has_sep = false;
+            if prev.is_none() {
+                let mut j = 0;
+                for i in 0..self.wsbuf.len() {
+                    match self.wsbuf[i] {
+                        Whitespace::Blank => self.wsbuf[i] = Whitespace::Ignore,
+                        // Tabs are kept, for the comments of struct fields to line up.
+                        Whitespace::Vtab => has_sep = true,
+                        Whitespace::Indent => {}
+                        _ => {
+                            j = i;
+                            break;
+                        }
+                    }
+                }
+                self.write_whitespace(j);
+            }
+            if !has_sep {
+                // A `/*` comment followed by more on the same line gets a blank.
+                let sep = if pos.line == next.line { b' ' } else { b'\t' };
+                self.write_byte(sep, 1);
+            }
+            return;
+        }
+
+        // The comment is on a line of its own; separate it by at least one line break.
+        let mut dropped_linebreak = false;
+        let mut j = 0;
+        for i in 0..self.wsbuf.len() {
+            match self.wsbuf[i] {
+                Whitespace::Blank | Whitespace::Vtab => {
+                    self.wsbuf[i] = Whitespace::Ignore;
+                    continue;
+                }
+                Whitespace::Indent => continue,
+                Whitespace::Unindent => {
+                    // All but the last unindent belong to the construct before. The last
+                    // one closes a block unless the comment lines up with the next token,
+                    // as comments before a case label do.
+                    if i + 1 < self.wsbuf.len() && self.wsbuf[i + 1] == Whitespace::Unindent {
+                        continue;
+                    }
+                    if tok != TokenTypes::RBrace && pos.column == next.column {
+                        continue;
+                    }
+                }
+                Whitespace::Newline | Whitespace::Formfeed => {
+                    self.wsbuf[i] = Whitespace::Ignore;
+                    dropped_linebreak = prev.is_none();
+                }
+                Whitespace::Ignore => {}
+            }
+            j = i;
+            break;
+        }
+        self.write_whitespace(j);
+
+        let mut n = 0;
+        if pos.is_valid() && self.last.is_valid() {
+            n = pos.line.saturating_sub(self.last.line);
+        }
+        // A blank line before a comment at the top level is kept.
+        if self.indent == 0 && dropped_linebreak {
+            n += 1;
+        }
+        if n == 0 && prev_is_line {
+            n = 1;
+        }
+        if n > 0 {
+            // Formfeeds break the alignment of the lines before the comment.
+            self.write_byte(b'\x0c', nlimit(n));
+        }
+    }
+
+    fn write_comment(&mut self, text: &str, pos: Position) {
+        // A line directive stays in the first column.
+        let indent = self.indent;
+        if text.starts_with("//line ") && pos.column == 1 {
+            self.indent = 0;
+        }
+        if text.starts_with("//") {
+            self.write_string(pos, text.trim_end(), true);
+            self.indent = indent;
+            return;
+        }
+
+        // A block comment is written line by line, so that each line is indented.
+        let mut lines: Vec<String> = text.split('\n').map(|l| l.to_string()).collect();
+        // A comment in the first column that is going to be indented is treated as if it
+        // already were, so that formatting it again gives the same result.
+        if pos.column == 1 && self.indent > 0 {
+            for line in &mut lines[1..] {
+                line.insert_str(0, "   ");
+            }
+        }
+        strip_common_prefix(&mut lines);
+        let mut pos = pos;
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                self.write_byte(b'\x0c', 1);
+                pos = self.pos;
+            }
+            if !line.is_empty() {
+                self.write_string(pos, line.trim_end(), true);
+            }
+        }
+        self.indent = indent;
+    }
+
+    // Writes the line break after a comment if needed and handles the rest of the pending
+    // white space. Returns whether a line break was written and whether a formfeed was
+    // dropped.
+    fn write_comment_suffix(&mut self, mut needs_linebreak: bool) -> (bool, bool) {
+        let mut wrote_newline = false;
+        let mut dropped_ff = false;
+        for i in 0..self.wsbuf.len() {
+            match self.wsbuf[i] {
+                Whitespace::Blank | Whitespace::Vtab => self.wsbuf[i] = Whitespace::Ignore,
+                Whitespace::Newline | Whitespace::Formfeed => {
+                    // Exactly one line break is kept if one is needed.
+                    if needs_linebreak {
+                        needs_linebreak = false;
+                        wrote_newline = true;
+                    } else {
+                        if self.wsbuf[i] == Whitespace::Formfeed {
+                            dropped_ff = true;
+                        }
+                        self.wsbuf[i] = Whitespace::Ignore;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.write_whitespace(self.wsbuf.len());
+        if needs_linebreak {
+            self.write_byte(b'\n', 1);
+            wrote_newline = true;
+        }
+        return (wrote_newline, dropped_ff);
+    }
+
+    fn contains_linebreak(&self) -> bool {
+        return self
+            .wsbuf
+            .iter()
+            .any(|&ws| ws == Whitespace::Newline || ws == Whitespace::Formfeed);
+    }
+
+    // Prints the comments before the next token `tok` at `next`, mixed with the pending
+    // white space.
+    fn intersperse_comments(&mut self, next: Position, tok: TokenTypes) -> (bool, bool) {
+        let comments = self.comments;
+        let mut last: Option<&Comment> = None;
+        while self.comment_before(next) {
+            let (start, end) = self.info.comment.unwrap();
+            let group = &comments[start..end];
+            let first = self.pos_for(group[0].span.start);
+            let group_end = group[end - start - 1].span.end;
+
+            // An unindented group right before the next token is a doc comment at the top
+            // level, which is reformatted.
+            let mut formatted = None;
+            if self.last_tok != TokenTypes::Import
+                && first.column == 1
+                && self.pos_for(group_end + 1) == next
+            {
+                let texts: Vec<&str> = group.iter().map(|c| c.text.as_str()).collect();
+                formatted = comment::format_doc_comment(&texts);
+            }
+            match formatted {
+                Some(lines) => {
+                    let mut prev = last.map(|c| c.text.as_str());
+                    for line in &lines {
+                        self.write_comment_prefix(first, next, prev, tok);
+                        self.write_comment(line, first);
+                        prev = Some(line);
+                    }
+                    // Printing goes on where the group ended in the source.
+                    self.pos = self.pos_for(group_end);
+                    self.last = self.pos;
+                }
+                None => {
+                    for c in group {
+                        let pos = self.pos_for(c.span.start);
+                        self.write_comment_prefix(pos, next, last.map(|c| c.text.as_str()), tok);
+                        self.write_comment(&c.text, pos);
+                        last = Some(c);
+                    }
+                }
+            }
+            last = Some(&group[end - start - 1]);
+            self.next_comment();
+        }
+
+        let last = last.expect("no comments to intersperse");
+        let mut needs_linebreak = false;
+        // A `/*` comment followed by more on its line is separated by a blank, or by a
+        // line break when one is pending outside a composite literal. Commas and closing
+        // brackets right after an opening one follow immediately.
+        if self.mode & NO_EXTRA_BLANK == 0
+            && last.text.starts_with("/*")
+            && last.span.line == next.line
+            && tok != TokenTypes::Comma
+            && (tok != TokenTypes::RParen || self.prev_open == TokenTypes::LParen)
+            && (tok != TokenTypes::RBrack || self.prev_open == TokenTypes::LBrack)
+        {
+            if self.contains_linebreak() && self.mode & NO_EXTRA_LINEBREAK == 0 && self.level == 0 {
+                needs_linebreak = true;
+            } else {
+                self.write_byte(b' ', 1);
+            }
+        }
+        // A line comment, the end of the file and usually a closing brace need a line
+        // break after the comment.
+        if last.text.starts_with("//")
+            || tok == TokenTypes::Eof
+            || (tok == TokenTypes::RBrace && self.mode & NO_EXTRA_LINEBREAK == 0)
+        {
+            needs_linebreak = true;
+        }
+        return self.write_comment_suffix(needs_linebreak);
+    }
+
+    // Prints the comments and white space that come before the next token.
+    fn flush(&mut self, next: Position, tok: TokenTypes) -> (bool, bool) {
+        if self.comment_before(next) {
+            return self.intersperse_comments(next, tok);
+        }
+        self.write_whitespace(self.wsbuf.len());
+        return (false, false);
+    }
+
+    // ========
+    // Printing
+    // ========
+
+    fn record_open(&mut self) {
+        match self.last_tok {
+            TokenTypes::Illegal => {}
+            TokenTypes::LParen | TokenTypes::LBrack => self.prev_open = self.last_tok,
+            _ => self.prev_open = TokenTypes::Illegal,
+        }
+    }
+
+    // Adds white space, which is written before the next token.
+    fn ws(&mut self, ws: Whitespace) {
+        self.record_open();
+        // Ignored entries would get in the way of a correcting unindent.
+        if ws == Whitespace::Ignore {
+            return;
+        }
+        self.wsbuf.push(ws);
+        if ws == Whitespace::Newline || ws == Whitespace::Formfeed {
+            // The line break comes before any comments written with the next token.
+            self.implied_semi = false;
+        }
+        self.last_tok = TokenTypes::Illegal;
+    }
+
+    fn toggle(&mut self, mode: u8) {
+        self.record_open();
+        self.mode ^= mode;
+    }
+
+    fn tok(&mut self, tok: TokenTypes) {
+        self.record_open();
+        let data = tok.go_name();
+        if may_combine(self.last_tok, data.as_bytes()[0]) {
+            // The two tokens would read as one without a blank.
+            self.wsbuf.push(Whitespace::Blank);
+        }
+        let implied_semi = matches!(
+            tok,
+            TokenTypes::Break
+                | TokenTypes::Continue
+                | TokenTypes::Fallthrough
+                | TokenTypes::Return
+                | TokenTypes::Increment
+                | TokenTypes::Decrement
+                | TokenTypes::RParen
+                | TokenTypes::RBrack
+                | TokenTypes::RBrace
+        );
+        self.last_tok = tok;
+        self.emit(data, false, implied_semi);
+    }
+
+    fn name(&mut self, name: &str) {
+        self.record_open();
+        self.last_tok = TokenTypes::Identifier;
+        self.emit(name, false, true);
+    }
+
+    fn lit(&mut self, kind: TokenTypes, value: &str) {
+        self.record_open();
+        self.last_tok = kind;
+        self.emit(value, true, true);
+    }
+
+    fn emit(&mut self, data: &str, is_lit: bool, mut implied_semi: bool) {
+        let next = self.pos;
+        let (wrote_newline, dropped_ff) = self.flush(next, self.last_tok);
+
+        // Line breaks of the source before the token are kept if they cannot end a
+        // statement. This is not done by flush, which would add them at the end of the
+        // file.
+        if !self.implied_semi {
+            let mut n = nlimit(next.line.saturating_sub(self.pos.line));
+            if wrote_newline && n == MAX_NEWLINES {
+                n = MAX_NEWLINES - 1;
+            }
+            if n > 0 {
+                let ch = if dropped_ff { b'\x0c' } else { b'\n' };
+                self.write_byte(ch, n);
+                implied_semi = false;
+            }
+        }
+
+        if let Some(slot) = self.line_ptr.take() {
+            self.line_slots[slot] = self.out.line;
+        }
+        self.write_string(next, data, is_lit);
+        self.implied_semi = implied_semi;
+    }
+
+    // Prints enough line breaks to get to `line`, but at least `min`, after `ws`. The
+    // first line break is a formfeed if `new_section` is set. Returns the number of line
+    // breaks, counting a formfeed as two.
+    fn linebreak(&mut self, line: usize, min: usize, ws: Whitespace, new_section: bool) -> usize {
+        let mut n = usize::max(nlimit(line.saturating_sub(self.pos.line)), min);
+        let mut nbreaks = 0;
+        if n > 0 {
+            self.ws(ws);
+            if new_section {
+                self.ws(Whitespace::Formfeed);
+                n -= 1;
+                nbreaks = 2;
+            }
+            nbreaks += n;
+            for _ in 0..n {
+                self.ws(Whitespace::Newline);
+            }
+        }
+        return nbreaks;
+    }
+}
+
+fn nlimit(n: usize) -> usize {
+    return usize::min(n, MAX_NEWLINES);
+}
+
+// Whether a token starting with `next` would combine with the token before it.
+fn may_combine(prev: TokenTypes, next: u8) -> bool {
+    return match prev {
+        TokenTypes::Int | TokenTypes::Octal | TokenTypes::Hex | TokenTypes::Binary => next == b'.',
+        TokenTypes::Add => next == b'+',
+        TokenTypes::Sub => next == b'-',
+        TokenTypes::Quo => next == b'*',
+        TokenTypes::Lss => next == b'-' || next == b'<',
+        TokenTypes::Amp => next == b'&' || next == b'^',
+        _ => false,
+    };
+}
+
+// Groups adjacent comments as go/parser does. A group ends at a blank line or a token,
+// and a comment that follows a token on its line only groups with comments on that line.
+fn comment_groups(src: &[u8], comments: &[Comment], lines: &LineIndex) -> Vec<(usize, usize)> {
+    let mut groups: Vec<(usize, usize)> = Vec::new();
+    let mut after_token = false;
+    for (i, c) in comments.iter().enumerate() {
+        if i > 0 {
+            let prev = &comments[i - 1];
+            let between = &src[prev.span.end..c.span.start];
+            let gap = c.span.line - lines.line_col(prev.span.end).0;
+            let allowed = if after_token { 0 } else { 1 };
+            if between.iter().all(u8::is_ascii_whitespace) && gap <= allowed {
+                groups.last_mut().unwrap().1 = i + 1;
+                continue;
+            }
+        }
+        let line_start = c.span.start + 1 - c.span.column;
+        after_token = !src[line_start..c.span.start]
+            .iter()
+            .all(u8::is_ascii_whitespace);
+        groups.push((i, i + 1));
+    }
+    return groups;
+}
+
+// Removes the indentation common to the lines of a block comment, keeping a column of
+// stars and the text aligned with the opening `/*`.
+fn strip_common_prefix(lines: &mut [String]) {
+    if lines.len() <= 1 {
+        return;
+    }
+    let is_blank = |s: &str| s.bytes().all(|c| c == b' ' || c == b'\t');
+
+    // The common white space and star prefix of the inner lines that are not blank, or
+    // of the last line if there are none.
+    let mut prefix: Option<String> = None;
+    let n = lines.len();
+    for line in &mut lines[1..n - 1] {
+        if is_blank(line) {
+            line.clear();
+        } else {
+            prefix = Some(match prefix {
+                Some(prefix) => common_prefix(&prefix, line).to_string(),
+                None => common_prefix(line, line).to_string(),
+            });
+        }
+    }
+    let mut prefix =
+        prefix.unwrap_or_else(|| common_prefix(&lines[n - 1], &lines[n - 1]).to_string());
+
+    let mut line_of_stars = false;
+    if let Some(star) = prefix.find('*') {
+        // The blank before the stars is left, so that they stay aligned.
+        prefix.truncate(star);
+        if prefix.ends_with(' ') {
+            prefix.pop();
+        }
+        line_of_stars = true;
+    } else {
+        let first = lines[0].as_bytes();
+        if is_blank(&lines[0][2..]) {
+            // Without text on the first line, the text keeps up to three blanks or a tab
+            // of indentation relative to the `/*`.
+            let mut i = prefix.len();
+            let bytes = prefix.as_bytes();
+            let mut k = 0;
+            while k < 3 && i > 0 && bytes[i - 1] == b' ' {
+                i -= 1;
+                k += 1;
+            }
+            if i == prefix.len() && i > 0 && bytes[i - 1] == b'\t' {
+                i -= 1;
+            }
+            prefix.truncate(i);
+        } else {
+            // The white space after the `/*` is taken to stand in for the `/*` itself,
+            // unless it starts with a tab.
+            let mut end = 2;
+            while end < first.len() && first[end] <= b' ' {
+                end += 1;
+            }
+            let suffix = if end > 2 && first[2] == b'\t' {
+                lines[0][2..end].to_string()
+            } else {
+                format!("  {}", &lines[0][2..end])
+            };
+            if prefix.ends_with(&suffix) {
+                prefix.truncate(prefix.len() - suffix.len());
+            }
+        }
+    }
+
+    // A last line with only the closing `*/` lines up with the opening `/*`.
+    let last = &lines[n - 1];
+    let before = &last[..last.find("*/").unwrap_or(last.len())];
+    if is_blank(before) {
+        let closing = if line_of_stars { " */" } else { "*/" };
+        lines[n - 1] = format!("{}{}", prefix, closing);
+    } else {
+        prefix = common_prefix(&prefix, last).to_string();
+    }
+
+    for line in &mut lines[1..] {
+        if !line.is_empty() {
+            line.drain(..prefix.len());
+        }
+    }
+}
+
+// The common prefix of `a` and `b` made of white space and stars.
+fn common_prefix<'s>(a: &'s str, b: &str) -> &'s str {
+    let len = a
+        .bytes()
+        .zip(b.bytes())
+        .take_while(|&(x, y)| x == y && (x <= b' ' || x == b'*'))
+        .count();
+    return &a[..len];
+}
+
+// Removes trailing white space and the escapes around literals, and turns vertical tabs
+// and formfeeds into tabs and line breaks.
+fn trim(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len());
+    let mut space: Vec<u8> = Vec::new();
+    let mut escaped = false;
+    for &b in data {
+        if escaped {
+            if b == ESCAPE {
+                escaped = false;
+            } else {
+                out.push(b);
+            }
+            continue;
+        }
+        match b {
+            b'\t' | b'\x0b' | b' ' => space.push(if b == b'\x0b' { b'\t' } else { b }),
+            b'\n' | b'\x0c' => {
+                space.clear();
+                out.push(b'\n');
+            }
+            _ => {
+                out.append(&mut space);
+                if b == ESCAPE {
+                    escaped = true;
+                } else {
+                    out.push(b);
+                }
+            }
+        }
+    }
+    return out;
+}
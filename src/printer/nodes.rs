@@ -0,0 +1,1608 @@
+// Copyright 2009 The Go Authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be found in the
+// LICENSE-go file.
+
+// Printing of declarations, statements and expressions, following go/printer. Positions
+// that the syntax tree does not keep, such as those of operators, colons and closing
+// brackets, are found by looking for the token in the source.
+
+use super::{Node, Printer, Whitespace, INFINITY, NO_EXTRA_BLANK, NO_EXTRA_LINEBREAK};
+use crate::ast::visit::{walk_expr, Visitor};
+use crate::ast::*;
+use crate::token::TokenTypes;
+
+const LOWEST_PREC: u8 = 0;
+const UNARY_PREC: u8 = 6;
+const HIGHEST_PREC: u8 = 7;
+
+// Options of expr_list: a trailing comma is printed when the list ends on its own line,
+// and a list spanning lines is not indented further.
+const COMMA_TERM: u8 = 1;
+const NO_INDENT: u8 = 2;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ParamMode {
+    Func,
+    FuncTParam,
+    TypeTParam,
+}
+
+// A statement of a statement list, or a clause of a switch or select statement.
+trait ListStmt {
+    fn start(&self) -> usize;
+    fn print(&self, p: &mut Printer<'_>, next_is_rbrace: bool);
+
+    fn is_empty_stmt(&self) -> bool {
+        return false;
+    }
+
+    // The number of labels in front of the statement.
+    fn labels(&self) -> usize {
+        return 0;
+    }
+}
+
+impl ListStmt for Stmt {
+    fn start(&self) -> usize {
+        return self.span().start;
+    }
+
+    fn print(&self, p: &mut Printer<'_>, next_is_rbrace: bool) {
+        p.stmt(self, next_is_rbrace);
+    }
+
+    fn is_empty_stmt(&self) -> bool {
+        return matches!(self, Stmt::Empty(_));
+    }
+
+    fn labels(&self) -> usize {
+        let mut n = 0;
+        let mut stmt = self;
+        while let Stmt::Labeled(s) = stmt {
+            n += 1;
+            stmt = &s.stmt;
+        }
+        return n;
+    }
+}
+
+impl ListStmt for CaseClause {
+    fn start(&self) -> usize {
+        return self.span.start;
+    }
+
+    fn print(&self, p: &mut Printer<'_>, next_is_rbrace: bool) {
+        p.set_pos(self.span.start);
+        let colon;
+        if let Some(last) = self.list.last() {
+            p.tok(TokenTypes::Case);
+            p.ws(Whitespace::Blank);
+            colon = p.token_at(last.span().end);
+            p.expr_list(Some(self.span.start), &self.list, 1, 0, Some(colon));
+        } else {
+            p.tok(TokenTypes::Default);
+            colon = p.token_at(self.span.start + "default".len());
+        }
+        p.set_pos(colon);
+        p.tok(TokenTypes::Colon);
+        p.stmt_list(&self.body, 1, next_is_rbrace);
+    }
+}
+
+impl ListStmt for CommClause {
+    fn start(&self) -> usize {
+        return self.span.start;
+    }
+
+    fn print(&self, p: &mut Printer<'_>, next_is_rbrace: bool) {
+        p.set_pos(self.span.start);
+        let colon;
+        if let Some(comm) = &self.comm {
+            p.tok(TokenTypes::Case);
+            p.ws(Whitespace::Blank);
+            p.stmt(comm, false);
+            colon = p.token_at(comm.span().end);
+        } else {
+            p.tok(TokenTypes::Default);
+            colon = p.token_at(self.span.start + "default".len());
+        }
+        p.set_pos(colon);
+        p.tok(TokenTypes::Colon);
+        p.stmt_list(&self.body, 1, next_is_rbrace);
+    }
+}
+
+impl Printer<'_> {
+    // ========
+    // Declarations
+    // ========
+
+    pub(super) fn file(&mut self, file: &File) {
+        self.set_pos(file.package.start);
+        self.tok(TokenTypes::Package);
+        self.ws(Whitespace::Blank);
+        self.ident(&file.name);
+        self.decl_list(&file.decls);
+        self.ws(Whitespace::Newline);
+    }
+
+    fn decl_list(&mut self, decls: &[Decl]) {
+        let mut tok = TokenTypes::Illegal;
+        for decl in decls {
+            let prev = tok;
+            tok = match decl {
+                Decl::Bad(_) => TokenTypes::Illegal,
+                Decl::Gen(d) => d.tok,
+                Decl::Func(_) => TokenTypes::Func,
+            };
+            let span = decl.span();
+            if !self.output.is_empty() {
+                // A blank line goes between declarations of different kinds and before
+                // documented ones, and a function spanning lines starts a new section.
+                let min = if prev != tok || self.has_doc(span.start) {
+                    2
+                } else {
+                    1
+                };
+                let lines = self.line_for(span.end) - self.line_for(span.start) + 1;
+                let line = self.line_for(span.start);
+                self.linebreak(
+                    line,
+                    min,
+                    Whitespace::Ignore,
+                    tok == TokenTypes::Func && lines > 1,
+                );
+            }
+            match decl {
+                Decl::Bad(d) => {
+                    self.set_pos(d.span.start);
+                    self.lit(TokenTypes::String, "BadDecl");
+                }
+                Decl::Gen(d) => self.gen_decl(d),
+                Decl::Func(d) => self.func_decl(d),
+            }
+        }
+    }
+
+    fn gen_decl(&mut self, d: &GenDecl) {
+        self.set_pos(d.span.start);
+        self.tok(d.tok);
+        self.ws(Whitespace::Blank);
+        if d.lparen.is_none() && d.specs.len() == 1 {
+            self.spec(&d.specs[0], 1, true);
+            return;
+        }
+
+        if let Some(lparen) = d.lparen {
+            self.set_pos(lparen.start);
+        }
+        self.tok(TokenTypes::LParen);
+        let n = d.specs.len();
+        if n > 0 {
+            self.ws(Whitespace::Indent);
+            self.ws(Whitespace::Formfeed);
+            let grouped_values = n > 1 && matches!(d.tok, TokenTypes::Const | TokenTypes::Var);
+            let keep_type = match grouped_values {
+                true => keep_type_column(&d.specs),
+                false => Vec::new(),
+            };
+            let line = self.line_slot();
+            for (i, spec) in d.specs.iter().enumerate() {
+                if i > 0 {
+                    let new_section = self.lines_from(line) > 0;
+                    let spec_line = self.line_for(spec.span().start);
+                    self.linebreak(spec_line, 1, Whitespace::Ignore, new_section);
+                }
+                self.record_line(line);
+                match spec {
+                    Spec::Value(s) if grouped_values => self.value_spec(s, keep_type[i]),
+                    _ => self.spec(spec, n, false),
+                }
+            }
+            self.ws(Whitespace::Unindent);
+            self.ws(Whitespace::Formfeed);
+        }
+        if let Some(rparen) = d.rparen {
+            self.set_pos(rparen.start);
+        }
+        self.tok(TokenTypes::RParen);
+    }
+
+    // Prints a spec of a const or var group, with the names, type, values and a line
+    // comment in columns.
+    fn value_spec(&mut self, s: &ValueSpec, keep_type: bool) {
+        self.ident_list(&s.names, false);
+        let mut extra_tabs = 3;
+        if s.ty.is_some() || keep_type {
+            self.ws(Whitespace::Vtab);
+            extra_tabs -= 1;
+        }
+        if let Some(ty) = &s.ty {
+            self.expr(ty);
+        }
+        if !s.values.is_empty() {
+            self.ws(Whitespace::Vtab);
+            self.tok(TokenTypes::Assign);
+            self.ws(Whitespace::Blank);
+            self.expr_list(None, &s.values, 1, 0, None);
+            extra_tabs -= 1;
+        }
+        if self.has_line_comment(s.span.end) {
+            for _ in 0..extra_tabs {
+                self.ws(Whitespace::Vtab);
+            }
+        }
+    }
+
+    fn spec(&mut self, spec: &Spec, n: usize, do_indent: bool) {
+        match spec {
+            Spec::Import(s) => {
+                if let Some(name) = &s.name {
+                    self.ident(name);
+                    self.ws(Whitespace::Blank);
+                }
+                self.set_pos(s.path.span.start);
+                match sanitize_import_path(&s.path) {
+                    Some(path) => self.lit(TokenTypes::String, &path),
+                    None => self.lit(s.path.kind, &s.path.value),
+                }
+            }
+            Spec::Value(s) => {
+                self.ident_list(&s.names, do_indent);
+                if let Some(ty) = &s.ty {
+                    self.ws(Whitespace::Blank);
+                    self.expr(ty);
+                }
+                if !s.values.is_empty() {
+                    self.ws(Whitespace::Blank);
+                    self.tok(TokenTypes::Assign);
+                    self.ws(Whitespace::Blank);
+                    self.expr_list(None, &s.values, 1, 0, None);
+                }
+            }
+            Spec::Type(s) => {
+                self.ident(&s.name);
+                if let Some(params) = &s.type_params {
+                    self.parameters(params, ParamMode::TypeTParam);
+                }
+                self.ws(if n == 1 {
+                    Whitespace::Blank
+                } else {
+                    Whitespace::Vtab
+                });
+                if s.assign.is_some() {
+                    self.tok(TokenTypes::Assign);
+                    self.ws(Whitespace::Blank);
+                }
+                self.expr(&s.ty);
+            }
+        }
+    }
+
+    fn func_decl(&mut self, d: &FuncDecl) {
+        self.set_pos(d.span.start);
+        self.tok(TokenTypes::Func);
+        self.ws(Whitespace::Blank);
+        // The column is taken after `func`, which writes the white space before it.
+        let start_col = self.out.column - "func ".len();
+        if let Some(recv) = &d.recv {
+            self.parameters(recv, ParamMode::Func);
+            self.ws(Whitespace::Blank);
+        }
+        self.ident(&d.name);
+        self.signature(&d.ty);
+        let header_size = self.distance_from(d.span.start, start_col);
+        self.func_body(header_size, Whitespace::Vtab, d.body.as_ref());
+    }
+
+    // The width of the output since `start_col` if it is still on the line of `start`.
+    fn distance_from(&self, start: usize, start_col: usize) -> usize {
+        if self.pos.is_valid() && self.line_for(start) == self.pos.line {
+            return self.out.column - start_col;
+        }
+        return INFINITY;
+    }
+
+    // Prints a function body, on the line of the header if both fit on a line.
+    fn func_body(&mut self, header_size: usize, sep: Whitespace, body: Option<&BlockStmt>) {
+        let Some(b) = body else {
+            return;
+        };
+        let level = self.level;
+        self.level = 0;
+
+        const MAX_SIZE: usize = 100;
+        if header_size + self.body_size(b, MAX_SIZE) <= MAX_SIZE {
+            self.ws(sep);
+            self.set_pos(b.span.start);
+            self.tok(TokenTypes::LBrace);
+            if !b.stmts.is_empty() {
+                self.ws(Whitespace::Blank);
+                for (i, s) in b.stmts.iter().enumerate() {
+                    if i > 0 {
+                        self.tok(TokenTypes::Semicolon);
+                        self.ws(Whitespace::Blank);
+                    }
+                    self.stmt(s, i == b.stmts.len() - 1);
+                }
+                self.ws(Whitespace::Blank);
+            }
+            self.toggle(NO_EXTRA_LINEBREAK);
+            self.set_pos(b.span.end - 1);
+            self.tok(TokenTypes::RBrace);
+            self.toggle(NO_EXTRA_LINEBREAK);
+        } else {
+            if sep != Whitespace::Ignore {
+                self.ws(Whitespace::Blank);
+            }
+            self.block(b, 1);
+        }
+        self.level = level;
+    }
+
+    // The size of a block on one line, or more than `max_size` if it does not fit.
+    fn body_size(&mut self, b: &BlockStmt, max_size: usize) -> usize {
+        let rbrace = b.span.end - 1;
+        if self.line_for(b.span.start) != self.line_for(rbrace) || b.stmts.len() > 5 {
+            return max_size + 1;
+        }
+        let mut size = self.comment_size_before(self.pos_for(rbrace));
+        for (i, s) in b.stmts.iter().enumerate() {
+            if size > max_size {
+                break;
+            }
+            if i > 0 {
+                // For the semicolon and the blank.
+                size += 2;
+            }
+            size += self.node_size(Node::Stmt(s), max_size);
+        }
+        return size;
+    }
+
+    // Whether the declaration at `offset` has a doc comment.
+    fn has_doc(&self, offset: usize) -> bool {
+        let i = self.comments.partition_point(|c| c.span.start < offset);
+        if i == 0 {
+            return false;
+        }
+        let c = &self.comments[i - 1];
+        return c.doc && self.line_for(c.span.end) + 1 == self.line_for(offset);
+    }
+
+    // Whether a comment follows `end` on its line, with nothing after it on the line.
+    fn has_line_comment(&self, end: usize) -> bool {
+        let i = self.comments.partition_point(|c| c.span.start < end);
+        let Some(c) = self.comments.get(i) else {
+            return false;
+        };
+        let between = &self.src[end..c.span.start];
+        return between.iter().all(|&b| b == b' ' || b == b'\t')
+            && self.line_for(self.token_at(end)) > self.line_for(end);
+    }
+
+    // ========
+    // Statements
+    // ========
+
+    fn block(&mut self, b: &BlockStmt, nindent: usize) {
+        self.braced_list(b.span.start, &b.stmts, b.span.end - 1, nindent);
+    }
+
+    fn braced_list<S: ListStmt>(
+        &mut self,
+        lbrace: usize,
+        list: &[S],
+        rbrace: usize,
+        nindent: usize,
+    ) {
+        self.set_pos(lbrace);
+        self.tok(TokenTypes::LBrace);
+        self.stmt_list(list, nindent, true);
+        self.linebreak(self.line_for(rbrace), 1, Whitespace::Ignore, true);
+        self.set_pos(rbrace);
+        self.tok(TokenTypes::RBrace);
+    }
+
+    fn stmt_list<S: ListStmt>(&mut self, list: &[S], nindent: usize, next_is_rbrace: bool) {
+        if nindent > 0 {
+            self.ws(Whitespace::Indent);
+        }
+        let line = self.line_slot();
+        let mut i = 0;
+        for s in list {
+            if s.is_empty_stmt() {
+                continue;
+            }
+            // Without indentation the list holds the clauses of a switch or select
+            // statement, each of which is a new section.
+            if !self.output.is_empty() {
+                let new_section = i == 0 || nindent == 0 || self.lines_from(line) > 0;
+                self.linebreak(self.line_for(s.start()), 1, Whitespace::Ignore, new_section);
+            }
+            self.record_line(line);
+            s.print(self, next_is_rbrace && i == list.len() - 1);
+            // Labels are on lines of their own, before the line of the statement.
+            self.line_slots[line] += s.labels();
+            i += 1;
+        }
+        if nindent > 0 {
+            self.ws(Whitespace::Unindent);
+        }
+    }
+
+    pub(super) fn stmt(&mut self, stmt: &Stmt, next_is_rbrace: bool) {
+        self.set_pos(stmt.span().start);
+        match stmt {
+            Stmt::Bad(_) => self.lit(TokenTypes::String, "BadStmt"),
+            Stmt::Decl(d) => self.gen_decl(d),
+            Stmt::Empty(_) => {}
+            Stmt::Labeled(s) => {
+                // The label goes back one level of indentation.
+                self.ws(Whitespace::Unindent);
+                self.ident(&s.label);
+                self.set_pos(self.token_at(s.label.span.end));
+                self.tok(TokenTypes::Colon);
+                self.ws(Whitespace::Indent);
+                if let Stmt::Empty(e) = &s.stmt {
+                    if !next_is_rbrace {
+                        self.ws(Whitespace::Newline);
+                        self.set_pos(e.span.start);
+                        self.tok(TokenTypes::Semicolon);
+                        return;
+                    }
+                } else {
+                    let line = self.line_for(s.stmt.span().start);
+                    self.linebreak(line, 1, Whitespace::Ignore, true);
+                }
+                self.stmt(&s.stmt, next_is_rbrace);
+            }
+            Stmt::Expr(x) => self.expr0(x, 1),
+            Stmt::Send(s) => {
+                self.expr0(&s.chan, 1);
+                self.ws(Whitespace::Blank);
+                self.set_pos(self.token_at(s.chan.span().end));
+                self.tok(TokenTypes::Arrow);
+                self.ws(Whitespace::Blank);
+                self.expr0(&s.value, 1);
+            }
+            Stmt::IncDec(s) => {
+                self.expr0(&s.x, 2);
+                self.set_pos(s.span.end - 2);
+                self.tok(s.tok);
+            }
+            Stmt::Assign(s) => {
+                let depth = if s.lhs.len() > 1 && s.rhs.len() > 1 {
+                    2
+                } else {
+                    1
+                };
+                let tok_pos = self.token_at(s.lhs.last().map_or(s.span.start, |x| x.span().end));
+                self.expr_list(Some(s.span.start), &s.lhs, depth, 0, Some(tok_pos));
+                self.ws(Whitespace::Blank);
+                self.set_pos(tok_pos);
+                self.tok(s.tok);
+                self.ws(Whitespace::Blank);
+                self.expr_list(Some(tok_pos), &s.rhs, depth, 0, None);
+            }
+            Stmt::Go(s) => {
+                self.tok(TokenTypes::Go);
+                self.ws(Whitespace::Blank);
+                self.expr(&s.call);
+            }
+            Stmt::Defer(s) => {
+                self.tok(TokenTypes::Defer);
+                self.ws(Whitespace::Blank);
+                self.expr(&s.call);
+            }
+            Stmt::Return(s) => {
+                self.tok(TokenTypes::Return);
+                if !s.results.is_empty() {
+                    self.ws(Whitespace::Blank);
+                    if self.indent_list(&s.results) {
+                        self.ws(Whitespace::Indent);
+                        self.expr_list(None, &s.results, 1, NO_INDENT, None);
+                        self.ws(Whitespace::Unindent);
+                    } else {
+                        self.expr_list(None, &s.results, 1, 0, None);
+                    }
+                }
+            }
+            Stmt::Branch(s) => {
+                self.tok(s.tok);
+                if let Some(label) = &s.label {
+                    self.ws(Whitespace::Blank);
+                    self.ident(label);
+                }
+            }
+            Stmt::Block(b) => self.block(b, 1),
+            Stmt::If(s) => {
+                self.tok(TokenTypes::If);
+                self.control_clause(false, s.init.as_ref(), Some(&s.cond), None);
+                self.block(&s.body, 1);
+                if let Some(els) = &s.els {
+                    self.ws(Whitespace::Blank);
+                    self.tok(TokenTypes::Else);
+                    self.ws(Whitespace::Blank);
+                    match els {
+                        Stmt::Block(_) | Stmt::If(_) => self.stmt(els, next_is_rbrace),
+                        _ => {
+                            self.tok(TokenTypes::LBrace);
+                            self.ws(Whitespace::Indent);
+                            self.ws(Whitespace::Formfeed);
+                            self.stmt(els, true);
+                            self.ws(Whitespace::Unindent);
+                            self.ws(Whitespace::Formfeed);
+                            self.tok(TokenTypes::RBrace);
+                        }
+                    }
+                }
+            }
+            Stmt::Switch(s) => {
+                self.tok(TokenTypes::Switch);
+                self.control_clause(false, s.init.as_ref(), s.tag.as_ref(), None);
+                let lbrace = match (&s.tag, &s.init) {
+                    (Some(tag), _) => self.token_at(tag.span().end),
+                    (None, Some(init)) => self.token_at(self.token_at(init.span().end) + 1),
+                    (None, None) => self.token_at(s.span.start + "switch".len()),
+                };
+                self.braced_list(lbrace, &s.body, s.span.end - 1, 0);
+            }
+            Stmt::TypeSwitch(s) => {
+                self.tok(TokenTypes::Switch);
+                if let Some(init) = &s.init {
+                    self.ws(Whitespace::Blank);
+                    self.stmt(init, false);
+                    self.tok(TokenTypes::Semicolon);
+                }
+                self.ws(Whitespace::Blank);
+                self.stmt(&s.assign, false);
+                self.ws(Whitespace::Blank);
+                let lbrace = self.token_at(s.assign.span().end);
+                self.braced_list(lbrace, &s.body, s.span.end - 1, 0);
+            }
+            Stmt::Select(s) => {
+                self.tok(TokenTypes::Select);
+                self.ws(Whitespace::Blank);
+                let lbrace = self.token_at(s.span.start + "select".len());
+                let rbrace = s.span.end - 1;
+                if s.body.is_empty() && !self.comment_before(self.pos_for(rbrace)) {
+                    self.set_pos(lbrace);
+                    self.tok(TokenTypes::LBrace);
+                    self.set_pos(rbrace);
+                    self.tok(TokenTypes::RBrace);
+                } else {
+                    self.braced_list(lbrace, &s.body, rbrace, 0);
+                }
+            }
+            Stmt::For(s) => {
+                self.tok(TokenTypes::For);
+                self.control_clause(true, s.init.as_ref(), s.cond.as_ref(), s.post.as_ref());
+                self.block(&s.body, 1);
+            }
+            Stmt::Range(s) => {
+                self.tok(TokenTypes::For);
+                self.ws(Whitespace::Blank);
+                if let Some(key) = &s.key {
+                    self.expr(key);
+                    if let Some(value) = &s.value {
+                        self.set_pos(value.span().start);
+                        self.tok(TokenTypes::Comma);
+                        self.ws(Whitespace::Blank);
+                        self.expr(value);
+                    }
+                    self.ws(Whitespace::Blank);
+                    let last = s.value.as_ref().unwrap_or(key);
+                    self.set_pos(self.token_at(last.span().end));
+                    self.tok(s.tok.unwrap_or(TokenTypes::Define));
+                    self.ws(Whitespace::Blank);
+                }
+                self.tok(TokenTypes::Range);
+                self.ws(Whitespace::Blank);
+                self.expr(strip_parens(&s.x));
+                self.ws(Whitespace::Blank);
+                self.block(&s.body, 1);
+            }
+        }
+    }
+
+    // Prints the header of an if, switch or for statement.
+    fn control_clause(
+        &mut self,
+        is_for: bool,
+        init: Option<&Stmt>,
+        expr: Option<&Expr>,
+        post: Option<&Stmt>,
+    ) {
+        self.ws(Whitespace::Blank);
+        let mut needs_blank = false;
+        if init.is_none() && post.is_none() {
+            if let Some(x) = expr {
+                self.expr(strip_parens(x));
+                needs_blank = true;
+            }
+        } else {
+            if let Some(init) = init {
+                self.stmt(init, false);
+            }
+            self.tok(TokenTypes::Semicolon);
+            self.ws(Whitespace::Blank);
+            if let Some(x) = expr {
+                self.expr(strip_parens(x));
+                needs_blank = true;
+            }
+            if is_for {
+                self.tok(TokenTypes::Semicolon);
+                self.ws(Whitespace::Blank);
+                needs_blank = false;
+                if let Some(post) = post {
+                    self.stmt(post, false);
+                    needs_blank = true;
+                }
+            }
+        }
+        if needs_blank {
+            self.ws(Whitespace::Blank);
+        }
+    }
+
+    // Whether the results of a return statement read better indented: when they start on
+    // a later line than the first or several of them span lines.
+    fn indent_list(&self, list: &[Expr]) -> bool {
+        if list.len() < 2 {
+            return false;
+        }
+        let begin = self.line_for(list[0].span().start);
+        let end = self.line_for(list[list.len() - 1].span().end);
+        if begin >= end {
+            return false;
+        }
+        let mut n = 0;
+        let mut line = begin;
+        for x in list {
+            let x_begin = self.line_for(x.span().start);
+            let x_end = self.line_for(x.span().end);
+            if line < x_begin {
+                return true;
+            }
+            if x_begin < x_end {
+                n += 1;
+            }
+            line = x_end;
+        }
+        return n > 1;
+    }
+
+    // ========
+    // Expressions
+    // ========
+
+    pub(super) fn expr(&mut self, x: &Expr) {
+        self.expr1(x, LOWEST_PREC, 1);
+    }
+
+    fn expr0(&mut self, x: &Expr, depth: usize) {
+        self.expr1(x, LOWEST_PREC, depth);
+    }
+
+    fn ident(&mut self, ident: &Ident) {
+        let interner = self.interner;
+        self.set_pos(ident.span.start);
+        self.name(interner.resolve(ident.name));
+    }
+
+    // Prints `expr` where an operator of precedence `prec1` binds it, within `depth`
+    // levels of nested binary expressions and argument lists.
+    fn expr1(&mut self, expr: &Expr, prec1: u8, depth: usize) {
+        self.set_pos(expr.span().start);
+        match expr {
+            Expr::Bad(_) => self.lit(TokenTypes::String, "BadExpr"),
+            Expr::Ident(x) => self.ident(x),
+            Expr::BasicLit(x) => match normalized_number(x) {
+                Some(value) => self.lit(x.kind, &value),
+                None => self.lit(x.kind, &x.value),
+            },
+            Expr::Binary(x) => self.binary_expr(x, prec1, cutoff(x, depth), depth),
+            Expr::KeyValue(x) => {
+                self.expr(&x.key);
+                self.set_pos(self.token_at(x.key.span().end));
+                self.tok(TokenTypes::Colon);
+                self.ws(Whitespace::Blank);
+                self.expr(&x.value);
+            }
+            Expr::Star(x) => {
+                if UNARY_PREC < prec1 {
+                    self.tok(TokenTypes::LParen);
+                    self.tok(TokenTypes::Mul);
+                    self.expr(&x.x);
+                    self.tok(TokenTypes::RParen);
+                } else {
+                    self.tok(TokenTypes::Mul);
+                    self.expr(&x.x);
+                }
+            }
+            Expr::Unary(x) => {
+                if UNARY_PREC < prec1 {
+                    self.tok(TokenTypes::LParen);
+                    self.expr(expr);
+                    self.tok(TokenTypes::RParen);
+                } else {
+                    self.tok(x.op);
+                    self.expr1(&x.x, UNARY_PREC, depth);
+                }
+            }
+            Expr::FuncLit(x) => {
+                self.tok(TokenTypes::Func);
+                let start_col = self.out.column - "func".len();
+                self.signature(&x.ty);
+                let header_size = self.distance_from(x.span.start, start_col);
+                self.func_body(header_size, Whitespace::Blank, Some(&x.body));
+            }
+            Expr::Paren(x) => {
+                // Doubled parentheses are printed once.
+                if let Expr::Paren(_) = &x.x {
+                    self.expr0(&x.x, depth);
+                } else {
+                    self.tok(TokenTypes::LParen);
+                    self.expr0(&x.x, reduce_depth(depth));
+                    self.set_pos(x.span.end - 1);
+                    self.tok(TokenTypes::RParen);
+                }
+            }
+            Expr::Selector(x) => {
+                self.selector_expr(x, depth, false);
+            }
+            Expr::TypeAssert(x) => {
+                self.expr1(&x.x, HIGHEST_PREC, depth);
+                self.tok(TokenTypes::Dot);
+                let lparen = self.token_at(self.token_at(x.x.span().end) + 1);
+                self.set_pos(lparen);
+                self.tok(TokenTypes::LParen);
+                match &x.ty {
+                    Some(ty) => self.expr(ty),
+                    None => self.tok(TokenTypes::Type),
+                }
+                self.set_pos(x.span.end - 1);
+                self.tok(TokenTypes::RParen);
+            }
+            Expr::Index(x) => {
+                self.expr1(&x.x, HIGHEST_PREC, 1);
+                self.set_pos(self.token_at(x.x.span().end));
+                self.tok(TokenTypes::LBrack);
+                self.expr0(&x.index, depth + 1);
+                self.set_pos(x.span.end - 1);
+                self.tok(TokenTypes::RBrack);
+            }
+            Expr::IndexList(x) => {
+                self.expr1(&x.x, HIGHEST_PREC, 1);
+                let lbrack = self.token_at(x.x.span().end);
+                let rbrack = x.span.end - 1;
+                self.set_pos(lbrack);
+                self.tok(TokenTypes::LBrack);
+                self.expr_list(
+                    Some(lbrack),
+                    &x.indices,
+                    depth + 1,
+                    COMMA_TERM,
+                    Some(rbrack),
+                );
+                self.set_pos(rbrack);
+                self.tok(TokenTypes::RBrack);
+            }
+            Expr::Slice(x) => self.slice_expr(x, depth),
+            Expr::Call(x) => self.call_expr(x, depth),
+            Expr::CompositeLit(x) => {
+                if let Some(ty) = &x.ty {
+                    self.expr1(ty, HIGHEST_PREC, depth);
+                }
+                self.level += 1;
+                self.set_pos(x.lbrace.start);
+                self.tok(TokenTypes::LBrace);
+                let (lbrace, rbrace) = (x.lbrace.start, x.rbrace.start);
+                self.expr_list(Some(lbrace), &x.elts, 1, COMMA_TERM, Some(rbrace));
+                // A `/*` comment before the closing brace gets no line break, which would
+                // need a trailing comma, and no blank unless the literal is empty. The
+                // indentation is for comments alone on their lines.
+                let mut mode = NO_EXTRA_LINEBREAK;
+                if !x.elts.is_empty() {
+                    mode |= NO_EXTRA_BLANK;
+                }
+                self.ws(Whitespace::Indent);
+                self.ws(Whitespace::Unindent);
+                self.toggle(mode);
+                self.set_pos(rbrace);
+                self.tok(TokenTypes::RBrace);
+                self.toggle(mode);
+                self.level -= 1;
+            }
+            Expr::Ellipsis(x) => {
+                self.tok(TokenTypes::Ellipsis);
+                if let Some(elt) = &x.elt {
+                    self.expr(elt);
+                }
+            }
+            Expr::ArrayType(x) => {
+                self.tok(TokenTypes::LBrack);
+                if let Some(len) = &x.len {
+                    self.expr(len);
+                }
+                self.tok(TokenTypes::RBrack);
+                self.expr(&x.elt);
+            }
+            Expr::StructType(x) => {
+                self.tok(TokenTypes::Struct);
+                self.field_list(&x.fields, true);
+            }
+            Expr::FuncType(x) => {
+                self.tok(TokenTypes::Func);
+                self.signature(x);
+            }
+            Expr::InterfaceType(x) => {
+                self.tok(TokenTypes::Interface);
+                self.field_list(&x.methods, false);
+            }
+            Expr::MapType(x) => {
+                self.tok(TokenTypes::Map);
+                self.tok(TokenTypes::LBrack);
+                self.expr(&x.key);
+                self.tok(TokenTypes::RBrack);
+                self.expr(&x.value);
+            }
+            Expr::ChanType(x) => {
+                match x.dir {
+                    ChanDir::Both => self.tok(TokenTypes::Chan),
+                    ChanDir::Recv => {
+                        self.tok(TokenTypes::Arrow);
+                        self.tok(TokenTypes::Chan);
+                    }
+                    ChanDir::Send => {
+                        self.tok(TokenTypes::Chan);
+                        self.set_pos(self.token_at(x.span.start + "chan".len()));
+                        self.tok(TokenTypes::Arrow);
+                    }
+                }
+                self.ws(Whitespace::Blank);
+                self.expr(&x.value);
+            }
+        }
+    }
+
+    // Prints a binary expression with blanks around the operators of precedence below
+    // `cutoff`, breaking the line after the operator where the source does.
+    fn binary_expr(&mut self, x: &BinaryExpr, prec1: u8, cutoff: u8, depth: usize) {
+        let prec = x.op.precedence();
+        if prec < prec1 {
+            // The parentheses take away a level of depth.
+            let depth = reduce_depth(depth);
+            self.tok(TokenTypes::LParen);
+            self.set_pos(x.span.start);
+            self.binary_expr(x, LOWEST_PREC, self::cutoff(x, depth), depth);
+            self.tok(TokenTypes::RParen);
+            return;
+        }
+
+        let mut print_blank = prec < cutoff;
+        let mut ws = Whitespace::Indent;
+        self.expr1(&x.x, prec, depth + diff_prec(&x.x, prec));
+        if print_blank {
+            self.ws(Whitespace::Blank);
+        }
+        // The operator may be on the line of the right operand.
+        let x_line = self.pos.line;
+        let y_line = self.line_for(x.y.span().start);
+        self.set_pos(self.token_at(x.x.span().end));
+        self.tok(x.op);
+        if x_line != y_line && x_line > 0 && y_line > 0 && self.linebreak(y_line, 1, ws, true) > 0 {
+            ws = Whitespace::Ignore;
+            print_blank = false;
+        }
+        if print_blank {
+            self.ws(Whitespace::Blank);
+        }
+        self.expr1(&x.y, prec + 1, depth + 1);
+        if ws == Whitespace::Ignore {
+            self.ws(Whitespace::Unindent);
+        }
+    }
+
+    // Prints a selector, with the selected name indented on the next line if it is there
+    // in the source. Returns whether the name was indented and a method call still has to
+    // unindent after its arguments.
+    fn selector_expr(&mut self, x: &SelectorExpr, depth: usize, is_method: bool) -> bool {
+        self.expr1(&x.x, HIGHEST_PREC, depth);
+        self.tok(TokenTypes::Dot);
+        let line = self.line_for(x.sel.span.start);
+        if self.pos.is_valid() && self.pos.line < line {
+            self.ws(Whitespace::Indent);
+            self.ws(Whitespace::Newline);
+            self.ident(&x.sel);
+            if !is_method {
+                self.ws(Whitespace::Unindent);
+            }
+            return true;
+        }
+        self.ident(&x.sel);
+        return false;
+    }
+
+    fn slice_expr(&mut self, x: &SliceExpr, depth: usize) {
+        self.expr1(&x.x, HIGHEST_PREC, 1);
+        self.set_pos(self.token_at(x.x.span().end));
+        self.tok(TokenTypes::LBrack);
+        let mut indices = vec![x.low.as_ref(), x.high.as_ref()];
+        if let Some(max) = &x.max {
+            indices.push(Some(max));
+        }
+        // Blanks go around the colons of an outermost slice with several indices, one of
+        // them a binary expression.
+        let mut needs_blanks = false;
+        if depth <= 1 {
+            let count = indices.iter().flatten().count();
+            let has_binaries = indices
+                .iter()
+                .flatten()
+                .any(|x| matches!(x, Expr::Binary(_)));
+            needs_blanks = count > 1 && has_binaries;
+        }
+        for (i, index) in indices.iter().enumerate() {
+            if i > 0 {
+                if indices[i - 1].is_some() && needs_blanks {
+                    self.ws(Whitespace::Blank);
+                }
+                self.tok(TokenTypes::Colon);
+                if index.is_some() && needs_blanks {
+                    self.ws(Whitespace::Blank);
+                }
+            }
+            if let Some(index) = index {
+                self.expr0(index, depth + 1);
+            }
+        }
+        self.set_pos(x.span.end - 1);
+        self.tok(TokenTypes::RBrack);
+    }
+
+    fn call_expr(&mut self, x: &CallExpr, mut depth: usize) {
+        if x.args.len() > 1 {
+            depth += 1;
+        }
+        // Conversions to function types and receive-only channel types need parentheses
+        // around the type.
+        let paren = match &x.fun {
+            Expr::FuncType(_) => true,
+            Expr::ChanType(t) => t.dir == ChanDir::Recv,
+            _ => false,
+        };
+        if paren {
+            self.tok(TokenTypes::LParen);
+        }
+        let was_indented = match &x.fun {
+            Expr::Selector(sel) => self.selector_expr(sel, depth, true),
+            fun => {
+                self.expr1(fun, HIGHEST_PREC, depth);
+                false
+            }
+        };
+        if paren {
+            self.tok(TokenTypes::RParen);
+        }
+        let lparen = self.token_at(x.fun.span().end);
+        let rparen = x.span.end - 1;
+        self.set_pos(lparen);
+        self.tok(TokenTypes::LParen);
+        match x.ellipsis {
+            Some(ellipsis) => {
+                self.expr_list(Some(lparen), &x.args, depth, 0, Some(ellipsis.start));
+                self.set_pos(ellipsis.start);
+                self.tok(TokenTypes::Ellipsis);
+                if self.line_for(ellipsis.start) < self.line_for(rparen) {
+                    self.tok(TokenTypes::Comma);
+                    self.ws(Whitespace::Formfeed);
+                }
+            }
+            None => self.expr_list(Some(lparen), &x.args, depth, COMMA_TERM, Some(rparen)),
+        }
+        self.set_pos(rparen);
+        self.tok(TokenTypes::RParen);
+        if was_indented {
+            self.ws(Whitespace::Unindent);
+        }
+    }
+
+    // Prints a comma-separated list between the tokens at `prev` and `next`, keeping the
+    // line breaks of the source. Entries on lines of their own are aligned in columns,
+    // with the keys of key-value pairs in a column of their own, unless their sizes differ
+    // too much.
+    fn expr_list(
+        &mut self,
+        prev: Option<usize>,
+        list: &[Expr],
+        depth: usize,
+        mode: u8,
+        next: Option<usize>,
+    ) {
+        if list.is_empty() {
+            return;
+        }
+        let prev = prev.map(|offset| self.pos_for(offset)).unwrap_or_default();
+        let next = next.map(|offset| self.pos_for(offset)).unwrap_or_default();
+        let mut line = self.line_for(list[0].span().start);
+        let end_line = self.line_for(list[list.len() - 1].span().end);
+
+        if prev.is_valid() && prev.line == line && line == end_line {
+            for (i, x) in list.iter().enumerate() {
+                if i > 0 {
+                    // The comma takes the position of the next entry, for the comments.
+                    self.set_pos(x.span().start);
+                    self.tok(TokenTypes::Comma);
+                    self.ws(Whitespace::Blank);
+                }
+                self.expr0(x, depth);
+            }
+            return;
+        }
+
+        let mut ws = match mode & NO_INDENT {
+            0 => Whitespace::Indent,
+            _ => Whitespace::Ignore,
+        };
+        // The index of the last entry followed by a line break.
+        let mut prev_break: isize = -1;
+        if prev.is_valid() && prev.line < line && self.linebreak(line, 0, ws, true) > 0 {
+            ws = Whitespace::Ignore;
+            prev_break = 0;
+        }
+
+        // The size of the entry, or of its key, on one line, or 0 if it does not fit. The
+        // alignment breaks where it is far from the geometric mean of the sizes before.
+        const MAX_SIZE: usize = 1_000_000;
+        const SMALL_SIZE: usize = 40;
+        const RATIO: f64 = 2.5;
+        let mut size = 0;
+        let mut lnsum = 0.0;
+        let mut count = 0;
+
+        let mut prev_line = prev.line;
+        for (i, x) in list.iter().enumerate() {
+            line = self.line_for(x.span().start);
+            let pair = match x {
+                Expr::KeyValue(pair) => Some(pair),
+                _ => None,
+            };
+            let prev_size = size;
+            size = 0;
+            if prev.is_valid() && next.is_valid() {
+                size = self.node_size(Node::Expr(x), MAX_SIZE);
+                if size > MAX_SIZE {
+                    size = 0;
+                } else if let Some(pair) = pair {
+                    size = self.node_size(Node::Expr(&pair.key), MAX_SIZE);
+                }
+            }
+
+            let mut use_ff = true;
+            if prev_size > 0 && size > 0 {
+                if count == 0 || prev_size <= SMALL_SIZE && size <= SMALL_SIZE {
+                    use_ff = false;
+                } else {
+                    let geomean = (lnsum / count as f64).exp();
+                    let ratio = size as f64 / geomean;
+                    use_ff = RATIO * ratio <= 1.0 || RATIO <= ratio;
+                }
+            }
+
+            let needs_linebreak = 0 < prev_line && prev_line < line;
+            if i > 0 {
+                if !needs_linebreak {
+                    self.set_pos(x.span().start);
+                }
+                self.tok(TokenTypes::Comma);
+                let mut needs_blank = true;
+                if needs_linebreak {
+                    // Several entries on a line end the alignment as well.
+                    let new_section = use_ff || prev_break + 1 < i as isize;
+                    let nbreaks = self.linebreak(line, 0, ws, new_section);
+                    if nbreaks > 0 {
+                        ws = Whitespace::Ignore;
+                        prev_break = i as isize;
+                        needs_blank = false;
+                    }
+                    // The tabwriter ends the alignment at a blank line too.
+                    if nbreaks > 1 {
+                        lnsum = 0.0;
+                        count = 0;
+                    }
+                }
+                if needs_blank {
+                    self.ws(Whitespace::Blank);
+                }
+            }
+
+            match pair {
+                Some(pair) if list.len() > 1 && size > 0 && needs_linebreak => {
+                    self.expr(&pair.key);
+                    self.set_pos(self.token_at(pair.key.span().end));
+                    self.tok(TokenTypes::Colon);
+                    self.ws(Whitespace::Vtab);
+                    self.expr(&pair.value);
+                }
+                _ => self.expr0(x, depth),
+            }
+
+            if size > 0 {
+                lnsum += (size as f64).ln();
+                count += 1;
+            }
+            prev_line = self.line_for(x.span().end);
+        }
+
+        let indented = ws == Whitespace::Ignore && mode & NO_INDENT == 0;
+        if mode & COMMA_TERM != 0 && next.is_valid() && self.pos.line < next.line {
+            // The list ends on a line of its own.
+            self.tok(TokenTypes::Comma);
+            if indented {
+                self.ws(Whitespace::Unindent);
+            }
+            self.ws(Whitespace::Formfeed);
+            return;
+        }
+        if indented {
+            self.ws(Whitespace::Unindent);
+        }
+    }
+
+    fn ident_list(&mut self, list: &[Ident], indent: bool) {
+        let list: Vec<Expr> = list.iter().map(|&ident| Expr::Ident(ident)).collect();
+        let mode = if indent { 0 } else { NO_INDENT };
+        self.expr_list(None, &list, 1, mode, None);
+    }
+
+    // ========
+    // Types
+    // ========
+
+    fn parameters(&mut self, fields: &FieldList, mode: ParamMode) {
+        let (open, close) = match mode {
+            ParamMode::Func => (TokenTypes::LParen, TokenTypes::RParen),
+            ParamMode::FuncTParam | ParamMode::TypeTParam => {
+                (TokenTypes::LBrack, TokenTypes::RBrack)
+            }
+        };
+        let opening = fields.opening.map_or(fields.span.start, |s| s.start);
+        let closing = fields.closing.map_or(fields.span.end - 1, |s| s.start);
+        self.set_pos(opening);
+        self.tok(open);
+        if !fields.fields.is_empty() {
+            let mut prev_line = self.line_for(opening);
+            let mut ws = Whitespace::Indent;
+            for (i, par) in fields.fields.iter().enumerate() {
+                let line = self.line_for(par.span.start);
+                let needs_linebreak = 0 < prev_line && prev_line < line;
+                if i > 0 {
+                    if !needs_linebreak {
+                        self.set_pos(par.span.start);
+                    }
+                    self.tok(TokenTypes::Comma);
+                }
+                if needs_linebreak && self.linebreak(line, 0, ws, true) > 0 {
+                    ws = Whitespace::Ignore;
+                } else if i > 0 {
+                    self.ws(Whitespace::Blank);
+                }
+                if !par.names.is_empty() {
+                    self.ident_list(&par.names, ws == Whitespace::Indent);
+                    self.ws(Whitespace::Blank);
+                }
+                self.expr(strip_parens_always(&par.ty));
+                prev_line = self.line_for(par.ty.span().start);
+            }
+
+            let closing_line = self.line_for(closing);
+            if 0 < prev_line && prev_line < closing_line {
+                self.tok(TokenTypes::Comma);
+                self.linebreak(closing_line, 0, Whitespace::Ignore, true);
+            } else if mode == ParamMode::TypeTParam
+                && num_fields(fields) == 1
+                && combines_with_name(&fields.fields[0].ty)
+            {
+                // In `[P *T,]` the comma keeps the parameter from reading as an array
+                // length.
+                self.tok(TokenTypes::Comma);
+            }
+            if ws == Whitespace::Ignore {
+                self.ws(Whitespace::Unindent);
+            }
+        }
+        self.set_pos(closing);
+        self.tok(close);
+    }
+
+    fn signature(&mut self, sig: &FuncType) {
+        if let Some(params) = &sig.type_params {
+            self.parameters(params, ParamMode::FuncTParam);
+        }
+        self.parameters(&sig.params, ParamMode::Func);
+        let Some(results) = &sig.results else {
+            return;
+        };
+        let n = num_fields(results);
+        if n > 0 {
+            self.ws(Whitespace::Blank);
+            if n == 1 && results.fields[0].names.is_empty() {
+                // A single unnamed result needs no parentheses.
+                self.expr(strip_parens_always(&results.fields[0].ty));
+                return;
+            }
+            self.parameters(results, ParamMode::Func);
+        }
+    }
+
+    // Prints the fields of a struct or the elements of an interface.
+    fn field_list(&mut self, fields: &FieldList, is_struct: bool) {
+        let lbrace = fields.opening.map_or(fields.span.start, |s| s.start);
+        let rbrace = fields.closing.map_or(fields.span.end - 1, |s| s.start);
+        let list = &fields.fields;
+        let has_comments = self.comment_before(self.pos_for(rbrace));
+        let one_line = self.line_for(lbrace) == self.line_for(rbrace);
+
+        if !has_comments && one_line {
+            if list.is_empty() {
+                self.set_pos(lbrace);
+                self.tok(TokenTypes::LBrace);
+                self.set_pos(rbrace);
+                self.tok(TokenTypes::RBrace);
+                return;
+            }
+            if self.is_one_line_field_list(list) {
+                self.set_pos(lbrace);
+                self.tok(TokenTypes::LBrace);
+                self.ws(Whitespace::Blank);
+                let f = &list[0];
+                if is_struct {
+                    for (i, name) in f.names.iter().enumerate() {
+                        if i > 0 {
+                            self.tok(TokenTypes::Comma);
+                            self.ws(Whitespace::Blank);
+                        }
+                        self.ident(name);
+                    }
+                    if !f.names.is_empty() {
+                        self.ws(Whitespace::Blank);
+                    }
+                    self.expr(&f.ty);
+                } else {
+                    self.interface_elem(f);
+                }
+                self.ws(Whitespace::Blank);
+                self.set_pos(rbrace);
+                self.tok(TokenTypes::RBrace);
+                return;
+            }
+        }
+
+        self.ws(Whitespace::Blank);
+        self.set_pos(lbrace);
+        self.tok(TokenTypes::LBrace);
+        self.ws(Whitespace::Indent);
+        if has_comments || !list.is_empty() {
+            self.ws(Whitespace::Formfeed);
+        }
+
+        // A single field is not aligned with anything.
+        let sep = match list.len() {
+            1 => Whitespace::Blank,
+            _ => Whitespace::Vtab,
+        };
+        let line = self.line_slot();
+        for (i, f) in list.iter().enumerate() {
+            if i > 0 {
+                let new_section = self.lines_from(line) > 0;
+                self.linebreak(
+                    self.line_for(f.span.start),
+                    1,
+                    Whitespace::Ignore,
+                    new_section,
+                );
+            }
+            self.record_line(line);
+            if !is_struct {
+                self.interface_elem(f);
+                continue;
+            }
+
+            // Names, types, tags and line comments are in columns.
+            let mut extra_tabs;
+            if f.names.is_empty() {
+                self.expr(&f.ty);
+                extra_tabs = 2;
+            } else {
+                self.ident_list(&f.names, false);
+                self.ws(sep);
+                self.expr(&f.ty);
+                extra_tabs = 1;
+            }
+            let mut end = f.span.end;
+            if let Some(tag) = &f.tag {
+                if !f.names.is_empty() && sep == Whitespace::Vtab {
+                    self.ws(sep);
+                }
+                self.ws(sep);
+                self.set_pos(tag.span.start);
+                self.lit(tag.kind, &tag.value);
+                end = end.max(tag.span.end);
+                extra_tabs = 0;
+            }
+            if self.has_line_comment(end) {
+                for _ in 0..extra_tabs {
+                    self.ws(sep);
+                }
+            }
+        }
+        self.ws(Whitespace::Unindent);
+        self.ws(Whitespace::Formfeed);
+        self.set_pos(rbrace);
+        self.tok(TokenTypes::RBrace);
+    }
+
+    // Prints a method of an interface, or an embedded type or type union.
+    fn interface_elem(&mut self, f: &Field) {
+        match (f.names.first(), &f.ty) {
+            (Some(name), Expr::FuncType(sig)) => {
+                self.ident(name);
+                self.signature(sig);
+            }
+            _ => self.expr(&f.ty),
+        }
+    }
+
+    fn is_one_line_field_list(&mut self, list: &[Field]) -> bool {
+        if list.len() != 1 {
+            return false;
+        }
+        let f = &list[0];
+        if f.tag.is_some() || self.has_line_comment(f.span.end) {
+            return false;
+        }
+        // Only whether there are names counts, not how long they are.
+        const MAX_SIZE: usize = 30;
+        let names_size = usize::from(!f.names.is_empty());
+        let type_size = self.node_size(Node::Expr(&f.ty), MAX_SIZE);
+        return names_size + type_size <= MAX_SIZE;
+    }
+}
+
+// ========
+// Helpers
+// ========
+
+fn reduce_depth(depth: usize) -> usize {
+    return usize::max(depth - 1, 1);
+}
+
+fn diff_prec(x: &Expr, prec: u8) -> usize {
+    return match x {
+        Expr::Binary(x) if x.op.precedence() == prec => 0,
+        _ => 1,
+    };
+}
+
+// Walks the operands of a binary expression that are printed without parentheses.
+// Returns whether there are operators of precedence 4 and 5, and the precedence from
+// which blanks are needed so that no two operators read as one, as in `x / *p`.
+fn walk_binary(e: &BinaryExpr) -> (bool, bool, u8) {
+    let prec = e.op.precedence();
+    let mut has4 = prec == 4;
+    let mut has5 = prec == 5;
+    let mut max_problem = 0;
+
+    if let Expr::Binary(l) = &e.x {
+        if l.op.precedence() >= prec {
+            let (h4, h5, mp) = walk_binary(l);
+            has4 |= h4;
+            has5 |= h5;
+            max_problem = max_problem.max(mp);
+        }
+    }
+    match &e.y {
+        Expr::Binary(r) if r.op.precedence() > prec => {
+            let (h4, h5, mp) = walk_binary(r);
+            has4 |= h4;
+            has5 |= h5;
+            max_problem = max_problem.max(mp);
+        }
+        Expr::Star(_) if e.op == TokenTypes::Quo => max_problem = 5,
+        Expr::Unary(r) => match (e.op, r.op) {
+            (TokenTypes::Quo, TokenTypes::Mul)
+            | (TokenTypes::Amp, TokenTypes::Amp)
+            | (TokenTypes::Amp, TokenTypes::Caret) => max_problem = 5,
+            (TokenTypes::Add, TokenTypes::Add) | (TokenTypes::Sub, TokenTypes::Sub) => {
+                max_problem = max_problem.max(4)
+            }
+            _ => {}
+        },
+        _ => {}
+    }
+    return (has4, has5, max_problem);
+}
+
+// The precedence from which operators of a binary expression are printed with blanks:
+// all of them at the top level, except that the tighter ones go without blanks when they
+// mix with looser ones, as in `a*b + c`.
+fn cutoff(e: &BinaryExpr, depth: usize) -> u8 {
+    let (has4, has5, max_problem) = walk_binary(e);
+    if max_problem > 0 {
+        return max_problem + 1;
+    }
+    if has4 && has5 {
+        return if depth == 1 { 5 } else { 4 };
+    }
+    return if depth == 1 { 6 } else { 4 };
+}
+
+fn num_fields(fields: &FieldList) -> usize {
+    return fields.fields.iter().map(|f| f.names.len().max(1)).sum();
+}
+
+fn is_type_name(x: &Expr) -> bool {
+    return match x {
+        Expr::Ident(_) => true,
+        Expr::Selector(x) => is_type_name(&x.x),
+        _ => false,
+    };
+}
+
+fn is_type_elem(x: &Expr) -> bool {
+    return match x {
+        Expr::ArrayType(_)
+        | Expr::StructType(_)
+        | Expr::FuncType(_)
+        | Expr::InterfaceType(_)
+        | Expr::MapType(_)
+        | Expr::ChanType(_) => true,
+        Expr::Unary(x) => x.op == TokenTypes::Tilde,
+        Expr::Binary(x) => is_type_elem(&x.x) || is_type_elem(&x.y),
+        Expr::Paren(x) => is_type_elem(&x.x),
+        _ => false,
+    };
+}
+
+// Whether the constraint of a lone type parameter would combine with its name into an
+// expression, as `P *T` reads as a multiplication.
+fn combines_with_name(x: &Expr) -> bool {
+    return match x {
+        Expr::Star(x) => !is_type_elem(&x.x),
+        Expr::Binary(x) => combines_with_name(&x.x) && !is_type_elem(&x.y),
+        Expr::Paren(x) => !is_type_elem(&x.x),
+        _ => false,
+    };
+}
+
+// Looks for a composite literal with a type name outside of parentheses, which would
+// read as a block if its parentheses were removed.
+struct TypedLiteralFinder {
+    found: bool,
+}
+
+impl Visitor for TypedLiteralFinder {
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Paren(_) => {}
+            Expr::CompositeLit(x) => {
+                if x.ty.as_ref().is_some_and(is_type_name) {
+                    self.found = true;
+                }
+            }
+            _ => walk_expr(self, expr),
+        }
+    }
+}
+
+// Removes the parentheses around the expression of a control clause where they are not
+// needed.
+fn strip_parens(x: &Expr) -> &Expr {
+    if let Expr::Paren(px) = x {
+        let mut finder = TypedLiteralFinder { found: false };
+        finder.visit_expr(&px.x);
+        if !finder.found {
+            return strip_parens(&px.x);
+        }
+    }
+    return x;
+}
+
+fn strip_parens_always(x: &Expr) -> &Expr {
+    return match x {
+        Expr::Paren(px) => strip_parens_always(&px.x),
+        _ => x,
+    };
+}
+
+// Keeps the type column of a run of specs with values in a const or var group when one
+// of them has a type.
+fn keep_type_column(specs: &[Spec]) -> Vec<bool> {
+    let mut keep = vec![false; specs.len()];
+    let mut populate = |start: usize, end: usize, keep_type: bool| {
+        if keep_type {
+            keep[start..end].fill(true);
+        }
+    };
+    // The start of the current run.
+    let mut run: Option<usize> = None;
+    let mut keep_type = false;
+    for (i, spec) in specs.iter().enumerate() {
+        let Spec::Value(s) = spec else {
+            continue;
+        };
+        if !s.values.is_empty() {
+            if run.is_none() {
+                run = Some(i);
+                keep_type = false;
+            }
+        } else if let Some(start) = run.take() {
+            populate(start, i, keep_type);
+        }
+        if s.ty.is_some() {
+            keep_type = true;
+        }
+    }
+    if let Some(start) = run {
+        populate(start, specs.len(), keep_type);
+    }
+    return keep;
+}
+
+// The canonical spelling of a number: lowercase prefixes and exponents, and no leading
+// zeros in an integer imaginary literal. None if it is already canonical.
+fn normalized_number(lit: &BasicLit) -> Option<String> {
+    if !matches!(
+        lit.kind,
+        TokenTypes::Int
+            | TokenTypes::Octal
+            | TokenTypes::Hex
+            | TokenTypes::Binary
+            | TokenTypes::Float
+            | TokenTypes::Imag
+    ) || lit.value.len() < 2
+    {
+        return None;
+    }
+    let x = lit.value.as_str();
+    let lower_last = |x: &str, c: char| match x.rfind(c) {
+        Some(i) => format!("{}{}{}", &x[..i], c.to_ascii_lowercase(), &x[i + 1..]),
+        None => x.to_string(),
+    };
+    let normalized = match &x[..2] {
+        "0X" => lower_last(&format!("0x{}", &x[2..]), 'P'),
+        "0x" => lower_last(x, 'P'),
+        "0O" => format!("0o{}", &x[2..]),
+        "0B" => format!("0b{}", &x[2..]),
+        "0o" | "0b" => x.to_string(),
+        _ if x.contains('E') => lower_last(x, 'E'),
+        _ if x.ends_with('i') && !x.contains(['.', 'e']) => {
+            match x.trim_start_matches(['0', '_']) {
+                "i" => "0i".to_string(),
+                x => x.to_string(),
+            }
+        }
+        _ => x.to_string(),
+    };
+    return (normalized != lit.value).then_some(normalized);
+}
+
+// The import path as an interpreted string, for a raw string with a valid path. None if
+// it is kept as written.
+fn sanitize_import_path(lit: &BasicLit) -> Option<String> {
+    if lit.kind != TokenTypes::RawString || lit.value.len() < 2 {
+        return None;
+    }
+    let path = &lit.value[1..lit.value.len() - 1];
+    const ILLEGAL: &str = "!\"#$%&'()*,:;<=>?[\\]^{|}`\u{fffd}";
+    if path.is_empty()
+        || path
+            .chars()
+            .any(|c| c.is_control() || c.is_whitespace() || ILLEGAL.contains(c))
+    {
+        return None;
+    }
+    return Some(format!("\"{}\"", path));
+}
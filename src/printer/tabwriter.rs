@@ -0,0 +1,224 @@
+// Copyright 2009 The Go Authors. All rights reserved.
+// Use of this source code is governed by a BSD-style license that can be found in the
+// LICENSE-go file.
+
+// Column alignment for the printer, ported from Go's text/tabwriter with the settings
+// gofmt uses: no minimum width, tab width 8, padding 1, blanks for padding, empty
+// columns discarded and leading empty cells written as tabs.
+//
+// Text is split into cells terminated by a horizontal tab (a hard cell) or a vertical
+// tab (a soft cell). A column block is a run of consecutive lines that all have a cell in
+// that column, and all cells of a block are padded to the same width. A formfeed ends the
+// line and every open column block. Text between two ESCAPE bytes is taken literally.
+
+use std::mem;
+
+pub const ESCAPE: u8 = 0xff;
+
+const TAB_WIDTH: usize = 8;
+const PADDING: usize = 1;
+
+#[derive(Debug, Default, Clone, Copy)]
+struct Cell {
+    // The size of the cell in bytes.
+    size: usize,
+    // The width of the cell in characters.
+    width: usize,
+    // Set when the cell is terminated by a horizontal tab.
+    htab: bool,
+}
+
+pub struct TabWriter {
+    output: Vec<u8>,
+    // The text of all cells, without tabs or line breaks.
+    buf: Vec<u8>,
+    // The position in `buf` up to which the width of `cell` has been counted.
+    pos: usize,
+    cell: Cell,
+    escaped: bool,
+    lines: Vec<Vec<Cell>>,
+    widths: Vec<usize>,
+}
+
+impl TabWriter {
+    pub fn new() -> TabWriter {
+        TabWriter {
+            output: Vec::new(),
+            buf: Vec::new(),
+            pos: 0,
+            cell: Cell::default(),
+            escaped: false,
+            lines: vec![Vec::new()],
+            widths: Vec::new(),
+        }
+    }
+
+    pub fn write(&mut self, data: &[u8]) {
+        let mut n = 0;
+        for (i, &ch) in data.iter().enumerate() {
+            if self.escaped {
+                if ch == ESCAPE {
+                    self.append(&data[n..=i]);
+                    n = i + 1;
+                    self.end_escape();
+                }
+                continue;
+            }
+            match ch {
+                b'\t' | b'\x0b' | b'\n' | b'\x0c' => {
+                    self.append(&data[n..i]);
+                    self.update_width();
+                    n = i + 1;
+                    let ncells = self.terminate_cell(ch == b'\t');
+                    if ch == b'\n' || ch == b'\x0c' {
+                        self.lines.push(Vec::new());
+                        // A formfeed ends all column blocks. A line with a single cell
+                        // does not take part in any block, so it ends them as well.
+                        if ch == b'\x0c' || ncells == 1 {
+                            self.flush();
+                        }
+                    }
+                }
+                ESCAPE => {
+                    self.append(&data[n..i]);
+                    self.update_width();
+                    n = i;
+                    self.escaped = true;
+                }
+                _ => {}
+            }
+        }
+        self.append(&data[n..]);
+    }
+
+    // Writes out all buffered text and returns the output.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.flush();
+        return self.output;
+    }
+
+    fn append(&mut self, text: &[u8]) {
+        self.buf.extend_from_slice(text);
+        self.cell.size += text.len();
+    }
+
+    fn update_width(&mut self) {
+        self.cell.width += char_count(&self.buf[self.pos..]);
+        self.pos = self.buf.len();
+    }
+
+    fn end_escape(&mut self) {
+        self.update_width();
+        // The ESCAPE bytes themselves take no room.
+        self.cell.width -= 2;
+        self.escaped = false;
+    }
+
+    // Adds the current cell to the current line and returns the number of cells in it.
+    fn terminate_cell(&mut self, htab: bool) -> usize {
+        self.cell.htab = htab;
+        let line = self.lines.last_mut().unwrap();
+        line.push(mem::take(&mut self.cell));
+        return line.len();
+    }
+
+    fn flush(&mut self) {
+        if self.cell.size > 0 {
+            if self.escaped {
+                self.end_escape();
+            }
+            self.terminate_cell(false);
+        }
+        self.format(0, 0, self.lines.len());
+        self.buf.clear();
+        self.pos = 0;
+        self.cell = Cell::default();
+        self.lines = vec![Vec::new()];
+        self.widths.clear();
+    }
+
+    // Writes lines `line0..line1`, whose text starts at `pos` in `buf`, aligning the
+    // column after those in `widths`. Returns the position after the text written.
+    fn format(&mut self, mut pos: usize, mut line0: usize, line1: usize) -> usize {
+        let column = self.widths.len();
+        let mut this = line0;
+        while this < line1 {
+            // The last cell of a line is not tab-terminated and belongs to no column.
+            if column + 1 >= self.lines[this].len() {
+                this += 1;
+                continue;
+            }
+
+            pos = self.write_lines(pos, line0, this);
+            line0 = this;
+
+            let mut width = 0;
+            let mut discardable = true;
+            while this < line1 && column + 1 < self.lines[this].len() {
+                let cell = self.lines[this][column];
+                width = usize::max(width, cell.width + PADDING);
+                if cell.width > 0 || cell.htab {
+                    discardable = false;
+                }
+                this += 1;
+            }
+            if discardable {
+                width = 0;
+            }
+
+            self.widths.push(width);
+            pos = self.format(pos, line0, this);
+            self.widths.pop();
+            line0 = this;
+        }
+        return self.write_lines(pos, line0, line1);
+    }
+
+    fn write_lines(&mut self, mut pos: usize, line0: usize, line1: usize) -> usize {
+        for i in line0..line1 {
+            // Leading empty cells are indentation and padded with tabs.
+            let mut use_tabs = true;
+            for j in 0..self.lines[i].len() {
+                let cell = self.lines[i][j];
+                if cell.size > 0 {
+                    use_tabs = false;
+                    self.output
+                        .extend_from_slice(&self.buf[pos..pos + cell.size]);
+                    pos += cell.size;
+                }
+                if let Some(&width) = self.widths.get(j) {
+                    self.write_padding(cell.width, width, use_tabs);
+                }
+            }
+            if i + 1 == self.lines.len() {
+                // The last line has no line break yet.
+                self.output
+                    .extend_from_slice(&self.buf[pos..pos + self.cell.size]);
+                pos += self.cell.size;
+            } else {
+                self.output.push(b'\n');
+            }
+        }
+        return pos;
+    }
+
+    fn write_padding(&mut self, text_width: usize, cell_width: usize, use_tabs: bool) {
+        if use_tabs {
+            let cell_width = cell_width.div_ceil(TAB_WIDTH) * TAB_WIDTH;
+            let tabs = (cell_width - text_width).div_ceil(TAB_WIDTH);
+            self.output.extend(std::iter::repeat_n(b'\t', tabs));
+            return;
+        }
+        self.output
+            .extend(std::iter::repeat_n(b' ', cell_width - text_width));
+    }
+}
+
+// The number of characters in `text`, counting each byte of an invalid sequence as one.
+fn char_count(text: &[u8]) -> usize {
+    let mut count = 0;
+    for chunk in text.utf8_chunks() {
+        count += chunk.valid().chars().count() + chunk.invalid().len();
+    }
+    return count;
+}
@@ -0,0 +1,234 @@
+use crate::printer::format_source;
+use crate::source::FileId;
+use std::fs;
+
+fn format(src: &str) -> String {
+    let out = format_source(src.as_bytes(), FileId(0)).unwrap();
+    return String::from_utf8(out).unwrap();
+}
+
+// Checks that `src` formats to `expected`, and that formatting that again changes nothing.
+fn check(src: &str, expected: &str) {
+    let out = format(src);
+    assert_eq!(out, expected);
+    assert_eq!(format(&out), expected, "not idempotent");
+}
+
+// =====
+// Golden files
+// =====
+
+// Panics with the first line where `out`, the output for `path`, differs from `want`.
+fn assert_same(path: &str, want: &[u8], out: &[u8]) {
+    if out == want {
+        return;
+    }
+    let (want, out) = (String::from_utf8_lossy(want), String::from_utf8_lossy(out));
+    let line = want
+        .split_inclusive('\n')
+        .zip(out.split_inclusive('\n'))
+        .position(|(a, b)| a != b)
+        .unwrap_or(want.lines().count().min(out.lines().count()));
+    panic!(
+        "{} differs at line {}:\nwant: {:?}\ngot:  {:?}",
+        path,
+        line + 1,
+        want.split_inclusive('\n').nth(line),
+        out.split_inclusive('\n').nth(line)
+    );
+}
+
+// Each `.input` file in src/test_files/fmt formats to its `.golden` file byte for byte,
+// and the golden file is left as it is. The golden files were written by hand following
+// gofmt's rules; with a Go toolchain, `gofmt < name.input | diff - name.golden` checks
+// them.
+#[test]
+fn format_golden_files() {
+    let mut checked = 0;
+    for entry in fs::read_dir("src/test_files/fmt").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_none_or(|ext| ext != "input") {
+            continue;
+        }
+        let src = fs::read(&path).unwrap();
+        let golden_path = path.with_extension("golden");
+        let golden = fs::read(&golden_path).unwrap();
+        let out = format_source(&src, FileId(0)).unwrap();
+        assert_same(&path.to_string_lossy(), &golden, &out);
+        let out = format_source(&golden, FileId(0)).unwrap();
+        assert_same(&golden_path.to_string_lossy(), &golden, &out);
+        checked += 1;
+    }
+    assert_eq!(checked, 5);
+}
+
+// The test files are already in gofmt style, so formatting leaves them unchanged.
+#[test]
+fn format_test_files() {
+    for path in [
+        "src/test_files/implements.go",
+        "src/test_files/hugeparams.go",
+        "src/test_files/generics.go",
+    ] {
+        let src = fs::read(path).unwrap();
+        let out = format_source(&src, FileId(0)).unwrap();
+        assert_same(path, &src, &out);
+    }
+}
+
+#[test]
+fn syntax_errors_are_reported() {
+    let diagnostics =
+        format_source(b"package p\n\nfunc f() {\n\tx := )\n}\n", FileId(0)).unwrap_err();
+    assert!(!diagnostics.is_empty());
+}
+
+// =====
+// Layout
+// =====
+
+#[test]
+fn indent_with_tabs() {
+    check(
+        "package p\nfunc f() {\n    if x {\n        return\n    }\n}\n",
+        "package p\n\nfunc f() {\n\tif x {\n\t\treturn\n\t}\n}\n",
+    );
+}
+
+#[test]
+fn keep_at_most_one_blank_line() {
+    check(
+        "package p\n\n\n\nvar a = 1\n\n\n\nvar b = 2\n",
+        "package p\n\nvar a = 1\n\nvar b = 2\n",
+    );
+}
+
+#[test]
+fn short_function_bodies_stay_on_one_line() {
+    check(
+        "package p\nfunc f() int { return 1 }\nfunc g() {\n}\n",
+        "package p\n\nfunc f() int { return 1 }\nfunc g() {\n}\n",
+    );
+}
+
+#[test]
+fn blanks_around_binary_operators() {
+    check(
+        "package p\n\nvar a = b*c+d\nvar e = f(g+h, i*j)\nvar k = x[1:n+1]\n",
+        "package p\n\nvar a = b*c + d\nvar e = f(g+h, i*j)\nvar k = x[1 : n+1]\n",
+    );
+}
+
+#[test]
+fn normalize_number_literals() {
+    check(
+        "package p\n\nvar a = 0X1F + 0O17 + 0B1 + 1E3 + 0x1P4 + 007i\n",
+        "package p\n\nvar a = 0x1F + 0o17 + 0b1 + 1e3 + 0x1p4 + 7i\n",
+    );
+}
+
+#[test]
+fn collapse_redundant_parentheses() {
+    check(
+        "package p\n\nfunc f() {\n\tif (x) {\n\t}\n\tfor ((y)) {\n\t}\n}\n",
+        "package p\n\nfunc f() {\n\tif x {\n\t}\n\tfor y {\n\t}\n}\n",
+    );
+}
+
+#[test]
+fn strip_carriage_returns() {
+    check(
+        "package p\r\n\r\nfunc f() {\r\n\tx := `a\r\nb` // c\r\n\t/* d\r\n\t */\r\n}\r\n",
+        "package p\n\nfunc f() {\n\tx := `a\nb` // c\n\t/* d\n\t */\n}\n",
+    );
+}
+
+// =====
+// Alignment
+// =====
+
+#[test]
+fn align_struct_fields_and_comments() {
+    check(
+        "package p\n\ntype T struct {\n\tName string `json:\"name\"`\n\tAge int // years\n\tX, Y float64\n}\n",
+        "package p\n\ntype T struct {\n\tName string `json:\"name\"`\n\tAge  int    // years\n\tX, Y float64\n}\n",
+    );
+}
+
+#[test]
+fn blank_line_ends_alignment() {
+    check(
+        "package p\n\ntype T struct {\n\tA int\n\n\tLonger string\n\tB bool\n}\n",
+        "package p\n\ntype T struct {\n\tA int\n\n\tLonger string\n\tB      bool\n}\n",
+    );
+}
+
+#[test]
+fn align_const_groups() {
+    check(
+        "package p\n\nconst (\n\tA = iota // a\n\tLongName // b\n\tC\n)\n",
+        "package p\n\nconst (\n\tA        = iota // a\n\tLongName        // b\n\tC\n)\n",
+    );
+}
+
+#[test]
+fn align_composite_literal_keys() {
+    check(
+        "package p\n\nvar m = map[string]int{\n\"a\": 1,\n\"long\": 2, // two\n}\n",
+        "package p\n\nvar m = map[string]int{\n\t\"a\":    1,\n\t\"long\": 2, // two\n}\n",
+    );
+}
+
+// =====
+// Imports
+// =====
+
+#[test]
+fn sort_and_deduplicate_imports() {
+    check(
+        "package p\n\nimport (\n\t\"strings\"\n\tb \"bytes\"\n\t\"fmt\" // printing\n\t\"strings\"\n\n\t\"os\"\n\t\"io\"\n)\n",
+        "package p\n\nimport (\n\tb \"bytes\"\n\t\"fmt\" // printing\n\t\"strings\"\n\n\t\"io\"\n\t\"os\"\n)\n",
+    );
+}
+
+#[test]
+fn raw_import_paths_become_strings() {
+    check(
+        "package p\n\nimport `fmt`\n",
+        "package p\n\nimport \"fmt\"\n",
+    );
+}
+
+// =====
+// Comments
+// =====
+
+#[test]
+fn keep_comments_in_place() {
+    let src = "// Package p is a package.\npackage p\n\nfunc f() {\n\t/* a */ x := 1 /* b */\n\t// c\n\tg(x) // d\n\t// e\n}\n";
+    check(src, src);
+}
+
+#[test]
+fn reformat_doc_comments() {
+    check(
+        "package p\n\n//!+input\n//Doc of f.\n//   code\nfunc f() {}\n",
+        "package p\n\n// !+input\n// Doc of f.\n//\n//\tcode\nfunc f() {}\n",
+    );
+}
+
+#[test]
+fn keep_directives_after_doc_comments() {
+    check(
+        "package p\n\n//go:noinline\n// F is f.\nfunc F() {}\n",
+        "package p\n\n// F is f.\n//\n//go:noinline\nfunc F() {}\n",
+    );
+}
+
+#[test]
+fn reindent_block_comments() {
+    check(
+        "package p\n\nfunc f() {\n        /*\n         * a\n         * b\n         */\n\tg()\n}\n",
+        "package p\n\nfunc f() {\n\t/*\n\t * a\n\t * b\n\t */\n\tg()\n}\n",
+    );
+}
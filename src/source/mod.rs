@@ -0,0 +1,195 @@
+#[cfg(test)]
+mod test;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileId(pub u32);
+
+// A half-open byte range `start..end` in a source file. `line` and `column` are the
+// 1-based position of `start`, with columns counted in bytes as Go does.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub file: FileId,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(file: FileId, start: usize, end: usize, line: usize, column: usize) -> Span {
+        Span {
+            file,
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        return self.end - self.start;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.start == self.end;
+    }
+
+    // Returns the smallest span covering both `self` and `other`.
+    pub fn to(&self, other: Span) -> Span {
+        if other.start < self.start {
+            return other.to(*self);
+        }
+        return Span {
+            end: usize::max(self.end, other.end),
+            ..*self
+        };
+    }
+}
+
+// Maps byte offsets to line and column numbers without rescanning the source.
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+    len: usize,
+}
+
+impl LineIndex {
+    pub fn new(src: &[u8]) -> LineIndex {
+        let mut line_starts = vec![0];
+        for (i, &c) in src.iter().enumerate() {
+            if c == b'\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        LineIndex {
+            line_starts,
+            len: src.len(),
+        }
+    }
+
+    pub fn line_count(&self) -> usize {
+        return self.line_starts.len();
+    }
+
+    // Returns the 1-based line and column of `offset`. Offsets past the end of the
+    // source are clamped to the end.
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = usize::min(offset, self.len);
+        let line = match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        };
+        return (line + 1, offset - self.line_starts[line] + 1);
+    }
+
+    // Returns the byte range of a 1-based line, excluding its newline.
+    pub fn line_range(&self, line: usize) -> Option<(usize, usize)> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = match self.line_starts.get(line) {
+            Some(next) => next - 1,
+            None => self.len,
+        };
+        return Some((start, end));
+    }
+
+    pub fn span(&self, file: FileId, start: usize, end: usize) -> Span {
+        let (line, column) = self.line_col(start);
+        return Span::new(file, start, end, line, column);
+    }
+}
+
+// A `//line` or `/*line*/` directive. From `offset` on, positions are reported as if
+// the source at `offset` were at `line` and `column` of `file`. Without a column, columns
+// are reported unchanged; without a file, the previous name stays in effect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineDirective {
+    pub offset: usize,
+    pub file: Option<String>,
+    pub line: usize,
+    pub column: Option<usize>,
+}
+
+pub struct SourceFile {
+    pub id: FileId,
+    pub name: String,
+    pub src: Vec<u8>,
+    pub lines: LineIndex,
+    pub line_directives: Vec<LineDirective>,
+}
+
+impl SourceFile {
+    // The text of a 1-based line, without its newline or a trailing carriage return.
+    pub fn line_text(&self, line: usize) -> Option<&[u8]> {
+        let (start, end) = self.lines.line_range(line)?;
+        let text = &self.src[start..end];
+        return Some(text.strip_suffix(b"\r").unwrap_or(text));
+    }
+
+    // Returns the file name, line and column that diagnostics should report for
+    // `offset`, after applying any line directives that precede it.
+    pub fn presumed(&self, offset: usize) -> (&str, usize, usize) {
+        let (line, column) = self.lines.line_col(offset);
+        let mut name = self.name.as_str();
+        let mut current: Option<&LineDirective> = None;
+        for directive in self
+            .line_directives
+            .iter()
+            .take_while(|d| d.offset <= offset)
+        {
+            if let Some(file) = &directive.file {
+                name = file;
+            }
+            current = Some(directive);
+        }
+        let directive = match current {
+            Some(directive) => directive,
+            None => return (name, line, column),
+        };
+
+        let (base_line, base_column) = self.lines.line_col(directive.offset);
+        let presumed_column = match directive.column {
+            Some(start) if line == base_line => start + column - base_column,
+            _ => column,
+        };
+        return (name, directive.line + line - base_line, presumed_column);
+    }
+}
+
+// Owns the source of every file in a compilation so that diagnostics can quote it.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap { files: Vec::new() }
+    }
+
+    pub fn add(&mut self, name: &str, src: Vec<u8>) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        let lines = LineIndex::new(&src);
+        self.files.push(SourceFile {
+            id,
+            name: name.to_string(),
+            src,
+            lines,
+            line_directives: Vec::new(),
+        });
+        return id;
+    }
+
+    // Records the line directives of a file, in source order.
+    pub fn set_line_directives(&mut self, id: FileId, directives: Vec<LineDirective>) {
+        if let Some(file) = self.files.get_mut(id.0 as usize) {
+            file.line_directives = directives;
+        }
+    }
+
+    pub fn get(&self, id: FileId) -> Option<&SourceFile> {
+        return self.files.get(id.0 as usize);
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        return &self.files;
+    }
+}
@@ -0,0 +1,95 @@
+use crate::source::{FileId, LineDirective, LineIndex, SourceMap, Span};
+
+#[test]
+fn line_col_of_offsets() {
+    let index = LineIndex::new(b"package main\n\nfunc main() {\n}");
+
+    assert_eq!(index.line_count(), 4);
+    assert_eq!(index.line_col(0), (1, 1));
+    assert_eq!(index.line_col(8), (1, 9));
+    assert_eq!(index.line_col(12), (1, 13));
+    assert_eq!(index.line_col(13), (2, 1));
+    assert_eq!(index.line_col(14), (3, 1));
+    assert_eq!(index.line_col(19), (3, 6));
+    assert_eq!(index.line_col(28), (4, 1));
+    assert_eq!(index.line_col(100), (4, 2));
+}
+
+#[test]
+fn line_ranges() {
+    let index = LineIndex::new(b"a\nbc\n");
+
+    assert_eq!(index.line_range(0), None);
+    assert_eq!(index.line_range(1), Some((0, 1)));
+    assert_eq!(index.line_range(2), Some((2, 4)));
+    assert_eq!(index.line_range(3), Some((5, 5)));
+    assert_eq!(index.line_range(4), None);
+}
+
+#[test]
+fn span_from_index() {
+    let index = LineIndex::new(b"x :=\n  42");
+    let span = index.span(FileId(3), 7, 9);
+
+    assert_eq!(span, Span::new(FileId(3), 7, 9, 2, 3));
+    assert_eq!(span.len(), 2);
+}
+
+#[test]
+fn join_spans() {
+    let a = Span::new(FileId(0), 4, 6, 1, 5);
+    let b = Span::new(FileId(0), 10, 12, 2, 1);
+
+    assert_eq!(a.to(b), Span::new(FileId(0), 4, 12, 1, 5));
+    assert_eq!(b.to(a), Span::new(FileId(0), 4, 12, 1, 5));
+}
+
+#[test]
+fn source_map_files() {
+    let mut sources = SourceMap::new();
+    let a = sources.add("a.go", b"package a\r\nvar x = 1\n".to_vec());
+    let b = sources.add("b.go", b"package b".to_vec());
+
+    assert_eq!(a, FileId(0));
+    assert_eq!(b, FileId(1));
+    assert_eq!(sources.get(b).unwrap().name, "b.go");
+    assert!(sources.get(FileId(2)).is_none());
+
+    let file = sources.get(a).unwrap();
+    assert_eq!(file.line_text(1), Some(&b"package a"[..]));
+    assert_eq!(file.line_text(2), Some(&b"var x = 1"[..]));
+    assert_eq!(file.line_text(3), Some(&b""[..]));
+    assert_eq!(file.line_text(4), None);
+}
+
+#[test]
+fn presumed_positions_follow_line_directives() {
+    let mut sources = SourceMap::new();
+    let src = b"a\n//line gen.y:10\nb\nc /*line :20:5*/d e\nf\n".to_vec();
+    let id = sources.add("x.go", src);
+    sources.set_line_directives(
+        id,
+        vec![
+            LineDirective {
+                offset: 18,
+                file: Some("gen.y".to_string()),
+                line: 10,
+                column: None,
+            },
+            LineDirective {
+                offset: 36,
+                file: None,
+                line: 20,
+                column: Some(5),
+            },
+        ],
+    );
+    let file = sources.get(id).unwrap();
+
+    assert_eq!(file.presumed(0), ("x.go", 1, 1));
+    assert_eq!(file.presumed(18), ("gen.y", 10, 1));
+    assert_eq!(file.presumed(20), ("gen.y", 11, 1));
+    assert_eq!(file.presumed(36), ("gen.y", 20, 5));
+    assert_eq!(file.presumed(38), ("gen.y", 20, 7));
+    assert_eq!(file.presumed(40), ("gen.y", 21, 1));
+}
@@ -0,0 +1,9 @@
+package main
+
+func main() {
+	x := 0x1e3p + 1__0
+	s := "tab\q
+	r := 'ab'
+	€
+}
+/* never closed
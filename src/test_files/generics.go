@@ -0,0 +1,45 @@
+package main
+
+import "fmt"
+
+type Number interface {
+	~int | ~int64 | ~float64
+}
+
+type List[T any] struct {
+	next  *List[T]
+	value T
+}
+
+type Pair[K comparable, V any] struct {
+	Key   K
+	Value V
+}
+
+func (l *List[T]) Push(v T) *List[T] {
+	return &List[T]{next: l, value: v}
+}
+
+func Map[T, U any](xs []T, f func(T) U) []U {
+	ys := make([]U, 0, len(xs))
+	for _, x := range xs {
+		ys = append(ys, f(x))
+	}
+	return ys
+}
+
+func Sum[S ~[]E, E Number](xs S) E {
+	var sum E
+	for _, x := range xs {
+		sum += x
+	}
+	return sum
+}
+
+func main() {
+	strs := Map[int, string]([]int{1, 2, 3}, func(i int) string {
+		return fmt.Sprint(i)
+	})
+	pairs := []Pair[string, int]{{"a", 1}}
+	fmt.Println(strs, pairs, Sum([]float64{1.5, 2.5}))
+}
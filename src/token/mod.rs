@@ -1,30 +1,49 @@
+use crate::diagnostic::Diagnostic;
+use crate::intern::Symbol;
+use crate::source::Span;
 use std::fmt;
-use std::str;
 
-pub struct Token {
+// A token borrows its lexeme from the source buffer, so scanning one does not allocate.
+// Only the decoded `value` of string and rune literals is owned.
+#[derive(Clone)]
+pub struct Token<'src> {
     pub token_type: TokenTypes,
-    pub lexeme: Vec<u8>,
-    pub line: usize,
+    pub lexeme: &'src [u8],
+    pub value: Option<Vec<u8>>,
+    // The interned name of an identifier or keyword.
+    pub symbol: Option<Symbol>,
+    // The error reported for an Illegal token.
+    pub error: Option<Box<Diagnostic>>,
+    pub span: Span,
 }
 
-impl Token {
-    pub fn new(token_type: TokenTypes, lexeme: Vec<u8>, line: usize) -> Token {
+impl<'src> Token<'src> {
+    pub fn new(token_type: TokenTypes, lexeme: &'src [u8], span: Span) -> Token<'src> {
         Token {
             token_type,
             lexeme,
-            line,
+            value: None,
+            symbol: None,
+            error: None,
+            span,
         }
     }
 }
 
-impl fmt::Display for Token {
+impl fmt::Display for Token<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        return write!(f, "Lexeme: {:?}, Line: {}, Type: {:?}",str::from_utf8(&self.lexeme).unwrap(), self.line, self.token_type);
+        return write!(
+            f,
+            "Lexeme: {:?}, Line: {}, Column: {}, Type: {:?}",
+            String::from_utf8_lossy(self.lexeme),
+            self.span.line,
+            self.span.column,
+            self.token_type
+        );
     }
 }
 
-#[derive(Debug)]
-#[derive(PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TokenTypes {
     // Keywords
     Break,
@@ -101,7 +120,7 @@ pub enum TokenTypes {
     LshiftAssign,
     Rshift,
     RshiftAssign,
-    
+    Tilde,
 
     // Literals
     Int,
@@ -110,6 +129,7 @@ pub enum TokenTypes {
     Binary,
     Float,
     Imag,
+    Char,
     String,
     RawString,
     False,
@@ -118,5 +138,125 @@ pub enum TokenTypes {
 
     // Misc
     Identifier,
+    Comment,
+    DocComment,
+    Illegal,
     Eof,
 }
+
+impl TokenTypes {
+    // The name go/scanner uses for this kind of token, so that token dumps can be
+    // compared with the output of the Go toolchain.
+    pub fn go_name(&self) -> &'static str {
+        return match self {
+            TokenTypes::Break => "break",
+            TokenTypes::Default => "default",
+            TokenTypes::Func => "func",
+            TokenTypes::Interface => "interface",
+            TokenTypes::Select => "select",
+            TokenTypes::Case => "case",
+            TokenTypes::Defer => "defer",
+            TokenTypes::Go => "go",
+            TokenTypes::Map => "map",
+            TokenTypes::Struct => "struct",
+            TokenTypes::Chan => "chan",
+            TokenTypes::Else => "else",
+            TokenTypes::Goto => "goto",
+            TokenTypes::Package => "package",
+            TokenTypes::Switch => "switch",
+            TokenTypes::Const => "const",
+            TokenTypes::Fallthrough => "fallthrough",
+            TokenTypes::If => "if",
+            TokenTypes::Range => "range",
+            TokenTypes::Type => "type",
+            TokenTypes::Continue => "continue",
+            TokenTypes::For => "for",
+            TokenTypes::Import => "import",
+            TokenTypes::Return => "return",
+            TokenTypes::Var => "var",
+            TokenTypes::Dot => ".",
+            TokenTypes::Add => "+",
+            TokenTypes::Sub => "-",
+            TokenTypes::Mul => "*",
+            TokenTypes::Quo => "/",
+            TokenTypes::And => "&&",
+            TokenTypes::Amp => "&",
+            TokenTypes::Or => "|",
+            TokenTypes::OrOr => "||",
+            TokenTypes::AddAssign => "+=",
+            TokenTypes::SubAssign => "-=",
+            TokenTypes::MulAssign => "*=",
+            TokenTypes::QuoAssign => "/=",
+            TokenTypes::AndAssign => "&=",
+            TokenTypes::OrAssign => "|=",
+            TokenTypes::Arrow => "<-",
+            TokenTypes::Eql => "==",
+            TokenTypes::Lss => "<",
+            TokenTypes::Gtr => ">",
+            TokenTypes::Assign => "=",
+            TokenTypes::Not => "!",
+            TokenTypes::Neq => "!=",
+            TokenTypes::Leq => "<=",
+            TokenTypes::Geq => ">=",
+            TokenTypes::Define => ":=",
+            TokenTypes::Ellipsis => "...",
+            TokenTypes::LParen => "(",
+            TokenTypes::LBrack => "[",
+            TokenTypes::LBrace => "{",
+            TokenTypes::Comma => ",",
+            TokenTypes::RParen => ")",
+            TokenTypes::RBrack => "]",
+            TokenTypes::RBrace => "}",
+            TokenTypes::Semicolon => ";",
+            TokenTypes::Colon => ":",
+            TokenTypes::Caret => "^",
+            TokenTypes::Percent => "%",
+            TokenTypes::CaretAssign => "^=",
+            TokenTypes::PercentAssign => "%=",
+            TokenTypes::BitClear => "&^",
+            TokenTypes::BitClearAssign => "&^=",
+            TokenTypes::Increment => "++",
+            TokenTypes::Decrement => "--",
+            TokenTypes::Lshift => "<<",
+            TokenTypes::LshiftAssign => "<<=",
+            TokenTypes::Rshift => ">>",
+            TokenTypes::RshiftAssign => ">>=",
+            TokenTypes::Tilde => "~",
+            TokenTypes::Int | TokenTypes::Octal | TokenTypes::Hex | TokenTypes::Binary => "INT",
+            TokenTypes::Float => "FLOAT",
+            TokenTypes::Imag => "IMAG",
+            TokenTypes::Char => "CHAR",
+            TokenTypes::String | TokenTypes::RawString => "STRING",
+            TokenTypes::False | TokenTypes::True | TokenTypes::Nil | TokenTypes::Identifier => {
+                "IDENT"
+            }
+            TokenTypes::Comment | TokenTypes::DocComment => "COMMENT",
+            TokenTypes::Illegal => "ILLEGAL",
+            TokenTypes::Eof => "EOF",
+        };
+    }
+
+    // The precedence of a binary operator, from 1 for `||` to 5 for the multiplicative
+    // operators, or 0 for any other token.
+    pub fn precedence(&self) -> u8 {
+        return match self {
+            TokenTypes::OrOr => 1,
+            TokenTypes::And => 2,
+            TokenTypes::Eql
+            | TokenTypes::Neq
+            | TokenTypes::Lss
+            | TokenTypes::Leq
+            | TokenTypes::Gtr
+            | TokenTypes::Geq => 3,
+            TokenTypes::Add | TokenTypes::Sub | TokenTypes::Or | TokenTypes::Caret => 4,
+            TokenTypes::Mul
+            | TokenTypes::Quo
+            | TokenTypes::Percent
+            | TokenTypes::Lshift
+            | TokenTypes::Rshift
+            | TokenTypes::Amp
+            | TokenTypes::BitClear => 5,
+            _ => 0,
+        };
+    }
+}